@@ -0,0 +1,568 @@
+use crate::edge::Edge;
+use crate::Pos;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+
+/// Disjoint sets of anchors, merged as edges connect them.
+#[derive(Clone, Debug)]
+pub(crate) struct UnionFind {
+    parent: Vec<usize>,
+    rank: Vec<u8>,
+}
+
+impl UnionFind {
+    /// Creates `count` sets with one element each.
+    pub(crate) fn new(count: usize) -> Self {
+        Self {
+            parent: (0..count).collect(),
+            rank: vec![0; count],
+        }
+    }
+
+    /// Returns the representative of the set containing `element`.
+    pub(crate) fn find(&mut self, element: usize) -> usize {
+        let mut root = element;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        // Point everything on the way directly at the root
+        let mut current = element;
+        while self.parent[current] != root {
+            current = std::mem::replace(&mut self.parent[current], root);
+        }
+        root
+    }
+
+    /// Merges the sets containing `a` and `b`.
+    ///
+    /// # Returns
+    /// `false` if they already were in the same set
+    pub(crate) fn union(&mut self, a: usize, b: usize) -> bool {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        match self.rank[a].cmp(&self.rank[b]) {
+            Ordering::Less => self.parent[a] = b,
+            Ordering::Greater => self.parent[b] = a,
+            Ordering::Equal => {
+                self.parent[b] = a;
+                self.rank[a] += 1;
+            }
+        }
+        true
+    }
+}
+
+/// How the length of a path is measured.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Metric {
+    /// Every edge counts as one step
+    Hops,
+    /// The distance between the anchors of each edge
+    Euclidean,
+    /// The weight property of each edge
+    Weight,
+}
+
+impl Metric {
+    /// All metrics, in the order they are offered in the UI.
+    pub(crate) const ALL: [Metric; 3] = [Metric::Hops, Metric::Euclidean, Metric::Weight];
+
+    /// Returns the name shown in the UI.
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Metric::Hops => "Fewest edges",
+            Metric::Euclidean => "Shortest distance",
+            Metric::Weight => "Lowest weight",
+        }
+    }
+}
+
+/// A path between two anchors.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Path {
+    /// The anchors along the path, from start to end
+    pub(crate) anchors: Vec<usize>,
+    /// The edges along the path, as indices into the edge list
+    pub(crate) edges: Vec<usize>,
+    /// The length of the path in the metric it was found with
+    pub(crate) length: f32,
+}
+
+/// Degree statistics of the anchors.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct DegreeStats {
+    pub(crate) min: usize,
+    pub(crate) max: usize,
+    pub(crate) mean: f32,
+    /// Number of anchors without edges
+    pub(crate) isolated: usize,
+}
+
+/// The bridges and articulation points of a graph.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct CutSets {
+    /// Edges whose removal disconnects their anchors, as indices into the edge list
+    pub(crate) bridges: Vec<usize>,
+    /// Anchors whose removal splits their component
+    pub(crate) articulation_points: Vec<usize>,
+}
+
+/// A queue entry of Dijkstra's algorithm, ordered so the heap pops the
+/// shortest distance first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Candidate {
+    distance: f32,
+    anchor: usize,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.distance.total_cmp(&self.distance).then_with(|| other.anchor.cmp(&self.anchor))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A read-only view of the board for graph queries.
+///
+/// Built from the anchors' positions and the edges; it is cheap enough to
+/// build whenever a query is needed.
+#[derive(Clone, Debug)]
+pub(crate) struct Graph<'a> {
+    positions: Vec<Pos>,
+    edges: &'a [Edge],
+    /// For each anchor, its neighbours and the edges leading to them
+    neighbours: Vec<Vec<(usize, usize)>>,
+}
+
+impl<'a> Graph<'a> {
+    /// Builds the graph of anchors at `positions` connected by `edges`.
+    pub(crate) fn new(positions: Vec<Pos>, edges: &'a [Edge]) -> Self {
+        Self::filtered(positions, edges, |_| true)
+    }
+
+    /// Builds the graph of anchors at `positions` connected by the edges
+    /// `keep` accepts. Edges keep their indices into `edges`.
+    pub(crate) fn filtered(positions: Vec<Pos>, edges: &'a [Edge], keep: impl Fn(&Edge) -> bool) -> Self {
+        let mut neighbours = vec![Vec::new(); positions.len()];
+        for (index, edge) in edges.iter().enumerate().filter(|(_, edge)| keep(edge)) {
+            neighbours[edge.from].push((edge.to, index));
+            neighbours[edge.to].push((edge.from, index));
+        }
+        Self { positions, edges, neighbours }
+    }
+
+    /// Returns the number of edges connected to an anchor.
+    pub(crate) fn degree(&self, anchor: usize) -> usize {
+        self.neighbours[anchor].len()
+    }
+
+    /// Returns the component of each anchor, numbered from 0 in the order
+    /// the components are first met.
+    pub(crate) fn components(&self) -> Vec<usize> {
+        let mut sets = UnionFind::new(self.positions.len());
+        for (anchor, neighbours) in self.neighbours.iter().enumerate() {
+            for &(next, _) in neighbours {
+                sets.union(anchor, next);
+            }
+        }
+
+        let mut numbers = vec![usize::MAX; self.positions.len()];
+        let mut next = 0;
+        (0..self.positions.len())
+            .map(|anchor| {
+                let root = sets.find(anchor);
+                if numbers[root] == usize::MAX {
+                    numbers[root] = next;
+                    next += 1;
+                }
+                numbers[root]
+            })
+            .collect()
+    }
+
+    /// Returns the number of components. An anchor without edges is a component of its own.
+    pub(crate) fn component_count(&self) -> usize {
+        self.components().into_iter().max().map_or(0, |last| last + 1)
+    }
+
+    /// Returns the length of an edge in the given metric.
+    fn edge_length(&self, edge: usize, metric: Metric) -> f32 {
+        let Edge { from, to, props } = &self.edges[edge];
+        match metric {
+            Metric::Hops => 1.0,
+            Metric::Euclidean => self.positions[*from].distance(&self.positions[*to]),
+            // Negative weights would break Dijkstra's algorithm
+            Metric::Weight => props.weight.max(0.0),
+        }
+    }
+
+    /// Finds the shortest path between two anchors.
+    ///
+    /// Counting hops uses a breadth-first search, the other metrics Dijkstra's algorithm.
+    ///
+    /// # Returns
+    /// The path, or `None` if the anchors are not connected
+    pub(crate) fn shortest_path(&self, from: usize, to: usize, metric: Metric) -> Option<Path> {
+        // For each reached anchor, the anchor and edge it was reached through
+        let mut previous: Vec<Option<(usize, usize)>> = vec![None; self.positions.len()];
+        let mut distance = vec![f32::INFINITY; self.positions.len()];
+        distance[from] = 0.0;
+
+        if metric == Metric::Hops {
+            let mut queue = VecDeque::from([from]);
+            while let Some(anchor) = queue.pop_front() {
+                if anchor == to {
+                    break;
+                }
+                for &(next, edge) in &self.neighbours[anchor] {
+                    if distance[next].is_infinite() {
+                        distance[next] = distance[anchor] + 1.0;
+                        previous[next] = Some((anchor, edge));
+                        queue.push_back(next);
+                    }
+                }
+            }
+        } else {
+            let mut heap = BinaryHeap::from([Candidate { distance: 0.0, anchor: from }]);
+            while let Some(Candidate { distance: reached, anchor }) = heap.pop() {
+                if anchor == to {
+                    break;
+                }
+                if reached > distance[anchor] {
+                    continue;
+                }
+                for &(next, edge) in &self.neighbours[anchor] {
+                    let through = reached + self.edge_length(edge, metric);
+                    if through < distance[next] {
+                        distance[next] = through;
+                        previous[next] = Some((anchor, edge));
+                        heap.push(Candidate { distance: through, anchor: next });
+                    }
+                }
+            }
+        }
+
+        if distance[to].is_infinite() {
+            return None;
+        }
+        let mut anchors = vec![to];
+        let mut edges = Vec::new();
+        while let Some((anchor, edge)) = previous[*anchors.last()?] {
+            anchors.push(anchor);
+            edges.push(edge);
+        }
+        anchors.reverse();
+        edges.reverse();
+        Some(Path { anchors, edges, length: distance[to] })
+    }
+
+    /// Finds the path with the fewest edges from any anchor in `from` to any anchor in `to`.
+    ///
+    /// # Returns
+    /// The path, or `None` if no anchor of `to` is connected to one of `from`
+    pub(crate) fn nearest_path(&self, from: &[usize], to: &[usize]) -> Option<Path> {
+        let mut is_target = vec![false; self.positions.len()];
+        for &anchor in to {
+            is_target[anchor] = true;
+        }
+
+        // For each reached anchor, the anchor and edge it was reached through
+        let mut previous: Vec<Option<(usize, usize)>> = vec![None; self.positions.len()];
+        let mut reached = vec![false; self.positions.len()];
+        let mut queue = VecDeque::new();
+        for &anchor in from {
+            if !reached[anchor] {
+                reached[anchor] = true;
+                queue.push_back(anchor);
+            }
+        }
+
+        let end = loop {
+            let anchor = queue.pop_front()?;
+            if is_target[anchor] {
+                break anchor;
+            }
+            for &(next, edge) in &self.neighbours[anchor] {
+                if !reached[next] {
+                    reached[next] = true;
+                    previous[next] = Some((anchor, edge));
+                    queue.push_back(next);
+                }
+            }
+        };
+
+        let mut anchors = vec![end];
+        let mut edges = Vec::new();
+        while let Some((anchor, edge)) = previous[*anchors.last()?] {
+            anchors.push(anchor);
+            edges.push(edge);
+        }
+        anchors.reverse();
+        edges.reverse();
+        let length = edges.len() as f32;
+        Some(Path { anchors, edges, length })
+    }
+
+    /// Finds a cycle, if the graph has one.
+    ///
+    /// # Returns
+    /// The edges of a cycle, as indices into the edge list
+    pub(crate) fn find_cycle(&self) -> Option<Vec<usize>> {
+        // For each visited anchor, the anchor and edge it was reached through
+        let mut parent: Vec<Option<Option<(usize, usize)>>> = vec![None; self.positions.len()];
+        for root in 0..self.positions.len() {
+            if parent[root].is_some() {
+                continue;
+            }
+            parent[root] = Some(None);
+            let mut stack = vec![root];
+            while let Some(anchor) = stack.pop() {
+                let arrived_by = parent[anchor].flatten().map(|(_, edge)| edge);
+                for &(next, edge) in &self.neighbours[anchor] {
+                    if Some(edge) == arrived_by {
+                        continue;
+                    }
+                    if parent[next].is_none() {
+                        parent[next] = Some(Some((anchor, edge)));
+                        stack.push(next);
+                        continue;
+                    }
+
+                    // Reached a visited anchor another way: walk both back to where they meet
+                    let ancestors = |mut anchor: usize| {
+                        let mut chain = vec![(anchor, None)];
+                        while let Some(Some((up, edge))) = parent[anchor] {
+                            chain.push((up, Some(edge)));
+                            anchor = up;
+                        }
+                        chain
+                    };
+                    let (ours, theirs) = (ancestors(anchor), ancestors(next));
+                    let meet = ours.iter().position(|(a, _)| theirs.iter().any(|(b, _)| a == b))?;
+                    let meet_anchor = ours[meet].0;
+                    let theirs_meet = theirs.iter().position(|(b, _)| *b == meet_anchor)?;
+                    let mut cycle: Vec<usize> = ours[1..=meet].iter().filter_map(|(_, edge)| *edge).collect();
+                    cycle.extend(theirs[1..=theirs_meet].iter().filter_map(|(_, edge)| *edge));
+                    cycle.push(edge);
+                    return Some(cycle);
+                }
+            }
+        }
+        None
+    }
+
+    /// Finds the bridges and articulation points with Tarjan's algorithm.
+    pub(crate) fn cut_sets(&self) -> CutSets {
+        const UNVISITED: usize = usize::MAX;
+        let count = self.positions.len();
+        let mut order = vec![UNVISITED; count];
+        let mut low = vec![0; count];
+        let mut is_articulation = vec![false; count];
+        let mut bridges = Vec::new();
+        let mut time = 0;
+
+        for root in 0..count {
+            if order[root] != UNVISITED {
+                continue;
+            }
+            order[root] = time;
+            low[root] = time;
+            time += 1;
+            let mut root_children = 0;
+
+            // Anchor, edge it was reached by, and how many neighbours were looked at.
+            // Iterating instead of recursing keeps large boards off the call stack.
+            let mut stack: Vec<(usize, Option<usize>, usize)> = vec![(root, None, 0)];
+            while let Some(&mut (anchor, arrived_by, ref mut next_neighbour)) = stack.last_mut() {
+                if let Some(&(next, edge)) = self.neighbours[anchor].get(*next_neighbour) {
+                    *next_neighbour += 1;
+                    if Some(edge) == arrived_by {
+                        continue;
+                    }
+                    if order[next] == UNVISITED {
+                        order[next] = time;
+                        low[next] = time;
+                        time += 1;
+                        if anchor == root {
+                            root_children += 1;
+                        }
+                        stack.push((next, Some(edge), 0));
+                    } else {
+                        low[anchor] = low[anchor].min(order[next]);
+                    }
+                    continue;
+                }
+
+                stack.pop();
+                if let (Some(edge), Some(&(parent, _, _))) = (arrived_by, stack.last()) {
+                    low[parent] = low[parent].min(low[anchor]);
+                    if low[anchor] > order[parent] {
+                        bridges.push(edge);
+                    }
+                    if parent != root && low[anchor] >= order[parent] {
+                        is_articulation[parent] = true;
+                    }
+                }
+            }
+            is_articulation[root] = root_children > 1;
+        }
+
+        bridges.sort_unstable();
+        CutSets {
+            bridges,
+            articulation_points: (0..count).filter(|anchor| is_articulation[*anchor]).collect(),
+        }
+    }
+
+    /// Returns statistics of the anchors' degrees.
+    pub(crate) fn degree_stats(&self) -> DegreeStats {
+        let degrees = (0..self.positions.len()).map(|anchor| self.degree(anchor));
+        if self.positions.is_empty() {
+            return DegreeStats::default();
+        }
+        DegreeStats {
+            min: degrees.clone().min().unwrap_or(0),
+            max: degrees.clone().max().unwrap_or(0),
+            mean: degrees.clone().sum::<usize>() as f32 / self.positions.len() as f32,
+            isolated: degrees.filter(|degree| *degree == 0).count(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edge::EdgeProps;
+
+    fn graph(edges: &[Edge]) -> Graph<'_> {
+        let positions = vec![
+            Pos::new(0.0, 0.0),
+            Pos::new(10.0, 0.0),
+            Pos::new(10.0, 10.0),
+            Pos::new(0.0, 10.0),
+            Pos::new(20.0, 10.0),
+            Pos::new(30.0, 10.0),
+        ];
+        Graph::new(positions, edges)
+    }
+
+    /// A square 0-1-2-3 with a heavy diagonal 0-2, and a tail 2-4-5.
+    fn edges() -> Vec<Edge> {
+        vec![
+            Edge::new(0, 1),
+            Edge::new(1, 2),
+            Edge::new(2, 3),
+            Edge::new(3, 0),
+            Edge::with_props(0, 2, EdgeProps { weight: 5.0, ..EdgeProps::default() }),
+            Edge::new(2, 4),
+            Edge::new(4, 5),
+        ]
+    }
+
+    #[test]
+    fn test_union_find() {
+        let mut sets = UnionFind::new(4);
+        assert!(sets.union(0, 1));
+        assert!(sets.union(2, 3));
+        assert!(!sets.union(1, 0));
+        assert_ne!(sets.find(0), sets.find(3));
+        assert!(sets.union(1, 3));
+        assert_eq!(sets.find(0), sets.find(2));
+    }
+
+    #[test]
+    fn test_components() {
+        let edges = vec![Edge::new(0, 1), Edge::new(2, 3), Edge::new(3, 4)];
+        let graph = graph(&edges);
+        assert_eq!(graph.components(), vec![0, 0, 1, 1, 1, 2]);
+        assert_eq!(graph.component_count(), 3);
+    }
+
+    #[test]
+    fn test_shortest_paths_by_metric() {
+        let edges = edges();
+        let graph = graph(&edges);
+
+        let hops = graph.shortest_path(0, 5, Metric::Hops).unwrap();
+        assert_eq!(hops.anchors, vec![0, 2, 4, 5]);
+        assert_eq!(hops.edges, vec![4, 5, 6]);
+        assert_eq!(hops.length, 3.0);
+
+        // The diagonal is shorter than going around the square, but heavier
+        let distance = graph.shortest_path(0, 2, Metric::Euclidean).unwrap();
+        assert_eq!(distance.edges, vec![4]);
+        assert!((distance.length - 200f32.sqrt()).abs() < 1e-4);
+        let weight = graph.shortest_path(0, 2, Metric::Weight).unwrap();
+        assert_eq!(weight.anchors.len(), 3);
+        assert_eq!(weight.length, 2.0);
+
+        assert_eq!(graph.shortest_path(3, 3, Metric::Hops).unwrap().anchors, vec![3]);
+        let lone = Graph::new(vec![Pos::new(0.0, 0.0), Pos::new(1.0, 0.0)], &[]);
+        assert_eq!(lone.shortest_path(0, 1, Metric::Euclidean), None);
+    }
+
+    #[test]
+    fn test_nearest_path_between_groups() {
+        let edges = edges();
+        let graph = graph(&edges);
+        let path = graph.nearest_path(&[1, 3], &[4, 5]).unwrap();
+        assert_eq!(path.anchors.len(), 3);
+        assert_eq!(path.anchors[2], 4);
+        assert_eq!(graph.nearest_path(&[0, 5], &[5]).unwrap().edges, Vec::<usize>::new());
+
+        // Only the kept edges count
+        let roads = Graph::filtered(graph.positions.clone(), &edges, |edge| edge.props.weight < 2.0 && !edge.touches(4));
+        assert_eq!(roads.nearest_path(&[0], &[5]), None);
+        assert_eq!(roads.components(), vec![0, 0, 0, 0, 1, 2]);
+        assert_eq!(roads.degree(0), 2);
+    }
+
+    #[test]
+    fn test_find_cycle() {
+        let tree = vec![Edge::new(0, 1), Edge::new(1, 2), Edge::new(2, 4), Edge::new(2, 3)];
+        assert_eq!(graph(&tree).find_cycle(), None);
+
+        let edges = edges();
+        let mut cycle = graph(&edges).find_cycle().unwrap();
+        // Every anchor on a cycle is entered and left once
+        let mut ends: Vec<usize> = cycle.iter().flat_map(|edge| [edges[*edge].from, edges[*edge].to]).collect();
+        ends.sort_unstable();
+        assert!(ends.chunks(2).all(|pair| pair[0] == pair[1]));
+        cycle.sort_unstable();
+        cycle.dedup();
+        assert!(cycle.len() >= 3);
+
+        // Two edges between the same anchors are a cycle too
+        let double = vec![Edge::new(0, 1), Edge::new(1, 0)];
+        assert_eq!(graph(&double).find_cycle().map(|cycle| cycle.len()), Some(2));
+    }
+
+    #[test]
+    fn test_bridges_and_articulation_points() {
+        let edges = edges();
+        let cuts = graph(&edges).cut_sets();
+        assert_eq!(cuts.bridges, vec![5, 6]);
+        assert_eq!(cuts.articulation_points, vec![2, 4]);
+
+        let path = vec![Edge::new(0, 1), Edge::new(1, 2)];
+        let cuts = graph(&path).cut_sets();
+        assert_eq!(cuts.bridges, vec![0, 1]);
+        assert_eq!(cuts.articulation_points, vec![1]);
+    }
+
+    #[test]
+    fn test_degree_stats() {
+        let edges = vec![Edge::new(0, 1), Edge::new(0, 2), Edge::new(0, 3)];
+        let stats = graph(&edges).degree_stats();
+        assert_eq!(stats, DegreeStats { min: 0, max: 3, mean: 1.0, isolated: 2 });
+    }
+}
@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+
+/// What an anchor represents on the battlefield, which decides how it is drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum AnchorKind {
+    /// A player's home, which has to be defended
+    Base,
+    /// A plain junction
+    #[default]
+    Neutral,
+    /// A source of income for whoever holds it
+    Resource,
+    /// An obstacle that no edge may connect to
+    Blocker,
+}
+
+impl AnchorKind {
+    /// All kinds, in the order they are offered in the UI.
+    pub(crate) const ALL: [AnchorKind; 4] = [AnchorKind::Base, AnchorKind::Neutral, AnchorKind::Resource, AnchorKind::Blocker];
+
+    /// Returns the name shown in the UI.
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            AnchorKind::Base => "Base",
+            AnchorKind::Neutral => "Neutral",
+            AnchorKind::Resource => "Resource",
+            AnchorKind::Blocker => "Blocker",
+        }
+    }
+}
+
+/// The game data carried by an anchor.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct AnchorProps {
+    /// What the anchor represents
+    pub(crate) kind: AnchorKind,
+    /// The player owning the anchor, if any
+    pub(crate) owner: Option<u8>,
+    /// The most edges the anchor can have, or `None` for no limit
+    pub(crate) capacity: Option<usize>,
+    /// How much damage the anchor takes before it falls
+    pub(crate) hit_points: u32,
+}
+
+impl Default for AnchorProps {
+    fn default() -> Self {
+        Self {
+            kind: AnchorKind::Neutral,
+            owner: None,
+            capacity: None,
+            hit_points: 100,
+        }
+    }
+}
+
+impl AnchorProps {
+    /// Returns `true` if an anchor with `degree` edges can take another one.
+    /// Blockers never take edges, whatever their capacity.
+    pub(crate) fn accepts_edge(&self, degree: usize) -> bool {
+        match (self.kind, self.capacity) {
+            (AnchorKind::Blocker, _) => false,
+            (_, Some(capacity)) => degree < capacity,
+            (_, None) => true,
+        }
+    }
+
+    /// Returns `true` if an anchor may keep `degree` edges it already has.
+    pub(crate) fn allows_degree(&self, degree: usize) -> bool {
+        degree == 0 || self.accepts_edge(degree - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capacity_limits_edges() {
+        let props = AnchorProps { capacity: Some(2), ..AnchorProps::default() };
+        assert!(props.accepts_edge(1));
+        assert!(!props.accepts_edge(2));
+        assert!(AnchorProps::default().accepts_edge(1000));
+
+        assert!(props.allows_degree(2));
+        assert!(!props.allows_degree(3));
+
+        let blocker = AnchorProps { kind: AnchorKind::Blocker, ..AnchorProps::default() };
+        assert!(!blocker.accepts_edge(0));
+        assert!(blocker.allows_degree(0));
+        assert!(!blocker.allows_degree(1));
+    }
+}
@@ -1,46 +1,447 @@
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::Stream;
 use cpal::{FromSample, SizedSample};
+use ringbuf::{Consumer, Producer, RingBuffer};
 use std::borrow::BorrowMut;
-use std::f32::consts::PI;
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
+/// How quickly a voice follows changes of its pan and gain, per sample.
+const PLACEMENT_SMOOTHING: f32 = 0.001;
+/// How strongly a voice is attenuated towards the edges of the board.
+const DISTANCE_ROLLOFF: f32 = 0.6;
+/// Preferred buffer size in frames; larger buffers reduce audio artifacts.
+const PREFERRED_BUFFER_SIZE: u32 = 2048;
+/// Seconds of output the recording buffer holds before samples are dropped.
+const RECORD_BUFFER_SECONDS: u32 = 2;
+/// Sample rates offered in the Settings window when a device supports them.
+const COMMON_SAMPLE_RATES: [u32; 7] = [22050, 32000, 44100, 48000, 88200, 96000, 192000];
+
+/// Upper bound for the channels a stream is mixed into; further channels stay silent.
+const MAX_CHANNELS: usize = 32;
+
+/// A sound generator mixed into the output stream.
+pub trait Voice: Send {
+    /// Produces the next sample of the voice.
+    ///
+    /// # Returns
+    /// `(value, pan, gain)` - the mono sample, its stereo position (-1.0 to 1.0)
+    /// and its distance gain
+    fn next_sample(&mut self) -> (f32, f32, f32);
+}
+
+impl<F> Voice for F
+where
+    F: FnMut() -> (f32, f32, f32) + Send,
+{
+    fn next_sample(&mut self) -> (f32, f32, f32) {
+        self()
+    }
+}
+
+/// The state shared between the game and the voices running on the audio thread.
+#[derive(Clone)]
+pub struct Controls {
+    /// Frequency, volume and placement of the drag hum
+    pub freq: Arc<Mutex<crate::FreqWrapper>>,
+    /// Settings and intensity of the background music
+    pub music: Arc<Mutex<crate::music::MusicParams>>,
+    /// While set, the mixer copies its output into the recording buffer
+    pub recording: Arc<AtomicBool>,
+}
+
+/// Errors that can occur while opening an audio output.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AudioError {
+    /// The host has no default output device
+    NoDefaultDevice,
+    /// The selected output device is not (or no longer) connected
+    DeviceNotFound(String),
+    /// The device does not support the requested sample rate
+    UnsupportedSampleRate(u32),
+    /// The device reported a sample format we can't produce
+    UnsupportedFormat(String),
+    /// The device could not be queried for its configuration
+    Config(String),
+    /// The output stream could not be built or started
+    Stream(String),
+}
+
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AudioError::NoDefaultDevice => write!(f, "no default output device"),
+            AudioError::DeviceNotFound(name) => write!(f, "output device \"{}\" not found", name),
+            AudioError::UnsupportedSampleRate(rate) => write!(f, "sample rate {} Hz is not supported", rate),
+            AudioError::UnsupportedFormat(format) => write!(f, "sample format {} is not supported", format),
+            AudioError::Config(err) => write!(f, "could not query device: {}", err),
+            AudioError::Stream(err) => write!(f, "could not start stream: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for AudioError {}
+
+impl From<cpal::DevicesError> for AudioError {
+    fn from(err: cpal::DevicesError) -> Self {
+        AudioError::Config(err.to_string())
+    }
+}
+
+impl From<cpal::DefaultStreamConfigError> for AudioError {
+    fn from(err: cpal::DefaultStreamConfigError) -> Self {
+        AudioError::Config(err.to_string())
+    }
+}
+
+impl From<cpal::SupportedStreamConfigsError> for AudioError {
+    fn from(err: cpal::SupportedStreamConfigsError) -> Self {
+        AudioError::Config(err.to_string())
+    }
+}
+
+impl From<cpal::BuildStreamError> for AudioError {
+    fn from(err: cpal::BuildStreamError) -> Self {
+        AudioError::Stream(err.to_string())
+    }
+}
+
+impl From<cpal::PlayStreamError> for AudioError {
+    fn from(err: cpal::PlayStreamError) -> Self {
+        AudioError::Stream(err.to_string())
+    }
+}
+
+/// Selects the output device and sample rate. `None` uses the host default.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OutputConfig {
+    /// Name of the output device
+    pub device: Option<String>,
+    /// Sample rate in Hz
+    pub sample_rate: Option<u32>,
+}
+
+impl OutputConfig {
+    /// Returns the device to fall back to when a stream was lost: the
+    /// selected one while it is still connected, otherwise the default.
+    pub fn fallback_device(&self, devices: &[String]) -> Option<String> {
+        self.device.clone().filter(|device| devices.contains(device))
+    }
+
+    /// Returns the selection moved to another device, keeping the sample rate
+    /// if the device supports it.
+    ///
+    /// # Arguments
+    /// * `device` - Name of the device, or `None` for the host default
+    /// * `rates` - The sample rates the device supports
+    pub fn on_device(&self, device: Option<String>, rates: &[u32]) -> Self {
+        Self { device, sample_rate: self.sample_rate.filter(|rate| rates.contains(rate)) }
+    }
+}
+
+/// State of the audio output as shown in the Settings window.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
+    /// No stream has been opened yet
+    Idle,
+    /// The last stream was opened on this device
+    Ready { device: String, sample_rate: u32 },
+    /// Audio could not be opened; the game keeps running silently
+    Unavailable(AudioError),
+}
+
+impl Status {
+    /// Returns the status after an attempt to open a stream.
+    ///
+    /// # Arguments
+    /// * `opened` - The device and sample rate the stream plays at, or why it could not be opened
+    fn after(opened: Result<(&str, u32), &AudioError>) -> Self {
+        match opened {
+            Ok((device, sample_rate)) => Status::Ready { device: device.to_string(), sample_rate },
+            Err(err) => Status::Unavailable(err.clone()),
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Idle => write!(f, "idle"),
+            Status::Ready { device, sample_rate } => write!(f, "{} at {} Hz", device, sample_rate),
+            Status::Unavailable(err) => write!(f, "unavailable ({})", err),
+        }
+    }
+}
+
+/// Output selection, the known devices and the resulting status.
+#[derive(Debug)]
+pub struct Output {
+    /// The device and sample rate to open streams with
+    pub config: OutputConfig,
+    /// Result of the last attempt to open a stream
+    pub status: Status,
+    /// Names of the output devices found by the last refresh
+    pub devices: Vec<String>,
+    /// Sample rates supported by the selected device
+    pub sample_rates: Vec<u32>,
+    /// Whether the devices have been enumerated yet
+    listed: bool,
+}
+
+impl Output {
+    /// Creates an output using the host defaults. Devices are enumerated lazily.
+    pub fn new() -> Self {
+        Self {
+            config: OutputConfig::default(),
+            status: Status::Idle,
+            devices: Vec::new(),
+            sample_rates: Vec::new(),
+            listed: false,
+        }
+    }
+
+    /// Re-enumerates the output devices and the rates of the selected one.
+    pub fn refresh(&mut self) {
+        self.devices = output_devices();
+        self.sample_rates = sample_rates(self.config.device.as_deref());
+        self.listed = true;
+    }
+
+    /// Enumerates the devices the first time they are shown.
+    pub fn list(&mut self) {
+        if !self.listed {
+            self.refresh();
+        }
+    }
+
+    /// Selects another device, keeping the sample rate if the device supports it.
+    ///
+    /// # Arguments
+    /// * `device` - Name of the device, or `None` for the host default
+    pub fn select_device(&mut self, device: Option<String>) {
+        self.sample_rates = sample_rates(device.as_deref());
+        self.config = self.config.on_device(device, &self.sample_rates);
+    }
+
+    /// Opens a stream playing the game's voices, recording the outcome in `status`.
+    ///
+    /// # Returns
+    /// * `Some(handle)` if the stream is playing
+    /// * `None` if audio is unavailable; the reason is kept in `status`
+    pub fn open(&mut self, controls: &Controls) -> Option<Handle> {
+        let opened = start(controls, &self.config);
+        self.status = Status::after(opened.as_ref().map(|handle| (handle.device.as_str(), handle.sample_rate)));
+        opened.ok()
+    }
+
+    /// Replaces a stream whose device failed: on the same device if it is
+    /// still connected, otherwise on the host default device. The sample rate
+    /// is kept where the device supports it.
+    ///
+    /// # Returns
+    /// The new handle, or `None` if no device is left to play on
+    pub fn reselect(&mut self, controls: &Controls) -> Option<Handle> {
+        self.devices = output_devices();
+        self.listed = true;
+        self.select_device(self.config.fallback_device(&self.devices));
+        self.open(controls)
+    }
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct Handle {
     pub stream: Stream,
+    /// Name of the device the stream plays on
+    pub device: String,
+    /// Sample rate of the stream in Hz
+    pub sample_rate: u32,
+    /// Number of interleaved channels of the stream
+    pub channels: u16,
+    /// Set by the stream's error callback when the device stops working
+    lost: Arc<AtomicBool>,
+    /// Mono copy of the output for the analyser, filled by the audio callback
+    pub tap: Consumer<f32>,
+    /// Interleaved copy of the output while recording, filled by the audio callback
+    pub record: Consumer<f32>,
+}
+
+impl Handle {
+    /// Returns `true` if the device went away or the stream failed.
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Relaxed)
+    }
+}
+
+/// Returns the names of all output devices of the default host.
+pub fn output_devices() -> Vec<String> {
+    cpal::default_host()
+        .output_devices()
+        .map(|devices| devices.filter_map(|device| device.name().ok()).collect())
+        .unwrap_or_default()
+}
+
+/// Returns the common sample rates supported by a device, including its default rate.
+///
+/// # Arguments
+/// * `device` - Name of the device, or `None` for the host default
+pub fn sample_rates(device: Option<&str>) -> Vec<u32> {
+    let Ok(device) = find_device(device) else {
+        return Vec::new();
+    };
+    let ranges: Vec<_> = device
+        .supported_output_configs()
+        .map(|configs| configs.collect())
+        .unwrap_or_default();
+
+    let mut rates: Vec<u32> = COMMON_SAMPLE_RATES
+        .iter()
+        .copied()
+        .filter(|rate| {
+            ranges
+                .iter()
+                .any(|range| range.min_sample_rate().0 <= *rate && *rate <= range.max_sample_rate().0)
+        })
+        .collect();
+    if let Ok(default_config) = device.default_output_config() {
+        rates.push(default_config.sample_rate().0);
+    }
+    rates.sort_unstable();
+    rates.dedup();
+    rates
 }
 
-pub fn beep(freq: Arc<Mutex<crate::FreqWrapper>>) -> Handle {
+fn find_device(name: Option<&str>) -> Result<cpal::Device, AudioError> {
     let host = cpal::default_host();
-    let device = host
-        .default_output_device()
-        .expect("failed to find a default output device");
-    let default_config = device.default_output_config().unwrap();
-    // Use a larger buffer size to reduce audio artifacts
-    let mut config = default_config.config();
-    config.buffer_size = cpal::BufferSize::Fixed(2048);
+    match name {
+        None => host.default_output_device().ok_or(AudioError::NoDefaultDevice),
+        Some(name) => host
+            .output_devices()?
+            .find(|device| device.name().ok().as_deref() == Some(name))
+            .ok_or_else(|| AudioError::DeviceNotFound(name.to_string())),
+    }
+}
 
-    Handle {
-        stream: match default_config.sample_format() {
-            cpal::SampleFormat::F32 => run::<f32>(&device, &config, freq),
-            cpal::SampleFormat::I16 => run::<i16>(&device, &config, freq),
-            cpal::SampleFormat::U16 => run::<u16>(&device, &config, freq),
-            // not all supported sample formats are included in this example
-            _ => panic!("Unsupported sample format!"),
-        },
+fn supported_config(
+    device: &cpal::Device,
+    sample_rate: Option<u32>,
+) -> Result<cpal::SupportedStreamConfig, AudioError> {
+    let default_config = device.default_output_config()?;
+    let rate = match sample_rate {
+        Some(rate) if rate != default_config.sample_rate().0 => rate,
+        _ => return Ok(default_config),
+    };
+
+    let ranges: Vec<_> = device
+        .supported_output_configs()?
+        .filter(|range| range.min_sample_rate().0 <= rate && rate <= range.max_sample_rate().0)
+        .collect();
+
+    // Prefer keeping the default layout and format, only changing the rate
+    ranges
+        .iter()
+        .find(|range| {
+            range.channels() == default_config.channels()
+                && range.sample_format() == default_config.sample_format()
+        })
+        .or_else(|| ranges.first())
+        .cloned()
+        .map(|range| range.with_sample_rate(cpal::SampleRate(rate)))
+        .ok_or(AudioError::UnsupportedSampleRate(rate))
+}
+
+/// Opens an output stream mixing the drag hum and the background music.
+///
+/// # Arguments
+/// * `controls` - The state the voices follow
+/// * `output` - The device and sample rate to open
+pub fn start(controls: &Controls, output: &OutputConfig) -> Result<Handle, AudioError> {
+    let device = find_device(output.device.as_deref())?;
+    let supported = supported_config(&device, output.sample_rate)?;
+
+    let mut config = supported.config();
+    if let cpal::SupportedBufferSize::Range { min, max } = supported.buffer_size() {
+        if (*min..=*max).contains(&PREFERRED_BUFFER_SIZE) {
+            config.buffer_size = cpal::BufferSize::Fixed(PREFERRED_BUFFER_SIZE);
+        }
     }
+
+    let lost = Arc::new(AtomicBool::new(false));
+    let (producer, tap) = RingBuffer::new(crate::spectrum::TAP_CAPACITY).split();
+    let record_capacity = (config.sample_rate.0 * RECORD_BUFFER_SECONDS) as usize * config.channels as usize;
+    let (record_producer, record) = RingBuffer::new(record_capacity).split();
+    let stream = match supported.sample_format() {
+        cpal::SampleFormat::I8 => run::<i8>(&device, &config, controls, lost.clone(), producer, record_producer),
+        cpal::SampleFormat::I16 => run::<i16>(&device, &config, controls, lost.clone(), producer, record_producer),
+        cpal::SampleFormat::I32 => run::<i32>(&device, &config, controls, lost.clone(), producer, record_producer),
+        cpal::SampleFormat::I64 => run::<i64>(&device, &config, controls, lost.clone(), producer, record_producer),
+        cpal::SampleFormat::U8 => run::<u8>(&device, &config, controls, lost.clone(), producer, record_producer),
+        cpal::SampleFormat::U16 => run::<u16>(&device, &config, controls, lost.clone(), producer, record_producer),
+        cpal::SampleFormat::U32 => run::<u32>(&device, &config, controls, lost.clone(), producer, record_producer),
+        cpal::SampleFormat::U64 => run::<u64>(&device, &config, controls, lost.clone(), producer, record_producer),
+        cpal::SampleFormat::F32 => run::<f32>(&device, &config, controls, lost.clone(), producer, record_producer),
+        cpal::SampleFormat::F64 => run::<f64>(&device, &config, controls, lost.clone(), producer, record_producer),
+        format => Err(AudioError::UnsupportedFormat(format.to_string())),
+    }?;
+
+    Ok(Handle {
+        stream,
+        device: device.name().unwrap_or_else(|_| "unknown device".to_string()),
+        sample_rate: config.sample_rate.0,
+        channels: config.channels,
+        lost,
+        tap,
+        record,
+    })
 }
 
 fn run<T>(
     device: &cpal::Device,
     config: &cpal::StreamConfig,
-    base_freq: Arc<Mutex<crate::FreqWrapper>>,
-) -> Stream
+    controls: &Controls,
+    lost: Arc<AtomicBool>,
+    tap: Producer<f32>,
+    recorder: Producer<f32>,
+) -> Result<Stream, AudioError>
 where
     T: SizedSample + FromSample<f32>,
 {
     let sample_rate = config.sample_rate.0 as f32;
     let channels = config.channels as usize;
 
+    let mut voices: Vec<Box<dyn Voice>> = vec![Box::new(hum(controls.freq.clone(), sample_rate))];
+    voices.extend(crate::music::voices(controls.music.clone(), sample_rate));
+    let mut mixer = Mixer::new(voices)
+        .with_tap(tap)
+        .with_recorder(recorder, controls.recording.clone());
+
+    // Any stream error (usually an unplugged device) marks the stream as lost,
+    // so the next update can re-select a working device.
+    let err_fn = move |err| {
+        crate::console::console_log!("an error occurred on stream: {}", err);
+        lost.store(true, Ordering::Relaxed);
+    };
+
+    let stream = device.build_output_stream(
+        config,
+        move |data: &mut [T], _| mixer.write(data, channels),
+        err_fn,
+        None,
+    )?;
+    stream.play()?;
+    Ok(stream)
+}
+
+/// The drag hum: a softly modulated tone following the frequency, volume and
+/// placement in `FreqWrapper`. It fades out when the frequency drops to zero
+/// and fades back in when a new drag sets it again.
+fn hum(base_freq: Arc<Mutex<crate::FreqWrapper>>, sample_rate: f32) -> impl Voice {
     // Produce a sinusoid of maximum amplitude.
     let mut sample_clock = 0f32;
 
@@ -66,11 +467,21 @@ where
     let mut stop_requested = false; // Track if we're stopping
     let start_fade_duration = 0.1; // 100ms fade in
     let stop_fade_duration = 0.15; // 150ms fade out
-    let mut next_value = move || {
+    let mut last_pan = 0.0;
+    let mut last_gain = 1.0;
+    move || {
         let mut base_freq = base_freq.clone();
         let freq_wrapper = base_freq.borrow_mut().lock().unwrap();
         let current_freq = freq_wrapper.value;
-        
+
+        // A new drag restarts the voice with a fresh fade-in
+        if stop_requested && current_freq > 0.01 {
+            stop_requested = false;
+            is_starting = true;
+            start_time = 0.0;
+            sample_clock = 0.0;
+        }
+
         // Crossfade between frequency changes to prevent pops
         let crossfade_samples = (crossfade_time * sample_rate) as u32;
         let freq_diff = current_freq - last_freq;
@@ -82,10 +493,15 @@ where
         let interpolated_freq = last_freq + freq_diff * crossfade_factor;
         last_freq = current_freq;
         
-        // Get volume and drop the lock early
+        // Get volume and placement and drop the lock early
         let volume = freq_wrapper.volume;
+        let (pan, gain) = (freq_wrapper.pan, freq_wrapper.gain);
         drop(freq_wrapper);
 
+        // Glide towards the new placement so fast mouse moves don't cause zipper noise
+        last_pan += (pan - last_pan) * PLACEMENT_SMOOTHING;
+        last_gain += (gain - last_gain) * PLACEMENT_SMOOTHING;
+
         sample_clock = (sample_clock + 1.0) % sample_rate;
         let current_sample = sample_clock as u32;
 
@@ -169,31 +585,257 @@ where
         }
 
         // Final scaling with volume and fade effects
-        dc_blocked * volume * volume_factor * 0.5
-    };
+        let output = dc_blocked * volume * volume_factor * 0.5;
 
-    let err_fn = |err| crate::console::console_log!("an error occurred on stream: {}", err);
+        (output, last_pan, last_gain)
+    }
+}
 
-    let stream = device
-        .build_output_stream(
-            config,
-            move |data: &mut [T], _| write_data(data, channels, &mut next_value),
-            err_fn,
-            None,
-        )
-        .unwrap();
-    stream.play().unwrap();
-    stream
+/// Sums all voices of a stream and writes the mix to the device's channels.
+pub struct Mixer {
+    voices: Vec<Box<dyn Voice>>,
+    /// Mono copy of the mix for the analyser
+    tap: Option<Producer<f32>>,
+    /// Interleaved copy of the output, filled while the flag is set
+    recorder: Option<(Producer<f32>, Arc<AtomicBool>)>,
 }
 
-fn write_data<T>(output: &mut [T], channels: usize, next_sample: &mut dyn FnMut() -> f32)
-where
-    T: SizedSample + FromSample<f32>,
-{
-    for frame in output.chunks_mut(channels) {
-        let value: T = T::from_sample(next_sample());
-        for sample in frame.iter_mut() {
-            *sample = value;
+impl Mixer {
+    /// Creates a mixer playing the given voices.
+    pub fn new(voices: Vec<Box<dyn Voice>>) -> Self {
+        Self {
+            voices,
+            tap: None,
+            recorder: None,
+        }
+    }
+
+    /// Sends a mono copy of the mix into `tap`. If the reader lags behind, samples are dropped.
+    pub fn with_tap(mut self, tap: Producer<f32>) -> Self {
+        self.tap = Some(tap);
+        self
+    }
+
+    /// Copies the interleaved output into `recorder` while `recording` is set.
+    ///
+    /// Only a lock-free push happens on the audio thread; if the reader lags
+    /// behind, samples are dropped instead of blocking the callback.
+    pub fn with_recorder(mut self, recorder: Producer<f32>, recording: Arc<AtomicBool>) -> Self {
+        self.recorder = Some((recorder, recording));
+        self
+    }
+
+    /// Fills an interleaved output buffer with the next frames of the mix.
+    ///
+    /// # Arguments
+    /// * `output` - The buffer to fill
+    /// * `channels` - Number of interleaved channels in `output`
+    pub fn write<T>(&mut self, output: &mut [T], channels: usize)
+    where
+        T: SizedSample + FromSample<f32>,
+    {
+        for frame in output.chunks_mut(channels) {
+            let mut mix = [0.0f32; MAX_CHANNELS];
+            let mut mono = 0.0;
+            for voice in self.voices.iter_mut() {
+                let (value, pan, gain) = voice.next_sample();
+                mono += value * gain;
+                for (channel, level) in mix.iter_mut().take(channels).enumerate() {
+                    *level += value * gain * channel_gain(pan, channel, channels);
+                }
+            }
+
+            if let Some(tap) = self.tap.as_mut() {
+                let _ = tap.push(mono);
+            }
+
+            let recorder = self
+                .recorder
+                .as_mut()
+                .filter(|(_, recording)| recording.load(Ordering::Relaxed));
+            match recorder {
+                Some((recorder, _)) => {
+                    for (channel, sample) in frame.iter_mut().enumerate() {
+                        let level = mix.get(channel).copied().unwrap_or(0.0);
+                        let _ = recorder.push(level);
+                        *sample = T::from_sample(level);
+                    }
+                }
+                None => {
+                    for (channel, sample) in frame.iter_mut().enumerate() {
+                        *sample = T::from_sample(mix.get(channel).copied().unwrap_or(0.0));
+                    }
+                }
+            }
         }
     }
+
+    /// Renders the mix without an audio device, e.g. for tests or offline bouncing.
+    ///
+    /// # Arguments
+    /// * `channels` - Number of interleaved channels to render
+    /// * `frames` - Number of frames to render
+    ///
+    /// # Returns
+    /// The interleaved samples
+    pub fn render(&mut self, channels: usize, frames: usize) -> Vec<f32> {
+        let mut output = vec![0.0; channels * frames];
+        self.write(&mut output, channels);
+        output
+    }
+}
+
+/// Returns the gain of `channel` for a voice panned to `pan` (-1.0 left to 1.0 right).
+///
+/// Uses an equal-power pan law so a voice keeps its loudness while it moves
+/// across the stereo field. Mono outputs get the unpanned signal, and every
+/// channel after the front left/right pair receives the centre mix.
+pub fn channel_gain(pan: f32, channel: usize, channels: usize) -> f32 {
+    if channels < 2 {
+        return 1.0;
+    }
+
+    let angle = (pan.clamp(-1.0, 1.0) + 1.0) * PI / 4.0;
+    match channel {
+        0 => angle.cos(),
+        1 => angle.sin(),
+        _ => FRAC_1_SQRT_2,
+    }
+}
+
+/// Places a sound source on the board relative to a listener at the board centre.
+///
+/// # Arguments
+/// * `x`, `y` - Position of the source in window coordinates (origin at the centre)
+/// * `half_width`, `half_height` - Half the size of the board
+///
+/// # Returns
+/// `(pan, gain)` where `pan` follows the horizontal position and `gain`
+/// falls off with the distance from the centre.
+pub fn placement(x: f32, y: f32, half_width: f32, half_height: f32) -> (f32, f32) {
+    if half_width <= 0.0 || half_height <= 0.0 {
+        return (0.0, 1.0);
+    }
+
+    let pan = (x / half_width).clamp(-1.0, 1.0);
+    let distance = ((x / half_width).powi(2) + (y / half_height).powi(2)).sqrt();
+    let gain = 1.0 / (1.0 + DISTANCE_ROLLOFF * distance);
+    (pan, gain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_mono_output_ignores_pan() {
+        assert_eq!(channel_gain(-1.0, 0, 1), 1.0);
+        assert_eq!(channel_gain(1.0, 0, 1), 1.0);
+    }
+
+    #[test]
+    fn test_stereo_pan_hard_left_and_right() {
+        assert_approx_eq!(channel_gain(-1.0, 0, 2), 1.0);
+        assert_approx_eq!(channel_gain(-1.0, 1, 2), 0.0);
+        assert_approx_eq!(channel_gain(1.0, 0, 2), 0.0);
+        assert_approx_eq!(channel_gain(1.0, 1, 2), 1.0);
+    }
+
+    #[test]
+    fn test_stereo_pan_keeps_power() {
+        for pan in [-1.0, -0.5, 0.0, 0.3, 1.0] {
+            let left = channel_gain(pan, 0, 2);
+            let right = channel_gain(pan, 1, 2);
+            assert_approx_eq!(left * left + right * right, 1.0);
+        }
+    }
+
+    #[test]
+    fn test_mixer_surround_layout() {
+        let mut mixer = Mixer::new(vec![Box::new(|| (1.0, -1.0, 0.5))]);
+        let output = mixer.render(4, 2);
+        for frame in output.chunks(4) {
+            assert_approx_eq!(frame[0], 0.5);
+            assert_approx_eq!(frame[1], 0.0);
+            assert_approx_eq!(frame[2], 0.5 * FRAC_1_SQRT_2);
+            assert_approx_eq!(frame[3], 0.5 * FRAC_1_SQRT_2);
+        }
+    }
+
+    #[test]
+    fn test_mixer_sums_voices() {
+        let mut mixer = Mixer::new(vec![Box::new(|| (0.25, 0.0, 1.0)), Box::new(|| (0.5, 0.0, 0.5))]);
+        let output = mixer.render(1, 3);
+        assert_eq!(output, vec![0.5, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn test_mixer_records_only_while_recording() {
+        let (producer, mut consumer) = RingBuffer::new(16).split();
+        let recording = Arc::new(AtomicBool::new(false));
+        let mut mixer = Mixer::new(vec![Box::new(|| (0.5, 0.0, 1.0))]).with_recorder(producer, recording.clone());
+
+        mixer.render(2, 2);
+        assert!(consumer.is_empty());
+
+        recording.store(true, Ordering::Relaxed);
+        let output = mixer.render(2, 2);
+        let mut recorded = vec![0.0; 8];
+        assert_eq!(consumer.pop_slice(&mut recorded), 4);
+        assert_eq!(&recorded[..4], &output[..]);
+    }
+
+    #[test]
+    fn test_placement_left_of_board_pans_left() {
+        let (pan, gain) = placement(-400.0, 0.0, 512.0, 512.0);
+        assert!(pan < -0.5);
+        assert!(gain < 1.0);
+
+        let (pan, gain) = placement(0.0, 0.0, 512.0, 512.0);
+        assert_eq!(pan, 0.0);
+        assert_eq!(gain, 1.0);
+    }
+
+    #[test]
+    fn test_cpal_errors_convert() {
+        let err = AudioError::from(cpal::BuildStreamError::DeviceNotAvailable);
+        assert_eq!(err, AudioError::Stream(cpal::BuildStreamError::DeviceNotAvailable.to_string()));
+        assert!(err.to_string().starts_with("could not start stream: "));
+        assert!(matches!(AudioError::from(cpal::PlayStreamError::DeviceNotAvailable), AudioError::Stream(_)));
+
+        let backend = cpal::BackendSpecificError { description: "no sound server".to_string() };
+        let err = AudioError::from(cpal::DevicesError::BackendSpecific { err: backend });
+        let message = err.to_string();
+        assert!(message.starts_with("could not query device: ") && message.ends_with("no sound server"));
+        assert!(matches!(AudioError::from(cpal::DefaultStreamConfigError::DeviceNotAvailable), AudioError::Config(_)));
+        let err = AudioError::from(cpal::SupportedStreamConfigsError::DeviceNotAvailable);
+        assert!(matches!(err, AudioError::Config(_)));
+    }
+
+    #[test]
+    fn test_reselection_keeps_what_the_device_supports() {
+        let config = OutputConfig { device: Some("USB".to_string()), sample_rate: Some(48000) };
+        let devices = ["Speakers".to_string(), "USB".to_string()];
+        assert_eq!(config.fallback_device(&devices), Some("USB".to_string()));
+        assert_eq!(config.fallback_device(&devices[..1]), None);
+
+        assert_eq!(config.on_device(None, &[44100, 48000]), OutputConfig { device: None, sample_rate: Some(48000) });
+        assert_eq!(config.on_device(None, &[44100]), OutputConfig::default());
+    }
+
+    #[test]
+    fn test_status_follows_open_attempts() {
+        let mut output = Output::new();
+        assert_eq!(output.status, Status::Idle);
+        assert_eq!(output.status.to_string(), "idle");
+
+        output.status = Status::after(Err(&AudioError::DeviceNotFound("USB".to_string())));
+        assert_eq!(output.status, Status::Unavailable(AudioError::DeviceNotFound("USB".to_string())));
+        assert_eq!(output.status.to_string(), "unavailable (output device \"USB\" not found)");
+
+        output.status = Status::after(Ok(("Speakers", 44100)));
+        assert_eq!(output.status, Status::Ready { device: "Speakers".to_string(), sample_rate: 44100 });
+        assert_eq!(output.status.to_string(), "Speakers at 44100 Hz");
+    }
 }
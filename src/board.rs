@@ -0,0 +1,92 @@
+use crate::edge::Edge;
+use crate::Anchor;
+use serde::{Deserialize, Serialize};
+
+/// File the board is saved to.
+pub const BOARD_FILE: &str = "hexbattle-board.yaml";
+/// Side of the square, centred on the origin, that new boards are scattered over.
+pub(crate) const BOARD_SIZE: f32 = 1024.0;
+
+/// The saved form of a board: where the anchors are and how they are connected.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Board {
+    /// The anchors with their positions and properties
+    pub(crate) anchors: Vec<Anchor>,
+    /// Edges with indices into `anchors`, and their properties
+    #[serde(default)]
+    pub(crate) edges: Vec<Edge>,
+}
+
+impl Board {
+    /// Serializes the board to YAML.
+    pub(crate) fn to_yaml(&self) -> Result<String, String> {
+        serde_yaml::to_string(self).map_err(|err| err.to_string())
+    }
+
+    /// Reads a board from YAML, rejecting edges that do not connect two
+    /// different existing anchors.
+    pub(crate) fn from_yaml(yaml: &str) -> Result<Self, String> {
+        let board: Board = serde_yaml::from_str(yaml).map_err(|err| err.to_string())?;
+        let count = board.anchors.len();
+        match board.edges.iter().find(|edge| edge.from >= count || edge.to >= count || edge.from == edge.to) {
+            Some(edge) => Err(format!("invalid edge ({}, {}) on a board with {} anchors", edge.from, edge.to, count)),
+            None => Ok(board),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anchor::{AnchorKind, AnchorProps};
+    use crate::edge::{EdgeKind, EdgeProps};
+    use crate::Pos;
+
+    #[test]
+    fn test_yaml_round_trip() {
+        let board = Board {
+            anchors: vec![
+                Anchor::new(Pos::new(0.0, 0.0)),
+                Anchor {
+                    pos: Pos::new(10.5, -3.0),
+                    props: AnchorProps {
+                        kind: AnchorKind::Base,
+                        owner: Some(0),
+                        capacity: Some(3),
+                        hit_points: 250,
+                    },
+                },
+                Anchor::new(Pos::new(4.0, 8.0)),
+            ],
+            edges: vec![
+                Edge::new(0, 1),
+                Edge::with_props(
+                    1,
+                    2,
+                    EdgeProps {
+                        kind: EdgeKind::Wall,
+                        owner: Some(1),
+                        weight: 2.0,
+                        label: Some("North gate".to_string()),
+                    },
+                ),
+            ],
+        };
+        assert_eq!(Board::from_yaml(&board.to_yaml().unwrap()).unwrap(), board);
+    }
+
+    #[test]
+    fn test_invalid_edges_are_rejected() {
+        let yaml = "anchors:\n  - {x: 0.0, y: 0.0}\n  - {x: 1.0, y: 0.0}\nedges:\n  - [0, 2]\n";
+        assert!(Board::from_yaml(yaml).is_err());
+        let yaml = "anchors:\n  - {x: 0.0, y: 0.0}\nedges:\n  - [0, 0]\n";
+        assert!(Board::from_yaml(yaml).is_err());
+        assert!(Board::from_yaml("anchors: []\n").unwrap().edges.is_empty());
+    }
+
+    #[test]
+    fn test_anchors_without_properties_are_neutral() {
+        let board = Board::from_yaml("anchors:\n  - {x: 1.5, y: -2.0}\n").unwrap();
+        assert_eq!(board.anchors, vec![Anchor::new(Pos::new(1.5, -2.0))]);
+    }
+}
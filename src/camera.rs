@@ -0,0 +1,102 @@
+use crate::Pos;
+use nannou::prelude::*;
+
+/// Zoom limits of the camera.
+pub const MIN_ZOOM: f32 = 0.25;
+pub const MAX_ZOOM: f32 = 4.0;
+/// Zoom factor of one mouse wheel notch.
+pub const ZOOM_STEP: f32 = 1.1;
+
+/// Which part of the board is shown, and how large.
+///
+/// Screen positions are relative to the window centre, like nannou's mouse
+/// position; world positions are where anchors live.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Camera {
+    /// Screen pixels per world unit
+    pub(crate) zoom: f32,
+    /// The world position shown at the window centre
+    pub(crate) center: Pos,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            zoom: 1.0,
+            center: Pos::new(0.0, 0.0),
+        }
+    }
+}
+
+impl Camera {
+    /// Converts a screen position to a world position.
+    pub(crate) fn to_world(&self, screen: Pos) -> Pos {
+        screen * (1.0 / self.zoom) + self.center
+    }
+
+    /// Converts a world position to a screen position.
+    pub(crate) fn to_screen(&self, world: Pos) -> Pos {
+        (world - self.center) * self.zoom
+    }
+
+    /// Returns a `Draw` that takes world positions.
+    pub(crate) fn transform(&self, draw: &Draw) -> Draw {
+        draw.scale(self.zoom).x_y(-self.center.x, -self.center.y)
+    }
+
+    /// Zooms by `factor`, keeping the world position under `screen` in place.
+    pub(crate) fn zoom_at(&mut self, screen: Pos, factor: f32) {
+        let anchor = self.to_world(screen);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.center = anchor - screen * (1.0 / self.zoom);
+    }
+
+    /// Moves the board along with a pointer that moved by a screen offset.
+    pub(crate) fn pan_by(&mut self, screen: Pos) {
+        self.center = self.center - screen * (1.0 / self.zoom);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Pos, b: Pos) -> bool {
+        a.distance(&b) < 1e-3
+    }
+
+    #[test]
+    fn test_screen_world_round_trip() {
+        let camera = Camera {
+            zoom: 2.0,
+            center: Pos::new(10.0, -5.0),
+        };
+        assert_eq!(camera.to_world(Pos::new(0.0, 0.0)), Pos::new(10.0, -5.0));
+        assert_eq!(camera.to_world(Pos::new(20.0, 0.0)), Pos::new(20.0, -5.0));
+        let pos = Pos::new(33.0, 71.0);
+        assert!(close(camera.to_screen(camera.to_world(pos)), pos));
+    }
+
+    #[test]
+    fn test_zoom_keeps_point_under_cursor() {
+        let mut camera = Camera::default();
+        let cursor = Pos::new(100.0, 50.0);
+        let before = camera.to_world(cursor);
+        camera.zoom_at(cursor, 2.0);
+        assert!(close(camera.to_world(cursor), before));
+
+        camera.zoom_at(cursor, 100.0);
+        assert_eq!(camera.zoom, MAX_ZOOM);
+        camera.zoom_at(cursor, 0.0001);
+        assert_eq!(camera.zoom, MIN_ZOOM);
+    }
+
+    #[test]
+    fn test_pan_follows_pointer() {
+        let mut camera = Camera { zoom: 2.0, ..Camera::default() };
+        let grabbed = camera.to_world(Pos::new(0.0, 0.0));
+        camera.pan_by(Pos::new(20.0, 10.0));
+        assert_eq!(camera.center, Pos::new(-10.0, -5.0));
+        assert_eq!(camera.to_world(Pos::new(20.0, 10.0)), grabbed);
+    }
+}
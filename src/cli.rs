@@ -0,0 +1,158 @@
+use crate::exchange::{self, Format, Report};
+use crate::levels::{self, Params};
+use clap::{Parser, Subcommand};
+use std::path::{Path, PathBuf};
+
+/// Works on board files without opening the editor, which only runs in the browser.
+#[derive(Debug, Parser)]
+#[clap(name = "hexbattle", version, about)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Converts a board to another format
+    Convert {
+        /// The board to read
+        input: PathBuf,
+        /// Where to write the converted board
+        output: PathBuf,
+        /// Format of the input, when its extension does not tell
+        #[clap(long)]
+        from: Option<Format>,
+        /// Format of the output, when its extension does not tell
+        #[clap(long)]
+        to: Option<Format>,
+    },
+    /// Reads a board and prints what is wrong with it
+    Check {
+        /// The board to read
+        input: PathBuf,
+        /// Format of the input, when its extension does not tell
+        #[clap(long)]
+        from: Option<Format>,
+    },
+    /// Generates a pack of untangle levels with known solutions
+    Levels {
+        /// Directory to write the levels to
+        output: PathBuf,
+        /// Number of levels
+        #[clap(long, default_value_t = 10)]
+        count: u64,
+        /// Seed of the first level, counted up for the others
+        #[clap(long, default_value_t = 0)]
+        seed: u64,
+        /// Anchors in each level
+        #[clap(long, default_value_t = 12)]
+        anchors: usize,
+        /// Edges per anchor, from 1 to just under 3
+        #[clap(long, default_value_t = 2.0)]
+        density: f32,
+        /// Crossings each level starts with, the anchor count if not given
+        #[clap(long)]
+        crossings: Option<usize>,
+    },
+}
+
+/// Runs the command given on the command line.
+///
+/// # Returns
+/// What went wrong, for printing
+pub(crate) fn run() -> Result<(), String> {
+    match Cli::parse().command {
+        None => {
+            println!("Must be run as a web app! Use trunk to build. (cargo install trunk && trunk serve)");
+            println!("Run with --help for the commands that work on board files.");
+            Ok(())
+        }
+        Some(Command::Convert { input, output, from, to }) => {
+            let (from, to) = (format_of(&input, from)?, format_of(&output, to)?);
+            let (text, report) = convert(&read(&input)?, from, to)?;
+            std::fs::write(&output, text).map_err(|err| format!("{}: {}", output.display(), err))?;
+            println!("{}: {}", output.display(), report);
+            Ok(())
+        }
+        Some(Command::Check { input, from }) => {
+            let (_, report) = exchange::import(&read(&input)?, format_of(&input, from)?)?;
+            println!("{}: {}", input.display(), report);
+            Ok(())
+        }
+        Some(Command::Levels { output, count, seed, anchors, density, crossings }) => {
+            if !(1.0..3.0).contains(&density) {
+                return Err(format!("density {} is not between 1 and 3", density));
+            }
+            let params = Params { anchors, density, crossings: crossings.unwrap_or(anchors) };
+            std::fs::create_dir_all(&output).map_err(|err| format!("{}: {}", output.display(), err))?;
+            for seed in seed..seed + count {
+                let level = levels::generate(params, seed);
+                let path = output.join(level_file(seed));
+                std::fs::write(&path, level.to_yaml()?).map_err(|err| format!("{}: {}", path.display(), err))?;
+                println!(
+                    "{}: {} edges, {} crossings, solved in {} moves",
+                    path.display(),
+                    level.board.edges.len(),
+                    level.crossings,
+                    level.moves()
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Returns the file name of the level generated from a seed.
+fn level_file(seed: u64) -> String {
+    format!("level-{:04}.yaml", seed)
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+/// Returns the format given, or else the one the file's extension tells.
+fn format_of(path: &Path, given: Option<Format>) -> Result<Format, String> {
+    given
+        .or_else(|| Format::of_file(&path.to_string_lossy()))
+        .ok_or_else(|| format!("{}: unknown format, pick one with --from or --to", path.display()))
+}
+
+/// Reads a board in one format and writes it in another.
+///
+/// # Returns
+/// The converted board and what was wrong with the input
+fn convert(text: &str, from: Format, to: Format) -> Result<(String, Report), String> {
+    let (board, report) = exchange::import(text, from)?;
+    Ok((exchange::export(&board, to)?, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_picks_formats_from_extensions() {
+        let cli = Cli::try_parse_from(["hexbattle", "convert", "level.dot", "level.out", "--to", "graphml"]).unwrap();
+        let Some(Command::Convert { input, output, from, to }) = cli.command else {
+            panic!("not a conversion");
+        };
+        assert_eq!(format_of(&input, from), Ok(Format::Dot));
+        assert_eq!(format_of(&output, to), Ok(Format::GraphMl));
+        assert!(format_of(&output, None).is_err());
+
+        let (graphml, report) = convert("graph { a -- b }", Format::Dot, Format::GraphMl).unwrap();
+        assert!(graphml.contains("<edge source=\"n0\" target=\"n1\">"));
+        assert_eq!(report.unplaced, 2);
+    }
+
+    #[test]
+    fn test_levels_defaults() {
+        let cli = Cli::try_parse_from(["hexbattle", "levels", "pack", "--seed", "7", "--crossings", "3"]).unwrap();
+        let Some(Command::Levels { count, seed, anchors, density, crossings, .. }) = cli.command else {
+            panic!("not a level pack");
+        };
+        assert_eq!((count, seed, anchors, density, crossings), (10, 7, 12, 2.0, Some(3)));
+        assert_eq!(level_file(seed), "level-0007.yaml");
+    }
+}
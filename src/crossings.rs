@@ -0,0 +1,198 @@
+use crate::{LineSegment, Pos};
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+
+/// The pairs of crossing edges, kept up to date as the edges change.
+///
+/// `sync` compares the edges' lines with the ones seen last time and only
+/// tests edges that were added or changed. When edges were only removed or
+/// only inserted, the known pairs are renumbered instead of tested again.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Crossings {
+    /// The line of each edge as of the last sync
+    lines: Vec<LineSegment>,
+    /// For each edge, the edges crossing it
+    partners: Vec<BTreeSet<usize>>,
+    /// Number of crossing pairs
+    count: usize,
+}
+
+impl Crossings {
+    /// Brings the pairs up to date with the current lines of the edges.
+    pub(crate) fn sync(&mut self, lines: Vec<LineSegment>) {
+        let (old, new) = (self.lines.len(), lines.len());
+        let renumbered = match new.cmp(&old) {
+            Ordering::Equal => None,
+            // Edges were removed: `kept[edge]` is where the edge was before
+            Ordering::Less => subsequence(&lines, &self.lines).map(|kept| {
+                let mut now = vec![None; old];
+                for (edge, before) in kept.iter().enumerate() {
+                    now[*before] = Some(edge);
+                }
+                self.partners = kept
+                    .iter()
+                    .map(|before| self.partners[*before].iter().filter_map(|partner| now[*partner]).collect())
+                    .collect();
+                Vec::new()
+            }),
+            // Edges were inserted: `moved[edge]` is where the edge is now
+            Ordering::Greater => subsequence(&self.lines, &lines).map(|moved| {
+                let mut partners = vec![BTreeSet::new(); new];
+                let mut inserted = vec![true; new];
+                for (before, partners_before) in self.partners.iter().enumerate() {
+                    partners[moved[before]] = partners_before.iter().map(|partner| moved[*partner]).collect();
+                    inserted[moved[before]] = false;
+                }
+                self.partners = partners;
+                (0..new).filter(|edge| inserted[*edge]).collect()
+            }),
+        };
+        let changed: Vec<usize> = match renumbered {
+            Some(inserted) => inserted,
+            None if old == new => (0..new).filter(|edge| self.lines[*edge] != lines[*edge]).collect(),
+            None => {
+                self.partners = vec![BTreeSet::new(); new];
+                (0..new).collect()
+            }
+        };
+
+        self.lines = lines;
+        self.retest(&changed);
+        self.count = self.partners.iter().map(BTreeSet::len).sum::<usize>() / 2;
+    }
+
+    /// Tests the given edges against all others again.
+    fn retest(&mut self, edges: &[usize]) {
+        let mut is_retested = vec![false; self.lines.len()];
+        for &edge in edges {
+            is_retested[edge] = true;
+            for partner in std::mem::take(&mut self.partners[edge]) {
+                self.partners[partner].remove(&edge);
+            }
+        }
+        for &edge in edges {
+            for (other, &retested) in is_retested.iter().enumerate() {
+                // Pairs of retested edges are tested once, from the lower index
+                if other == edge || (retested && other < edge) {
+                    continue;
+                }
+                if self.lines[edge].line_segments_intersect(&self.lines[other]) {
+                    self.partners[edge].insert(other);
+                    self.partners[other].insert(edge);
+                }
+            }
+        }
+    }
+
+    /// Returns the number of pairs of crossing edges.
+    pub(crate) fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the number of edges crossing an edge.
+    pub(crate) fn degree(&self, edge: usize) -> usize {
+        self.partners.get(edge).map_or(0, BTreeSet::len)
+    }
+
+    /// Returns the pairs of crossing edges, each once with the lower index first.
+    pub(crate) fn pairs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.partners
+            .iter()
+            .enumerate()
+            .flat_map(|(edge, partners)| partners.range(edge + 1..).map(move |partner| (edge, *partner)))
+    }
+
+    /// Returns the points where edges cross.
+    pub(crate) fn points(&self) -> impl Iterator<Item = Pos> + '_ {
+        self.pairs().filter_map(|(a, b)| self.lines[a].intersection(&self.lines[b]))
+    }
+
+    /// Returns the edge crossed by the most others, the lowest index on a tie,
+    /// or `None` if no edges cross.
+    pub(crate) fn most_tangled(&self) -> Option<usize> {
+        (0..self.partners.len())
+            .filter(|edge| self.degree(*edge) > 0)
+            .max_by(|a, b| self.degree(*a).cmp(&self.degree(*b)).then(b.cmp(a)))
+    }
+}
+
+/// Finds the positions of the items of `short` in `long`, in order.
+///
+/// # Returns
+/// The position in `long` of each item of `short`, or `None` if `short` is
+/// not a subsequence of `long`
+fn subsequence(short: &[LineSegment], long: &[LineSegment]) -> Option<Vec<usize>> {
+    let mut positions = Vec::with_capacity(short.len());
+    let mut rest = long.iter().enumerate();
+    for line in short {
+        let (position, _) = rest.find(|(_, other)| *other == line)?;
+        positions.push(position);
+    }
+    Some(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Pos;
+
+    fn line(x1: f32, y1: f32, x2: f32, y2: f32) -> LineSegment {
+        LineSegment::new(Pos::new(x1, y1), Pos::new(x2, y2))
+    }
+
+    /// Recomputes the pairs from scratch, to compare with the kept ones.
+    fn fresh(lines: &[LineSegment]) -> Vec<(usize, usize)> {
+        (0..lines.len())
+            .flat_map(|a| (a + 1..lines.len()).map(move |b| (a, b)))
+            .filter(|(a, b)| lines[*a].line_segments_intersect(&lines[*b]))
+            .collect()
+    }
+
+    fn check(crossings: &mut Crossings, lines: &[LineSegment]) {
+        crossings.sync(lines.to_vec());
+        let pairs: Vec<(usize, usize)> = crossings.pairs().collect();
+        assert_eq!(pairs, fresh(lines));
+        assert_eq!(crossings.count(), pairs.len());
+    }
+
+    #[test]
+    fn test_pairs_follow_changes() {
+        // A horizontal line crossed by two verticals, and one far away
+        let mut lines = vec![
+            line(0.0, 0.0, 100.0, 0.0),
+            line(20.0, -50.0, 20.0, 50.0),
+            line(500.0, 500.0, 600.0, 500.0),
+            line(80.0, -50.0, 80.0, 50.0),
+        ];
+        let mut crossings = Crossings::default();
+        check(&mut crossings, &lines);
+        assert_eq!(crossings.count(), 2);
+        assert_eq!(crossings.degree(0), 2);
+        assert_eq!(crossings.most_tangled(), Some(0));
+
+        // Moving an edge
+        lines[2] = line(50.0, -50.0, 50.0, 50.0);
+        check(&mut crossings, &lines);
+        assert_eq!(crossings.degree(0), 3);
+
+        // Removing edges renumbers the rest
+        lines.remove(1);
+        check(&mut crossings, &lines);
+        lines.remove(0);
+        check(&mut crossings, &lines);
+        assert_eq!(crossings.count(), 0);
+        assert_eq!(crossings.most_tangled(), None);
+
+        // Inserting edges in the middle and at the end
+        lines.insert(1, line(0.0, 10.0, 100.0, 10.0));
+        check(&mut crossings, &lines);
+        lines.push(line(0.0, 20.0, 100.0, 20.0));
+        check(&mut crossings, &lines);
+        assert_eq!(crossings.count(), 4);
+
+        // Replacing everything
+        lines = vec![line(0.0, 0.0, 10.0, 10.0), line(0.0, 10.0, 10.0, 0.0)];
+        check(&mut crossings, &lines);
+        assert_eq!(crossings.degree(5), 0);
+    }
+}
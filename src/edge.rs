@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+
+/// What an edge represents on the battlefield, which decides how it is drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum EdgeKind {
+    /// A plain connection units travel along
+    #[default]
+    Road,
+    /// A fortified connection
+    Wall,
+    /// A connection over water or a gap
+    Bridge,
+}
+
+impl EdgeKind {
+    /// All kinds, in the order they are offered in the UI.
+    pub(crate) const ALL: [EdgeKind; 3] = [EdgeKind::Road, EdgeKind::Wall, EdgeKind::Bridge];
+
+    /// Returns the name shown in the UI.
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            EdgeKind::Road => "Road",
+            EdgeKind::Wall => "Wall",
+            EdgeKind::Bridge => "Bridge",
+        }
+    }
+}
+
+/// The game data carried by an edge. New edges copy it from the template of
+/// the current tool.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct EdgeProps {
+    /// What the edge represents
+    pub(crate) kind: EdgeKind,
+    /// The player owning the edge, if any
+    pub(crate) owner: Option<u8>,
+    /// Strength of the edge, also used as its cost by the graph algorithms
+    pub(crate) weight: f32,
+    /// Text shown next to the edge
+    pub(crate) label: Option<String>,
+}
+
+impl Default for EdgeProps {
+    fn default() -> Self {
+        Self {
+            kind: EdgeKind::Road,
+            owner: None,
+            weight: 1.0,
+            label: None,
+        }
+    }
+}
+
+/// A connection between two anchors, given by their indices.
+///
+/// Boards written before edges had properties store edges as `[from, to]`,
+/// which still reads as an edge with default properties.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Edge {
+    pub(crate) from: usize,
+    pub(crate) to: usize,
+    #[serde(default)]
+    pub(crate) props: EdgeProps,
+}
+
+impl Edge {
+    /// Creates an edge with default properties.
+    pub(crate) fn new(from: usize, to: usize) -> Self {
+        Self::with_props(from, to, EdgeProps::default())
+    }
+
+    /// Creates an edge with the given properties.
+    pub(crate) fn with_props(from: usize, to: usize, props: EdgeProps) -> Self {
+        Self { from, to, props }
+    }
+
+    /// Returns the indices of the two anchors.
+    pub(crate) fn ends(&self) -> (usize, usize) {
+        (self.from, self.to)
+    }
+
+    /// Returns `true` if the edge is connected to the anchor.
+    pub(crate) fn touches(&self, anchor: usize) -> bool {
+        self.from == anchor || self.to == anchor
+    }
+
+    /// Returns `true` if the edge connects the two anchors, in either direction.
+    pub(crate) fn connects(&self, a: usize, b: usize) -> bool {
+        (self.from, self.to) == (a, b) || (self.from, self.to) == (b, a)
+    }
+}
+
+impl PartialEq<(usize, usize)> for Edge {
+    fn eq(&self, other: &(usize, usize)) -> bool {
+        self.ends() == *other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_old_boards_read_as_default_edges() {
+        let edges: Vec<Edge> = serde_yaml::from_str("- [0, 1]\n- {from: 2, to: 3}\n").unwrap();
+        assert_eq!(edges, vec![Edge::new(0, 1), Edge::new(2, 3)]);
+    }
+
+    #[test]
+    fn test_properties_round_trip() {
+        let edge = Edge::with_props(
+            4,
+            1,
+            EdgeProps {
+                kind: EdgeKind::Bridge,
+                owner: Some(2),
+                weight: 3.5,
+                label: Some("Ford".to_string()),
+            },
+        );
+        let yaml = serde_yaml::to_string(&edge).unwrap();
+        assert_eq!(serde_yaml::from_str::<Edge>(&yaml).unwrap(), edge);
+
+        // Missing properties fall back to their defaults
+        let edge: Edge = serde_yaml::from_str("{from: 0, to: 1, props: {kind: Wall}}").unwrap();
+        assert_eq!(edge.props.kind, EdgeKind::Wall);
+        assert_eq!(edge.props.weight, 1.0);
+    }
+
+    #[test]
+    fn test_connects_either_direction() {
+        let edge = Edge::new(3, 7);
+        assert!(edge.connects(7, 3) && edge.connects(3, 7));
+        assert!(!edge.connects(3, 4));
+        assert!(edge.touches(7) && !edge.touches(4));
+        assert_eq!(edge, (3, 7));
+    }
+}
@@ -0,0 +1,853 @@
+use crate::anchor::{AnchorKind, AnchorProps};
+use crate::board::{Board, BOARD_SIZE};
+use crate::edge::{Edge, EdgeKind, EdgeProps};
+use crate::{puzzle, Anchor, Pos};
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write};
+use std::str::FromStr;
+
+/// Radius of the circle anchors without a position are placed on.
+const UNPLACED_RADIUS: f32 = BOARD_SIZE * 0.4;
+
+/// A file format boards are exchanged in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Format {
+    /// The project's own save format
+    Yaml,
+    GraphMl,
+    Gexf,
+    /// Graphviz
+    Dot,
+    /// Node-link JSON as written by networkx and d3
+    Json,
+}
+
+impl Format {
+    /// All formats, in the order they are offered in the UI.
+    pub(crate) const ALL: [Format; 5] = [Format::Yaml, Format::GraphMl, Format::Gexf, Format::Dot, Format::Json];
+
+    /// Returns the name shown in the UI.
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Format::Yaml => "YAML",
+            Format::GraphMl => "GraphML",
+            Format::Gexf => "GEXF",
+            Format::Dot => "DOT (Graphviz)",
+            Format::Json => "JSON node-link",
+        }
+    }
+
+    /// Returns the usual file extension, without the dot.
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            Format::Yaml => "yaml",
+            Format::GraphMl => "graphml",
+            Format::Gexf => "gexf",
+            Format::Dot => "dot",
+            Format::Json => "json",
+        }
+    }
+
+    /// Returns the MIME type of files in this format.
+    pub(crate) fn mime(self) -> &'static str {
+        match self {
+            Format::Yaml => "text/yaml",
+            Format::GraphMl | Format::Gexf => "application/xml",
+            Format::Dot => "text/vnd.graphviz",
+            Format::Json => "application/json",
+        }
+    }
+
+    /// Guesses the format from a file name's extension.
+    pub(crate) fn of_file(name: &str) -> Option<Self> {
+        let (_, extension) = name.rsplit_once('.')?;
+        match extension.to_ascii_lowercase().as_str() {
+            "yml" => Some(Format::Yaml),
+            "gv" => Some(Format::Dot),
+            extension => Format::ALL.into_iter().find(|format| format.extension() == extension),
+        }
+    }
+
+    /// Returns the extensions of all formats as a file chooser takes them,
+    /// like `.yaml,.graphml`.
+    pub(crate) fn file_types() -> String {
+        let extensions = Format::ALL.iter().map(|format| format.extension()).chain(["yml", "gv"]);
+        extensions.map(|extension| format!(".{}", extension)).collect::<Vec<_>>().join(",")
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    /// Parses a format from its extension, like `graphml` or `dot`.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Format::of_file(&format!(".{}", name)).ok_or_else(|| format!("unknown format {}", name))
+    }
+}
+
+/// What was found wrong with an imported board.
+///
+/// Edges referring to missing anchors, loops and repeated edges are dropped;
+/// crossings are kept, since the board can still be untangled.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Report {
+    pub(crate) anchors: usize,
+    /// Edges kept on the board
+    pub(crate) edges: usize,
+    /// Pairs of kept edges that cross
+    pub(crate) crossings: usize,
+    /// Edges connecting anchors that were already connected
+    pub(crate) duplicate_edges: usize,
+    /// Edges referring to anchors that do not exist
+    pub(crate) out_of_range: usize,
+    /// Edges from an anchor to itself
+    pub(crate) loops: usize,
+    /// Anchors without a position, placed on a circle
+    pub(crate) unplaced: usize,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} anchors, {} edges, {} crossings", self.anchors, self.edges, self.crossings)?;
+        let dropped = [
+            (self.duplicate_edges, "duplicate edges"),
+            (self.out_of_range, "edges to missing anchors"),
+            (self.loops, "loops"),
+        ];
+        for (count, what) in dropped.into_iter().filter(|(count, _)| *count > 0) {
+            write!(f, "; dropped {} {}", count, what)?;
+        }
+        if self.unplaced > 0 {
+            write!(f, "; placed {} anchors without a position", self.unplaced)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes a board in the given format.
+pub(crate) fn export(board: &Board, format: Format) -> Result<String, String> {
+    match format {
+        Format::Yaml => board.to_yaml(),
+        Format::GraphMl => Ok(to_graphml(board)),
+        Format::Gexf => Ok(to_gexf(board)),
+        Format::Dot => Ok(to_dot(board)),
+        Format::Json => serde_json::to_string_pretty(&to_json(board)).map_err(|err| err.to_string()),
+    }
+}
+
+/// Reads a board in the given format and checks it.
+///
+/// # Returns
+/// The board, cleaned up as described by the report, or why it could not be read
+pub(crate) fn import(text: &str, format: Format) -> Result<(Board, Report), String> {
+    let builder = match format {
+        // Not `Board::from_yaml`, which rejects the problems the report counts
+        Format::Yaml => from_board(serde_yaml::from_str(text).map_err(|err| err.to_string())?),
+        Format::GraphMl => from_graphml(text)?,
+        Format::Gexf => from_gexf(text)?,
+        Format::Dot => from_dot(text)?,
+        Format::Json => from_json(text)?,
+    };
+    Ok(builder.finish())
+}
+
+/// Returns the properties of an anchor as named attributes, leaving out unset ones.
+fn anchor_attributes(props: &AnchorProps) -> Vec<(&'static str, String)> {
+    let mut attributes = vec![("kind", props.kind.label().to_string())];
+    attributes.extend(props.owner.map(|owner| ("owner", owner.to_string())));
+    attributes.extend(props.capacity.map(|capacity| ("capacity", capacity.to_string())));
+    attributes.push(("hit_points", props.hit_points.to_string()));
+    attributes
+}
+
+/// Returns the properties of an edge as named attributes, leaving out unset ones.
+fn edge_attributes(props: &EdgeProps) -> Vec<(&'static str, String)> {
+    let mut attributes = vec![("kind", props.kind.label().to_string())];
+    attributes.extend(props.owner.map(|owner| ("owner", owner.to_string())));
+    attributes.push(("weight", props.weight.to_string()));
+    attributes.extend(props.label.clone().map(|label| ("label", label)));
+    attributes
+}
+
+/// Escapes text for an XML attribute or element.
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn to_graphml(board: &Board) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+    let keys = [
+        ("x", "node", "x", "float"),
+        ("y", "node", "y", "float"),
+        ("kind", "node", "kind", "string"),
+        ("owner", "node", "owner", "int"),
+        ("capacity", "node", "capacity", "int"),
+        ("hit_points", "node", "hit_points", "int"),
+        ("edge_kind", "edge", "kind", "string"),
+        ("edge_owner", "edge", "owner", "int"),
+        ("weight", "edge", "weight", "double"),
+        ("label", "edge", "label", "string"),
+    ];
+    for (id, target, name, kind) in keys {
+        let _ = writeln!(
+            out,
+            "  <key id=\"{}\" for=\"{}\" attr.name=\"{}\" attr.type=\"{}\"/>",
+            id, target, name, kind
+        );
+    }
+    out.push_str("  <graph edgedefault=\"undirected\">\n");
+    for (index, anchor) in board.anchors.iter().enumerate() {
+        let _ = writeln!(out, "    <node id=\"n{}\">", index);
+        let _ = writeln!(out, "      <data key=\"x\">{}</data>", anchor.pos.x);
+        let _ = writeln!(out, "      <data key=\"y\">{}</data>", anchor.pos.y);
+        for (name, value) in anchor_attributes(&anchor.props) {
+            let _ = writeln!(out, "      <data key=\"{}\">{}</data>", name, xml_escape(&value));
+        }
+        out.push_str("    </node>\n");
+    }
+    for edge in &board.edges {
+        let _ = writeln!(out, "    <edge source=\"n{}\" target=\"n{}\">", edge.from, edge.to);
+        for (name, value) in edge_attributes(&edge.props) {
+            // Edge keys need their own ids where the names clash with node keys
+            let key = if name == "kind" || name == "owner" { format!("edge_{}", name) } else { name.to_string() };
+            let _ = writeln!(out, "      <data key=\"{}\">{}</data>", key, xml_escape(&value));
+        }
+        out.push_str("    </edge>\n");
+    }
+    out.push_str("  </graph>\n</graphml>\n");
+    out
+}
+
+fn to_gexf(board: &Board) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<gexf xmlns=\"http://gexf.net/1.3\" xmlns:viz=\"http://gexf.net/1.3/viz\" version=\"1.3\">\n");
+    out.push_str("  <graph defaultedgetype=\"undirected\">\n");
+    out.push_str("    <attributes class=\"node\">\n");
+    for (id, kind) in [("kind", "string"), ("owner", "integer"), ("capacity", "integer"), ("hit_points", "integer")] {
+        let _ = writeln!(out, "      <attribute id=\"{}\" title=\"{}\" type=\"{}\"/>", id, id, kind);
+    }
+    out.push_str("    </attributes>\n    <attributes class=\"edge\">\n");
+    for (id, kind) in [("kind", "string"), ("owner", "integer")] {
+        let _ = writeln!(out, "      <attribute id=\"{}\" title=\"{}\" type=\"{}\"/>", id, id, kind);
+    }
+    out.push_str("    </attributes>\n    <nodes>\n");
+    let attvalues = |out: &mut String, attributes: Vec<(&str, String)>| {
+        out.push_str("        <attvalues>\n");
+        for (name, value) in attributes {
+            let _ = writeln!(out, "          <attvalue for=\"{}\" value=\"{}\"/>", name, xml_escape(&value));
+        }
+        out.push_str("        </attvalues>\n");
+    };
+    for (index, anchor) in board.anchors.iter().enumerate() {
+        let _ = writeln!(out, "      <node id=\"{}\" label=\"{}\">", index, index);
+        attvalues(&mut out, anchor_attributes(&anchor.props));
+        let _ = writeln!(out, "        <viz:position x=\"{}\" y=\"{}\" z=\"0\"/>", anchor.pos.x, anchor.pos.y);
+        out.push_str("      </node>\n");
+    }
+    out.push_str("    </nodes>\n    <edges>\n");
+    for (index, edge) in board.edges.iter().enumerate() {
+        let _ = write!(
+            out,
+            "      <edge id=\"{}\" source=\"{}\" target=\"{}\" weight=\"{}\"",
+            index, edge.from, edge.to, edge.props.weight
+        );
+        if let Some(label) = &edge.props.label {
+            let _ = write!(out, " label=\"{}\"", xml_escape(label));
+        }
+        out.push_str(">\n");
+        let attributes = edge_attributes(&edge.props)
+            .into_iter()
+            .filter(|(name, _)| *name == "kind" || *name == "owner")
+            .collect();
+        attvalues(&mut out, attributes);
+        out.push_str("      </edge>\n");
+    }
+    out.push_str("    </edges>\n  </graph>\n</gexf>\n");
+    out
+}
+
+/// Quotes text as a DOT string.
+fn dot_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn to_dot(board: &Board) -> String {
+    let list = |attributes: Vec<(&str, String)>| {
+        let pairs: Vec<String> =
+            attributes.into_iter().map(|(name, value)| format!("{}={}", name, dot_quote(&value))).collect();
+        pairs.join(", ")
+    };
+    let mut out = String::from("graph hexbattle {\n");
+    for (index, anchor) in board.anchors.iter().enumerate() {
+        // Graphviz keeps positions ending in `!` fixed when laying out
+        let position = format!("{},{}!", anchor.pos.x, anchor.pos.y);
+        let mut attributes = vec![("pos", position)];
+        attributes.extend(anchor_attributes(&anchor.props));
+        let _ = writeln!(out, "  n{} [{}];", index, list(attributes));
+    }
+    for edge in &board.edges {
+        let _ = writeln!(out, "  n{} -- n{} [{}];", edge.from, edge.to, list(edge_attributes(&edge.props)));
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn to_json(board: &Board) -> Value {
+    let object = |attributes: Vec<(&str, String)>, mut map: Map<String, Value>| {
+        for (name, value) in attributes {
+            // Numbers stay numbers, so other tools can compute with them
+            let number = match value.parse::<i64>() {
+                Ok(integer) => Some(json!(integer)),
+                Err(_) => value.parse::<f64>().ok().map(|float| json!(float)),
+            };
+            let value = number.filter(|_| name != "label").unwrap_or(Value::String(value));
+            map.insert(name.to_string(), value);
+        }
+        Value::Object(map)
+    };
+    let nodes: Vec<Value> = board
+        .anchors
+        .iter()
+        .enumerate()
+        .map(|(index, anchor)| {
+            let map = json!({ "id": index, "x": anchor.pos.x, "y": anchor.pos.y });
+            object(anchor_attributes(&anchor.props), map.as_object().unwrap().clone())
+        })
+        .collect();
+    let links: Vec<Value> = board
+        .edges
+        .iter()
+        .map(|edge| {
+            let map = json!({ "source": edge.from, "target": edge.to });
+            object(edge_attributes(&edge.props), map.as_object().unwrap().clone())
+        })
+        .collect();
+    json!({ "directed": false, "multigraph": false, "graph": {}, "nodes": nodes, "links": links })
+}
+
+/// Collects the anchors and edges of an imported graph, which other formats
+/// refer to by name rather than by index.
+#[derive(Default)]
+struct Builder {
+    anchors: Vec<Anchor>,
+    /// The coordinates given for each anchor
+    coordinates: Vec<(Option<f32>, Option<f32>)>,
+    /// The index of each anchor by its name in the file
+    names: HashMap<String, usize>,
+    /// The ends of each edge, `None` where the anchor does not exist
+    edges: Vec<(Option<usize>, Option<usize>, EdgeProps)>,
+}
+
+impl Builder {
+    /// Returns the anchor with the given name, adding it if it is new.
+    fn anchor(&mut self, name: &str) -> usize {
+        if let Some(index) = self.names.get(name) {
+            return *index;
+        }
+        self.anchors.push(Anchor::new(Pos::new(0.0, 0.0)));
+        self.coordinates.push((None, None));
+        self.names.insert(name.to_string(), self.anchors.len() - 1);
+        self.anchors.len() - 1
+    }
+
+    /// Sets a named property of an anchor, ignoring ones the board does not know.
+    fn anchor_attribute(&mut self, index: usize, name: &str, value: &str) {
+        let value = value.trim();
+        let props = &mut self.anchors[index].props;
+        match name {
+            "x" => self.coordinates[index].0 = value.parse().ok(),
+            "y" => self.coordinates[index].1 = value.parse().ok(),
+            "pos" => {
+                let mut parts = value.trim_end_matches('!').split(',').map(|part| part.trim().parse().ok());
+                self.coordinates[index] = (parts.next().flatten(), parts.next().flatten());
+            }
+            "kind" => {
+                props.kind =
+                    AnchorKind::ALL.into_iter().find(|kind| kind.label().eq_ignore_ascii_case(value)).unwrap_or_default()
+            }
+            "owner" => props.owner = value.parse().ok(),
+            "capacity" => props.capacity = value.parse().ok(),
+            "hit_points" => props.hit_points = value.parse().unwrap_or(props.hit_points),
+            _ => {}
+        }
+    }
+
+    /// Adds an edge between named anchors, which must exist already.
+    ///
+    /// # Returns
+    /// The index of the edge
+    fn edge(&mut self, from: &str, to: &str) -> usize {
+        let (from, to) = (self.names.get(from).copied(), self.names.get(to).copied());
+        self.edges.push((from, to, EdgeProps::default()));
+        self.edges.len() - 1
+    }
+
+    /// Sets a named property of an edge, ignoring ones the board does not know.
+    fn edge_attribute(&mut self, index: usize, name: &str, value: &str) {
+        let props = &mut self.edges[index].2;
+        match name {
+            "kind" => {
+                props.kind =
+                    EdgeKind::ALL.into_iter().find(|kind| kind.label().eq_ignore_ascii_case(value.trim())).unwrap_or_default()
+            }
+            "owner" => props.owner = value.trim().parse().ok(),
+            "weight" => props.weight = value.trim().parse().unwrap_or(props.weight),
+            "label" => props.label = (!value.is_empty()).then(|| value.to_string()),
+            _ => {}
+        }
+    }
+
+    /// Places the anchors, drops the edges that cannot be kept and counts the crossings.
+    fn finish(self) -> (Board, Report) {
+        let Builder { mut anchors, coordinates, edges, .. } = self;
+        let mut report = Report { anchors: anchors.len(), ..Report::default() };
+        for (index, coordinates) in coordinates.into_iter().enumerate() {
+            anchors[index].pos = match coordinates {
+                (Some(x), Some(y)) => Pos::new(x, y),
+                _ => {
+                    report.unplaced += 1;
+                    let angle = index as f32 / anchors.len() as f32 * std::f32::consts::TAU;
+                    Pos::new(angle.cos(), angle.sin()) * UNPLACED_RADIUS
+                }
+            };
+        }
+
+        let mut connected = HashSet::new();
+        let mut kept = Vec::new();
+        for (from, to, props) in edges {
+            match (from, to) {
+                (Some(from), Some(to)) if from == to => report.loops += 1,
+                (Some(from), Some(to)) if !connected.insert((from.min(to), from.max(to))) => {
+                    report.duplicate_edges += 1
+                }
+                (Some(from), Some(to)) => kept.push(Edge::with_props(from, to, props)),
+                _ => report.out_of_range += 1,
+            }
+        }
+        report.edges = kept.len();
+        report.crossings = puzzle::crossings(&anchors, &kept);
+        (Board { anchors, edges: kept }, report)
+    }
+}
+
+/// Checks a board read from the project's own format, whose edges refer to
+/// anchors by index.
+fn from_board(board: Board) -> Builder {
+    let count = board.anchors.len();
+    Builder {
+        coordinates: board.anchors.iter().map(|anchor| (Some(anchor.pos.x), Some(anchor.pos.y))).collect(),
+        anchors: board.anchors,
+        names: HashMap::new(),
+        edges: board
+            .edges
+            .into_iter()
+            .map(|edge| ((edge.from < count).then_some(edge.from), (edge.to < count).then_some(edge.to), edge.props))
+            .collect(),
+    }
+}
+
+fn from_graphml(text: &str) -> Result<Builder, String> {
+    let document = roxmltree::Document::parse(text).map_err(|err| err.to_string())?;
+    // Data refers to keys by id, which tools pick freely; the name says what it is
+    let keys: HashMap<&str, &str> = document
+        .descendants()
+        .filter(|node| node.has_tag_name("key"))
+        .filter_map(|key| Some((key.attribute("id")?, key.attribute("attr.name").or(key.attribute("id"))?)))
+        .collect();
+    let data = |element: roxmltree::Node| -> Vec<(String, String)> {
+        element
+            .children()
+            .filter(|child| child.has_tag_name("data"))
+            .filter_map(|data| {
+                let key = data.attribute("key")?;
+                Some((keys.get(key).copied().unwrap_or(key).to_string(), data.text().unwrap_or("").to_string()))
+            })
+            .collect()
+    };
+    let graph = document.descendants().find(|node| node.has_tag_name("graph")).ok_or("no graph element")?;
+
+    let mut builder = Builder::default();
+    for node in graph.children().filter(|node| node.has_tag_name("node")) {
+        let index = builder.anchor(node.attribute("id").ok_or("a node has no id")?);
+        for (name, value) in data(node) {
+            builder.anchor_attribute(index, &name, &value);
+        }
+    }
+    for edge in graph.children().filter(|node| node.has_tag_name("edge")) {
+        let (from, to) = (edge.attribute("source").unwrap_or(""), edge.attribute("target").unwrap_or(""));
+        let index = builder.edge(from, to);
+        for (name, value) in data(edge) {
+            builder.edge_attribute(index, &name, &value);
+        }
+    }
+    Ok(builder)
+}
+
+fn from_gexf(text: &str) -> Result<Builder, String> {
+    let document = roxmltree::Document::parse(text).map_err(|err| err.to_string())?;
+    // Attribute values refer to declarations by id, separately for nodes and edges
+    let titles = |class: &str| -> HashMap<String, String> {
+        document
+            .descendants()
+            .filter(|node| node.has_tag_name("attributes") && node.attribute("class") == Some(class))
+            .flat_map(|attributes| attributes.children().filter(|node| node.has_tag_name("attribute")))
+            .filter_map(|attribute| {
+                let id = attribute.attribute("id")?;
+                Some((id.to_string(), attribute.attribute("title").unwrap_or(id).to_string()))
+            })
+            .collect()
+    };
+    let (node_titles, edge_titles) = (titles("node"), titles("edge"));
+    let values = |element: roxmltree::Node, titles: &HashMap<String, String>| -> Vec<(String, String)> {
+        element
+            .descendants()
+            .filter(|node| node.has_tag_name("attvalue"))
+            .filter_map(|value| {
+                let id = value.attribute("for").or(value.attribute("id"))?;
+                Some((titles.get(id).cloned().unwrap_or(id.to_string()), value.attribute("value")?.to_string()))
+            })
+            .collect()
+    };
+
+    let mut builder = Builder::default();
+    for node in document.descendants().filter(|node| node.has_tag_name("node")) {
+        let index = builder.anchor(node.attribute("id").ok_or("a node has no id")?);
+        for (name, value) in values(node, &node_titles) {
+            builder.anchor_attribute(index, &name, &value);
+        }
+        if let Some(position) = node.children().find(|child| child.has_tag_name("position")) {
+            for axis in ["x", "y"] {
+                builder.anchor_attribute(index, axis, position.attribute(axis).unwrap_or(""));
+            }
+        }
+    }
+    for edge in document.descendants().filter(|node| node.has_tag_name("edge")) {
+        let (from, to) = (edge.attribute("source").unwrap_or(""), edge.attribute("target").unwrap_or(""));
+        let index = builder.edge(from, to);
+        for name in ["weight", "label"] {
+            if let Some(value) = edge.attribute(name) {
+                builder.edge_attribute(index, name, value);
+            }
+        }
+        for (name, value) in values(edge, &edge_titles) {
+            builder.edge_attribute(index, &name, &value);
+        }
+    }
+    Ok(builder)
+}
+
+fn from_json(text: &str) -> Result<Builder, String> {
+    let graph: Value = serde_json::from_str(text).map_err(|err| err.to_string())?;
+    // Ids may be numbers or strings, and other attributes strings or numbers
+    let text_of = |value: &Value| match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    };
+    let items = |key: &str| graph.get(key).and_then(Value::as_array).cloned().unwrap_or_default();
+
+    let mut builder = Builder::default();
+    let nodes = graph.get("nodes").and_then(Value::as_array).ok_or("no nodes list")?;
+    for (position, node) in nodes.iter().enumerate() {
+        let object = node.as_object().ok_or("a node is not an object")?;
+        let index = builder.anchor(&object.get("id").map_or(position.to_string(), text_of));
+        for (name, value) in object.iter().filter(|(name, _)| *name != "id") {
+            builder.anchor_attribute(index, name, &text_of(value));
+        }
+    }
+    // networkx calls them links, other tools edges
+    for link in items("links").iter().chain(&items("edges")) {
+        let object = link.as_object().ok_or("an edge is not an object")?;
+        let end = |key: &str| object.get(key).map(text_of).unwrap_or_default();
+        let index = builder.edge(&end("source"), &end("target"));
+        for (name, value) in object.iter().filter(|(name, _)| *name != "source" && *name != "target") {
+            builder.edge_attribute(index, name, &text_of(value));
+        }
+    }
+    Ok(builder)
+}
+
+/// A token of the DOT language.
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    /// A name, number or quoted string
+    Id(String),
+    /// `--` or `->`
+    EdgeOp,
+    Punct(char),
+}
+
+fn dot_tokens(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '#' => while chars.next_if(|c| *c != '\n').is_some() {},
+            '/' if chars.peek() == Some(&'/') => while chars.next_if(|c| *c != '\n').is_some() {},
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            '"' => {
+                let mut id = String::new();
+                loop {
+                    match chars.next().ok_or("unterminated string")? {
+                        '"' => break,
+                        '\\' if chars.peek() == Some(&'"') => id.push(chars.next().unwrap()),
+                        '\\' if chars.peek() == Some(&'\n') => {
+                            chars.next();
+                        }
+                        c => id.push(c),
+                    }
+                }
+                tokens.push(Token::Id(id));
+            }
+            '-' if matches!(chars.peek(), Some('-') | Some('>')) => {
+                chars.next();
+                tokens.push(Token::EdgeOp);
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '.' || c == '-' => {
+                let mut id = c.to_string();
+                while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_' || *c == '.') {
+                    id.push(c);
+                }
+                tokens.push(Token::Id(id));
+            }
+            '{' | '}' | '[' | ']' | '=' | ';' | ',' | ':' => tokens.push(Token::Punct(c)),
+            c => return Err(format!("unexpected character {}", c)),
+        }
+    }
+    Ok(tokens)
+}
+
+type Tokens = std::iter::Peekable<std::vec::IntoIter<Token>>;
+
+/// Reads a name, number or quoted string.
+fn dot_id(tokens: &mut Tokens) -> Result<String, String> {
+    match tokens.next() {
+        Some(Token::Id(id)) => Ok(id),
+        other => Err(format!("expected a name, found {:?}", other)),
+    }
+}
+
+/// Reads the attribute lists following a statement, like `[a=1, b=2][c=3]`.
+fn dot_attributes(tokens: &mut Tokens) -> Result<Vec<(String, String)>, String> {
+    let mut list = Vec::new();
+    while tokens.next_if_eq(&Token::Punct('[')).is_some() {
+        loop {
+            match tokens.next() {
+                Some(Token::Punct(']')) => break,
+                Some(Token::Punct(',')) | Some(Token::Punct(';')) => {}
+                Some(Token::Id(name)) => {
+                    // A name without a value is a flag
+                    let value = match tokens.next_if_eq(&Token::Punct('=')) {
+                        Some(_) => dot_id(tokens)?,
+                        None => "true".to_string(),
+                    };
+                    list.push((name, value));
+                }
+                other => return Err(format!("unexpected {:?} in attributes", other)),
+            }
+        }
+    }
+    Ok(list)
+}
+
+/// Reads the statements of a DOT graph. Subgraphs are read as if their
+/// statements were in the graph itself, and default attributes are ignored.
+fn from_dot(text: &str) -> Result<Builder, String> {
+    let mut tokens = dot_tokens(text)?.into_iter().peekable();
+    if matches!(tokens.peek(), Some(Token::Id(id)) if id.eq_ignore_ascii_case("strict")) {
+        tokens.next();
+    }
+    match dot_id(&mut tokens)?.to_ascii_lowercase().as_str() {
+        "graph" | "digraph" => {}
+        other => return Err(format!("expected graph or digraph, found {}", other)),
+    }
+    if matches!(tokens.peek(), Some(Token::Id(_))) {
+        tokens.next();
+    }
+    if tokens.next() != Some(Token::Punct('{')) {
+        return Err("expected {".to_string());
+    }
+
+    let mut builder = Builder::default();
+    let mut depth = 1;
+    while depth > 0 {
+        let token = tokens.next().ok_or("missing }")?;
+        let id = match token {
+            Token::Punct('{') => {
+                depth += 1;
+                continue;
+            }
+            Token::Punct('}') => {
+                depth -= 1;
+                continue;
+            }
+            Token::Punct(';') | Token::Punct(',') => continue,
+            Token::Id(id) => id,
+            other => return Err(format!("unexpected {:?}", other)),
+        };
+        match id.to_ascii_lowercase().as_str() {
+            "graph" | "node" | "edge" if tokens.peek() == Some(&Token::Punct('[')) => {
+                dot_attributes(&mut tokens)?;
+                continue;
+            }
+            "subgraph" => {
+                if matches!(tokens.peek(), Some(Token::Id(_))) {
+                    tokens.next();
+                }
+                continue;
+            }
+            _ => {}
+        }
+        // A graph attribute like `rankdir = LR`
+        if tokens.next_if_eq(&Token::Punct('=')).is_some() {
+            dot_id(&mut tokens)?;
+            continue;
+        }
+
+        let mut chain = vec![id];
+        loop {
+            // Ports like `a:n` only say where on the node an edge ends
+            while tokens.next_if_eq(&Token::Punct(':')).is_some() {
+                dot_id(&mut tokens)?;
+            }
+            if tokens.next_if_eq(&Token::EdgeOp).is_none() {
+                break;
+            }
+            if tokens.peek() == Some(&Token::Punct('{')) {
+                return Err("subgraphs as edge ends are not supported".to_string());
+            }
+            chain.push(dot_id(&mut tokens)?);
+        }
+        let list = dot_attributes(&mut tokens)?;
+        let ends: Vec<usize> = chain.iter().map(|name| builder.anchor(name)).collect();
+        if let [anchor] = ends[..] {
+            for (name, value) in &list {
+                builder.anchor_attribute(anchor, name, value);
+            }
+        }
+        for pair in chain.windows(2) {
+            let edge = builder.edge(&pair[0], &pair[1]);
+            for (name, value) in &list {
+                builder.edge_attribute(edge, name, value);
+            }
+        }
+    }
+    Ok(builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Board {
+        let mut anchors = vec![
+            Anchor::new(Pos::new(0.0, 0.0)),
+            Anchor::new(Pos::new(120.5, -3.25)),
+            Anchor::new(Pos::new(60.0, 90.0)),
+        ];
+        anchors[0].props = AnchorProps { kind: AnchorKind::Base, owner: Some(1), capacity: Some(3), hit_points: 40 };
+        anchors[2].props.kind = AnchorKind::Resource;
+        let label = Some("A \"gate\" & <moat>".to_string());
+        let wall = EdgeProps { kind: EdgeKind::Wall, owner: Some(0), weight: 2.5, label };
+        Board { anchors, edges: vec![Edge::with_props(0, 1, wall), Edge::new(1, 2)] }
+    }
+
+    #[test]
+    fn test_every_format_round_trips() {
+        let board = sample();
+        for format in Format::ALL {
+            let text = export(&board, format).unwrap();
+            let (imported, report) = import(&text, format).unwrap();
+            assert_eq!(imported, board, "{} changed the board", format.label());
+            assert_eq!(report, Report { anchors: 3, edges: 2, ..Report::default() });
+        }
+    }
+
+    #[test]
+    fn test_import_reports_problems() {
+        let json = r#"{
+            "nodes": [{"id": "a", "x": 0, "y": 0}, {"id": "b", "x": 100, "y": 100},
+                      {"id": "c", "x": 0, "y": 100}, {"id": "d", "x": 100, "y": 0}],
+            "links": [{"source": "a", "target": "b"}, {"source": "c", "target": "d"},
+                      {"source": "b", "target": "a"}, {"source": "a", "target": "a"},
+                      {"source": "a", "target": "zz"}]
+        }"#;
+        let (board, report) = import(json, Format::Json).unwrap();
+        assert_eq!(board.edges.len(), 2);
+        assert_eq!(
+            report,
+            Report { anchors: 4, edges: 2, crossings: 1, duplicate_edges: 1, out_of_range: 1, loops: 1, unplaced: 0 }
+        );
+        assert_eq!(
+            report.to_string(),
+            "4 anchors, 2 edges, 1 crossings; dropped 1 duplicate edges; \
+             dropped 1 edges to missing anchors; dropped 1 loops"
+        );
+
+        let yaml = "anchors:\n  - {x: 0.0, y: 0.0}\nedges:\n  - [0, 4]\n";
+        assert_eq!(import(yaml, Format::Yaml).unwrap().1.out_of_range, 1);
+    }
+
+    #[test]
+    fn test_dot_from_other_tools() {
+        let dot = r#"
+            // Written by hand, as people do
+            strict digraph "roads" {
+                rankdir = LR;
+                node [shape=circle];
+                a [pos="10,20!", kind=base owner=0];
+                a -> b -> c [weight=3, label="main road"];
+                subgraph cluster_x { c:n -- d }
+                /* d has no position either */
+            }
+        "#;
+        let (board, report) = import(dot, Format::Dot).unwrap();
+        assert_eq!(board.anchors.len(), 4);
+        assert_eq!(board.anchors[0].pos, Pos::new(10.0, 20.0));
+        assert_eq!(board.anchors[0].props.kind, AnchorKind::Base);
+        assert_eq!(board.edges[1].ends(), (1, 2));
+        assert_eq!(board.edges[1].props.weight, 3.0);
+        assert_eq!(board.edges[1].props.label.as_deref(), Some("main road"));
+        assert_eq!(report.unplaced, 3);
+        assert!(import("graph { a -- }", Format::Dot).is_err());
+    }
+
+    #[test]
+    fn test_graphml_keys_are_read_by_name() {
+        let graphml = r#"<?xml version="1.0"?>
+            <graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+              <key id="d0" for="node" attr.name="x" attr.type="double"/>
+              <key id="d1" for="node" attr.name="y" attr.type="double"/>
+              <key id="d2" for="edge" attr.name="weight" attr.type="double"/>
+              <graph edgedefault="undirected">
+                <node id="first"><data key="d0">5</data><data key="d1">-7</data></node>
+                <node id="second"><data key="d0">50</data><data key="d1">70</data></node>
+                <edge source="second" target="first"><data key="d2">4</data></edge>
+              </graph>
+            </graphml>"#;
+        let (board, report) = import(graphml, Format::GraphMl).unwrap();
+        assert_eq!(board.anchors[0].pos, Pos::new(5.0, -7.0));
+        assert_eq!(board.edges[0].ends(), (1, 0));
+        assert_eq!(board.edges[0].props.weight, 4.0);
+        assert_eq!(report.unplaced, 0);
+        assert_eq!(Format::of_file("level.GraphML"), Some(Format::GraphMl));
+        assert!(Format::file_types().split(',').all(|file_type| Format::of_file(file_type).is_some()));
+        assert_eq!("gv".parse::<Format>(), Ok(Format::Dot));
+    }
+}
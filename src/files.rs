@@ -0,0 +1,185 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Where a picked file's name and text, or why it could not be read, end up.
+type Slot = Rc<RefCell<Option<Result<(String, String), String>>>>;
+
+/// Milliseconds a download's blob URL stays valid, as browsers may still read
+/// it after the click that started the download returns.
+#[cfg(target_family = "wasm")]
+const REVOKE_DELAY_MS: i32 = 40_000;
+
+/// Hands a file to the user: a browser download on the web, a file in the
+/// working directory when running natively.
+///
+/// # Arguments
+/// * `name` - The file name to offer
+/// * `bytes` - The file contents
+/// * `mime` - The MIME type of the contents
+///
+/// # Returns
+/// Where the file went, for showing in the UI
+#[cfg(not(target_family = "wasm"))]
+pub fn save(name: &str, bytes: &[u8], _mime: &str) -> Result<String, String> {
+    std::fs::write(name, bytes).map_err(|err| err.to_string())?;
+    Ok(std::env::current_dir()
+        .map(|dir| dir.join(name).display().to_string())
+        .unwrap_or_else(|_| name.to_string()))
+}
+
+#[cfg(target_family = "wasm")]
+pub fn save(name: &str, bytes: &[u8], mime: &str) -> Result<String, String> {
+    use wasm_bindgen::JsCast;
+
+    let parts = js_sys::Array::new();
+    parts.push(&js_sys::Uint8Array::from(bytes));
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type(mime);
+    let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options)
+        .map_err(|err| format!("{:?}", err))?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob).map_err(|err| format!("{:?}", err))?;
+
+    let window = web_sys::window().ok_or("no window to download from")?;
+    let document = window.document().ok_or("no document to download from")?;
+    let link: web_sys::HtmlAnchorElement = document
+        .create_element("a")
+        .map_err(|err| format!("{:?}", err))?
+        .dyn_into()
+        .map_err(|_| "could not create download link")?;
+    link.set_href(&url);
+    link.set_download(name);
+    link.click();
+
+    let revoke = wasm_bindgen::closure::Closure::once_into_js(move || {
+        let _ = web_sys::Url::revoke_object_url(&url);
+    });
+    window
+        .set_timeout_with_callback_and_timeout_and_arguments_0(revoke.unchecked_ref(), REVOKE_DELAY_MS)
+        .map_err(|err| format!("{:?}", err))?;
+    Ok(format!("downloaded {}", name))
+}
+
+/// Keeps a text file for a later `load`: a file in the working directory
+/// when running natively, an entry in the browser's local storage on the web.
+///
+/// # Arguments
+/// * `name` - The name to store the text under
+/// * `text` - The text to store
+///
+/// # Returns
+/// Where the text went, for showing in the UI
+#[cfg(not(target_family = "wasm"))]
+pub fn store(name: &str, text: &str) -> Result<String, String> {
+    save(name, text.as_bytes(), "text/yaml")
+}
+
+#[cfg(target_family = "wasm")]
+pub fn store(name: &str, text: &str) -> Result<String, String> {
+    local_storage()?
+        .set_item(name, text)
+        .map_err(|err| format!("{:?}", err))?;
+    Ok(format!("browser storage as {}", name))
+}
+
+/// Reads back text kept with `store`.
+///
+/// # Arguments
+/// * `name` - The name the text was stored under
+#[cfg(not(target_family = "wasm"))]
+pub fn load(name: &str) -> Result<String, String> {
+    std::fs::read_to_string(name).map_err(|err| format!("{}: {}", name, err))
+}
+
+#[cfg(target_family = "wasm")]
+pub fn load(name: &str) -> Result<String, String> {
+    local_storage()?
+        .get_item(name)
+        .map_err(|err| format!("{:?}", err))?
+        .ok_or_else(|| format!("nothing stored as {}", name))
+}
+
+#[cfg(target_family = "wasm")]
+fn local_storage() -> Result<web_sys::Storage, String> {
+    web_sys::window()
+        .and_then(|window| window.local_storage().ok().flatten())
+        .ok_or_else(|| "browser storage is not available".to_string())
+}
+
+/// A file the user picks to open. On the web it is read in the background
+/// and arrives some frames after it was asked for.
+pub struct Pick(Slot);
+
+impl Pick {
+    /// Takes the name and text of the picked file once it has been read.
+    pub fn take(&self) -> Option<Result<(String, String), String>> {
+        self.0.borrow_mut().take()
+    }
+}
+
+/// Asks the user for a text file to open: a file chooser on the web, the
+/// named file in the working directory when running natively.
+///
+/// # Arguments
+/// * `name` - The file to open when running natively
+/// * `accept` - The file types the chooser offers, like `.graphml,.dot`
+#[cfg(not(target_family = "wasm"))]
+pub fn pick(name: &str, _accept: &str) -> Pick {
+    Pick(Rc::new(RefCell::new(Some(load(name).map(|text| (name.to_string(), text))))))
+}
+
+#[cfg(target_family = "wasm")]
+pub fn pick(_name: &str, accept: &str) -> Pick {
+    let pick = Pick(Slot::default());
+    if let Err(err) = choose(accept, pick.0.clone()) {
+        *pick.0.borrow_mut() = Some(Err(err));
+    }
+    pick
+}
+
+/// Opens a file chooser that reads the chosen file into the slot. Nothing
+/// arrives when the chooser is cancelled.
+#[cfg(target_family = "wasm")]
+fn choose(accept: &str, slot: Slot) -> Result<(), String> {
+    use wasm_bindgen::{closure::Closure, JsCast};
+
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or("no document to pick a file in")?;
+    let input: web_sys::HtmlInputElement = document
+        .create_element("input")
+        .map_err(|err| format!("{:?}", err))?
+        .dyn_into()
+        .map_err(|_| "could not create file chooser")?;
+    input.set_type("file");
+    input.set_accept(accept);
+
+    let chooser = input.clone();
+    let on_change = Closure::once_into_js(move || {
+        let Some(file) = chooser.files().and_then(|files| files.get(0)) else {
+            return;
+        };
+        if let Err(err) = read(file, slot.clone()) {
+            *slot.borrow_mut() = Some(Err(err));
+        }
+    });
+    input.set_onchange(Some(on_change.unchecked_ref()));
+    input.click();
+    Ok(())
+}
+
+/// Reads a chosen file as text into the slot.
+#[cfg(target_family = "wasm")]
+fn read(file: web_sys::File, slot: Slot) -> Result<(), String> {
+    use wasm_bindgen::{closure::Closure, JsCast};
+
+    let reader = web_sys::FileReader::new().map_err(|err| format!("{:?}", err))?;
+    let name = file.name();
+    let finished = reader.clone();
+    // Fires once the file was read or reading it failed, when there is no text
+    let on_load_end = Closure::once_into_js(move || {
+        let text = finished.result().ok().and_then(|result| result.as_string());
+        *slot.borrow_mut() = Some(text.map(|text| (name.clone(), text)).ok_or_else(|| format!("could not read {}", name)));
+    });
+    reader.set_onloadend(Some(on_load_end.unchecked_ref()));
+    reader.read_as_text(&file).map_err(|err| format!("{:?}", err))
+}
@@ -0,0 +1,272 @@
+use crate::analysis::Graph;
+use crate::anchor::AnchorKind;
+use crate::edge::{Edge, EdgeProps};
+use crate::Anchor;
+
+/// Share of the board's width or height counted as one of its sides.
+pub(crate) const SIDE_BAND: f32 = 0.15;
+/// Seconds each edge of the winning path takes to appear in the replay.
+pub(crate) const REPLAY_STEP: f32 = 0.3;
+
+/// What the players have to connect to win.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Goal {
+    /// All the bases a player owns
+    Bases,
+    /// Two opposite sides of the board, left and right for the first player
+    /// and bottom and top for the second, as in Hex
+    Sides,
+}
+
+impl Goal {
+    /// All goals, in the order they are offered in the UI.
+    pub(crate) const ALL: [Goal; 2] = [Goal::Bases, Goal::Sides];
+
+    /// Returns the name shown in the UI.
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Goal::Bases => "Connect your bases",
+            Goal::Sides => "Connect opposite sides",
+        }
+    }
+
+    /// Returns the most players the goal can be played with.
+    pub(crate) fn max_players(self) -> u8 {
+        match self {
+            Goal::Bases => 4,
+            Goal::Sides => 2,
+        }
+    }
+}
+
+/// How a game is set up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Rules {
+    pub(crate) goal: Goal,
+    pub(crate) players: u8,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self { goal: Goal::Bases, players: 2 }
+    }
+}
+
+/// How a game was won.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Win {
+    pub(crate) player: u8,
+    /// The edges connecting the player's goal, as indices into the edge list,
+    /// in the order the replay shows them
+    pub(crate) path: Vec<usize>,
+}
+
+/// A connection game modeled on Hex and the Shannon switching game.
+///
+/// Players take turns drawing edges, which belong to whoever drew them. Edges
+/// still must not cross, so drawing across an opponent's route blocks it.
+#[derive(Clone, Debug)]
+pub(crate) struct Game {
+    pub(crate) rules: Rules,
+    /// The player whose turn it is
+    pub(crate) turn: u8,
+    pub(crate) winner: Option<Win>,
+    /// Seconds since the replay of the winning path started
+    replay: f32,
+}
+
+impl Game {
+    /// Starts a game with the first player to move.
+    pub(crate) fn new(rules: Rules) -> Self {
+        Self {
+            rules: Rules { players: rules.players.clamp(2, rules.goal.max_players()), ..rules },
+            turn: 0,
+            winner: None,
+            replay: 0.0,
+        }
+    }
+
+    /// Returns the properties of the edges drawn now: the template, owned by
+    /// the player whose turn it is.
+    pub(crate) fn claim(&self, template: &EdgeProps) -> EdgeProps {
+        EdgeProps { owner: Some(self.turn), ..template.clone() }
+    }
+
+    /// Returns `true` once a player has won.
+    pub(crate) fn is_over(&self) -> bool {
+        self.winner.is_some()
+    }
+
+    /// Checks whether the edge just drawn won the game, and passes the turn if not.
+    pub(crate) fn edge_drawn(&mut self, anchors: &[Anchor], edges: &[Edge]) {
+        if self.is_over() {
+            return;
+        }
+        if let Some(path) = winning_path(self.rules.goal, self.turn, anchors, edges) {
+            self.winner = Some(Win { player: self.turn, path });
+            self.replay = 0.0;
+        } else {
+            self.turn = (self.turn + 1) % self.rules.players;
+        }
+    }
+
+    /// Checks a won game again after the board changed, as the winning path
+    /// refers to edges by index. The path is found again if the winner is
+    /// still connected; otherwise the win is taken back and the winner moves
+    /// again.
+    pub(crate) fn recheck(&mut self, anchors: &[Anchor], edges: &[Edge]) {
+        let Some(win) = self.winner.as_mut() else {
+            return;
+        };
+        match winning_path(self.rules.goal, win.player, anchors, edges) {
+            Some(path) => win.path = path,
+            None => {
+                self.turn = win.player;
+                self.winner = None;
+            }
+        }
+    }
+
+    /// Advances the replay of the winning path.
+    pub(crate) fn tick(&mut self, dt: f32) {
+        if self.is_over() {
+            self.replay += dt;
+        }
+    }
+
+    /// Shows the winning path being drawn again from its first edge.
+    pub(crate) fn restart_replay(&mut self) {
+        self.replay = 0.0;
+    }
+
+    /// Returns the edges of the winning path shown so far by the replay.
+    pub(crate) fn replayed(&self) -> &[usize] {
+        let Some(win) = &self.winner else {
+            return &[];
+        };
+        let shown = (self.replay / REPLAY_STEP) as usize + 1;
+        &win.path[..shown.min(win.path.len())]
+    }
+}
+
+/// Returns the groups of anchors a player has to connect, one anchor of each group.
+pub(crate) fn targets(goal: Goal, player: u8, anchors: &[Anchor]) -> Vec<Vec<usize>> {
+    match goal {
+        Goal::Bases => (0..anchors.len())
+            .filter(|index| {
+                let props = &anchors[*index].props;
+                props.kind == AnchorKind::Base && props.owner == Some(player)
+            })
+            .map(|index| vec![index])
+            .collect(),
+        Goal::Sides => {
+            // The first player plays across, the second up
+            let coordinate = |anchor: &Anchor| if player == 0 { anchor.pos.x } else { anchor.pos.y };
+            let (min, max) = anchors
+                .iter()
+                .map(coordinate)
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), value| (min.min(value), max.max(value)));
+            let band = (max - min) * SIDE_BAND;
+            let side = |near: &dyn Fn(f32) -> bool| -> Vec<usize> {
+                (0..anchors.len()).filter(|index| near(coordinate(&anchors[*index]))).collect()
+            };
+            let low = side(&|value| value <= min + band);
+            let high = side(&|value| value >= max - band);
+            if low.is_empty() || high.is_empty() {
+                return Vec::new();
+            }
+            vec![low, high]
+        }
+    }
+}
+
+/// Finds the player's own edges connecting all of their target groups.
+///
+/// # Returns
+/// The connecting edges, grown outwards from the first group, or `None` if
+/// the groups are not all connected yet or there is nothing to connect
+pub(crate) fn winning_path(goal: Goal, player: u8, anchors: &[Anchor], edges: &[Edge]) -> Option<Vec<usize>> {
+    let groups = targets(goal, player, anchors);
+    let (first, rest) = groups.split_first()?;
+    if rest.is_empty() {
+        return None;
+    }
+
+    let positions = anchors.iter().map(|anchor| anchor.pos).collect();
+    let graph = Graph::filtered(positions, edges, |edge| edge.props.owner == Some(player));
+    let mut reached = first.clone();
+    let mut path = Vec::new();
+    for group in rest {
+        let joint = graph.nearest_path(&reached, group)?;
+        reached.extend(joint.anchors);
+        path.extend(joint.edges);
+    }
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anchor::AnchorProps;
+    use crate::Pos;
+
+    fn base(x: f32, y: f32, owner: u8) -> Anchor {
+        let mut anchor = Anchor::new(Pos::new(x, y));
+        anchor.props = AnchorProps { kind: AnchorKind::Base, owner: Some(owner), ..AnchorProps::default() };
+        anchor
+    }
+
+    fn owned(from: usize, to: usize, owner: u8) -> Edge {
+        Edge::with_props(from, to, EdgeProps { owner: Some(owner), ..EdgeProps::default() })
+    }
+
+    #[test]
+    fn test_bases_connected_by_own_edges_win() {
+        let anchors = vec![
+            base(0.0, 0.0, 0),
+            Anchor::new(Pos::new(50.0, 0.0)),
+            base(100.0, 0.0, 0),
+            base(50.0, 50.0, 1),
+        ];
+        let mut edges = vec![owned(0, 1, 0), owned(1, 2, 1)];
+        // The opponent's edge does not count
+        assert_eq!(winning_path(Goal::Bases, 0, &anchors, &edges), None);
+
+        edges[1] = owned(2, 1, 0);
+        assert_eq!(winning_path(Goal::Bases, 0, &anchors, &edges), Some(vec![0, 1]));
+        // A single base has nothing to connect
+        assert_eq!(winning_path(Goal::Bases, 1, &anchors, &edges), None);
+    }
+
+    #[test]
+    fn test_sides_are_opposite_bands() {
+        let anchors: Vec<Anchor> = [(0.0, 50.0), (50.0, 0.0), (50.0, 50.0), (50.0, 100.0), (100.0, 50.0)]
+            .into_iter()
+            .map(|(x, y)| Anchor::new(Pos::new(x, y)))
+            .collect();
+        assert_eq!(targets(Goal::Sides, 0, &anchors), vec![vec![0], vec![4]]);
+        assert_eq!(targets(Goal::Sides, 1, &anchors), vec![vec![1], vec![3]]);
+
+        let edges = vec![owned(0, 2, 0), owned(2, 4, 0), owned(1, 2, 1)];
+        assert_eq!(winning_path(Goal::Sides, 0, &anchors, &edges), Some(vec![0, 1]));
+        assert_eq!(winning_path(Goal::Sides, 1, &anchors, &edges), None);
+    }
+
+    #[test]
+    fn test_turns_pass_until_a_win() {
+        let anchors = vec![base(0.0, 0.0, 0), base(100.0, 0.0, 0), base(0.0, 100.0, 1), base(100.0, 100.0, 1)];
+        let mut game = Game::new(Rules { goal: Goal::Bases, players: 2 });
+        let mut edges = vec![Edge::with_props(0, 2, game.claim(&EdgeProps::default()))];
+        game.edge_drawn(&anchors, &edges);
+        assert_eq!(game.turn, 1);
+
+        edges.push(Edge::with_props(2, 3, game.claim(&EdgeProps::default())));
+        game.edge_drawn(&anchors, &edges);
+        assert_eq!(game.winner, Some(Win { player: 1, path: vec![1] }));
+        assert_eq!(game.turn, 1);
+
+        assert_eq!(game.replayed(), &[1]);
+        game.tick(10.0);
+        assert_eq!(game.replayed(), &[1]);
+    }
+}
@@ -0,0 +1,134 @@
+use crate::anchor::AnchorProps;
+use crate::edge::{Edge, EdgeProps};
+use crate::{Anchor, Pos};
+
+/// A reversible change to the board.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Edit {
+    /// An anchor was moved from one position to another
+    MoveAnchor { index: usize, from: Pos, to: Pos },
+    /// An anchor was removed together with its edges, each stored with its
+    /// former position in the edge list, in ascending order
+    RemoveAnchor {
+        index: usize,
+        anchor: Anchor,
+        edges: Vec<(usize, Edge)>,
+    },
+    /// An edge was removed from the given position in the edge list
+    RemoveEdge { index: usize, edge: Edge },
+    /// The properties of an anchor were changed
+    ChangeAnchor { index: usize, from: AnchorProps, to: AnchorProps },
+    /// The properties of an edge were changed
+    ChangeEdge { index: usize, from: EdgeProps, to: EdgeProps },
+    /// Anchors and edges were appended to the end of their lists
+    Add {
+        anchors: Vec<Anchor>,
+        edges: Vec<Edge>,
+    },
+    /// Several edits made at once, undone and redone together
+    Group(Vec<Edit>),
+}
+
+/// Undo and redo stacks of the edits made to the board.
+///
+/// Edits refer to anchors and edges by index, so they are only valid while
+/// they are replayed in order. Anything that changes the board without
+/// being recorded must call `clear`.
+#[derive(Debug, Default)]
+pub(crate) struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    /// Whether the last edit is still being made, so a merged edit of the
+    /// same thing joins it
+    merging: bool,
+}
+
+impl History {
+    /// Records a new edit. Anything that could be redone is dropped.
+    pub(crate) fn record(&mut self, edit: Edit) {
+        self.undo.push(edit);
+        self.redo.clear();
+        self.merging = false;
+    }
+
+    /// Records an edit, merging it into the previous edit if that is still
+    /// being made and both move the same anchor or change the properties of
+    /// the same anchor or edge. Dragging a slider then undoes in one step.
+    ///
+    /// # Returns
+    /// `true` if the edit was merged into the previous one
+    pub(crate) fn record_merged(&mut self, edit: Edit) -> bool {
+        if !self.merging {
+            self.record(edit);
+            self.merging = true;
+            return false;
+        }
+        match (self.undo.last_mut(), &edit) {
+            (Some(Edit::MoveAnchor { index, to, .. }), Edit::MoveAnchor { index: new_index, to: new_to, .. })
+                if index == new_index =>
+            {
+                *to = *new_to;
+                self.redo.clear();
+                true
+            }
+            (Some(Edit::ChangeAnchor { index, to, .. }), Edit::ChangeAnchor { index: new_index, to: new_to, .. })
+                if index == new_index =>
+            {
+                *to = new_to.clone();
+                self.redo.clear();
+                true
+            }
+            (Some(Edit::ChangeEdge { index, to, .. }), Edit::ChangeEdge { index: new_index, to: new_to, .. })
+                if index == new_index =>
+            {
+                *to = new_to.clone();
+                self.redo.clear();
+                true
+            }
+            _ => {
+                self.record(edit);
+                self.merging = true;
+                false
+            }
+        }
+    }
+
+    /// Ends the edit being made, so the next merged edit is recorded on its
+    /// own. Called once the widget making it is let go.
+    pub(crate) fn seal(&mut self) {
+        self.merging = false;
+    }
+
+    /// Takes the most recent edit to revert it, remembering it for `redo`.
+    pub(crate) fn undo(&mut self) -> Option<Edit> {
+        self.merging = false;
+        let edit = self.undo.pop()?;
+        self.redo.push(edit.clone());
+        Some(edit)
+    }
+
+    /// Takes the most recently reverted edit to apply it again.
+    pub(crate) fn redo(&mut self) -> Option<Edit> {
+        self.merging = false;
+        let edit = self.redo.pop()?;
+        self.undo.push(edit.clone());
+        Some(edit)
+    }
+
+    /// Returns `true` if there is an edit to undo.
+    pub(crate) fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    /// Returns `true` if there is an edit to redo.
+    pub(crate) fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Forgets all edits.
+    pub(crate) fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.merging = false;
+    }
+}
@@ -0,0 +1,395 @@
+use crate::Pos;
+use nannou::prelude::*;
+use std::collections::BTreeMap;
+
+/// Screen distance a mouse may travel between press and release of a click.
+pub const CLICK_TOLERANCE: f32 = 4.0;
+/// Screen distance a finger may travel between touching and lifting for a tap.
+pub const TAP_TOLERANCE: f32 = 12.0;
+/// Seconds a finger has to rest before a long press opens the context action.
+pub const LONG_PRESS: f32 = 0.5;
+/// How much larger anchors and edges are to touch than to click.
+pub const TOUCH_TOLERANCE_SCALE: f32 = 2.5;
+
+/// What the pointer is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Device {
+    Mouse,
+    /// A finger or a pen
+    Touch,
+}
+
+/// What a press is meant to do.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Intent {
+    /// Use the current tool; `shift` moves anchors or extends the selection
+    Primary { shift: bool },
+    /// Move the anchor under the pointer
+    Move,
+}
+
+/// An editor command produced from mouse or touch input.
+///
+/// Positions are screen positions; the editor converts them to the board.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Command {
+    /// The pointer went down
+    Press { at: Pos, intent: Intent, device: Device },
+    /// The pointer went up. `tap` is set when it barely moved since the press
+    Release { at: Pos, tap: bool },
+    /// Abort the gesture in progress without applying it
+    Abort,
+    /// The context action at a position: delete what is there
+    Context { at: Pos },
+    /// Zoom by `factor`, keeping `at` in place
+    Zoom { at: Pos, factor: f32 },
+    /// Move the view along with the pointer by a screen offset
+    Pan { by: Pos },
+}
+
+/// A press that has not been released yet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Press {
+    /// Where the press started
+    at: Pos,
+    /// Seconds the press has been held still
+    held: f32,
+    /// Whether it moved too far to be a tap or a long press
+    moved: bool,
+    /// Whether it came from the right mouse button
+    secondary: bool,
+}
+
+/// Turns mouse and touch events into editor commands, so both drive the
+/// editor the same way.
+#[derive(Clone, Debug)]
+pub(crate) struct Input {
+    /// The last screen position of the mouse or the first finger
+    pub(crate) pointer: Pos,
+    /// The device that was used last
+    pub(crate) device: Device,
+    press: Option<Press>,
+    /// Whether the view is being dragged with the middle mouse button
+    panning: bool,
+    /// Screen positions of the fingers on the screen
+    touches: BTreeMap<u64, Pos>,
+    /// Whether two fingers pinch; ends when all fingers are lifted
+    pinching: bool,
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self {
+            pointer: Pos::new(0.0, 0.0),
+            device: Device::Mouse,
+            press: None,
+            panning: false,
+            touches: BTreeMap::new(),
+            pinching: false,
+        }
+    }
+}
+
+impl Input {
+    /// Handles a mouse button going down.
+    pub(crate) fn mouse_pressed(&mut self, button: MouseButton, shift: bool) -> Vec<Command> {
+        self.device = Device::Mouse;
+        let intent = match button {
+            MouseButton::Left => Intent::Primary { shift },
+            MouseButton::Right => Intent::Move,
+            MouseButton::Middle => {
+                self.panning = true;
+                return Vec::new();
+            }
+            _ => return Vec::new(),
+        };
+        self.start_press(intent == Intent::Move);
+        vec![Command::Press { at: self.pointer, intent, device: Device::Mouse }]
+    }
+
+    /// Handles a mouse button going up.
+    pub(crate) fn mouse_released(&mut self, button: MouseButton) -> Vec<Command> {
+        match button {
+            MouseButton::Middle => {
+                self.panning = false;
+                Vec::new()
+            }
+            MouseButton::Left | MouseButton::Right => self.end_press(CLICK_TOLERANCE).into_iter().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Handles the mouse moving to a screen position.
+    pub(crate) fn mouse_moved(&mut self, at: Pos) -> Vec<Command> {
+        let by = at - self.pointer;
+        self.device = Device::Mouse;
+        self.move_pointer(at, CLICK_TOLERANCE);
+        if self.panning {
+            vec![Command::Pan { by }]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Handles the mouse wheel turning by a number of notches.
+    pub(crate) fn wheel(&mut self, notches: f32) -> Vec<Command> {
+        vec![Command::Zoom { at: self.pointer, factor: crate::camera::ZOOM_STEP.powf(notches) }]
+    }
+
+    /// Handles a finger or pen touching, moving on or leaving the screen.
+    ///
+    /// One finger drags like the left mouse button; resting it opens the
+    /// context action. A second finger aborts that and pinches to zoom and
+    /// pans with the midpoint of the two fingers. Fingers whose touch did not
+    /// start here, as it was on the UI, are ignored.
+    pub(crate) fn touch(&mut self, id: u64, phase: TouchPhase, at: Pos) -> Vec<Command> {
+        self.device = Device::Touch;
+        match phase {
+            TouchPhase::Started => {
+                self.touches.insert(id, at);
+                match self.touches.len() {
+                    1 if !self.pinching => {
+                        self.pointer = at;
+                        self.start_press(false);
+                        vec![Command::Press { at, intent: Intent::Primary { shift: false }, device: Device::Touch }]
+                    }
+                    2 => {
+                        self.pinching = true;
+                        let aborted = self.press.take().is_some();
+                        aborted.then_some(Command::Abort).into_iter().collect()
+                    }
+                    _ => Vec::new(),
+                }
+            }
+            _ if !self.touches.contains_key(&id) => Vec::new(),
+            TouchPhase::Moved => {
+                let before = self.pinch();
+                if let Some(touch) = self.touches.get_mut(&id) {
+                    *touch = at;
+                }
+                match (before, self.pinch()) {
+                    (Some((center, spread)), Some((new_center, new_spread))) => {
+                        let mut commands = vec![Command::Pan { by: new_center - center }];
+                        if spread > 0.0 && new_spread > 0.0 {
+                            commands.push(Command::Zoom { at: new_center, factor: new_spread / spread });
+                        }
+                        commands
+                    }
+                    _ if !self.pinching => {
+                        self.move_pointer(at, TAP_TOLERANCE);
+                        Vec::new()
+                    }
+                    _ => Vec::new(),
+                }
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                self.touches.remove(&id);
+                if self.touches.is_empty() {
+                    self.pinching = false;
+                }
+                match phase {
+                    TouchPhase::Ended => {
+                        self.pointer = at;
+                        self.end_press(TAP_TOLERANCE).into_iter().collect()
+                    }
+                    _ => self.press.take().map(|_| Command::Abort).into_iter().collect(),
+                }
+            }
+        }
+    }
+
+    /// Advances time, firing the context action of a finger held still long enough.
+    pub(crate) fn tick(&mut self, dt: f32) -> Vec<Command> {
+        if self.device != Device::Touch {
+            return Vec::new();
+        }
+        let Some(press) = self.press.as_mut() else {
+            return Vec::new();
+        };
+        if press.moved {
+            return Vec::new();
+        }
+
+        press.held += dt;
+        if press.held < LONG_PRESS {
+            return Vec::new();
+        }
+        let at = press.at;
+        self.press = None;
+        vec![Command::Abort, Command::Context { at }]
+    }
+
+    fn start_press(&mut self, secondary: bool) {
+        self.press = Some(Press {
+            at: self.pointer,
+            held: 0.0,
+            moved: false,
+            secondary,
+        });
+    }
+
+    fn move_pointer(&mut self, at: Pos, tolerance: f32) {
+        self.pointer = at;
+        if let Some(press) = self.press.as_mut() {
+            press.moved |= press.at.distance(&at) > tolerance;
+        }
+    }
+
+    /// Ends the press; a right click without dragging becomes the context action.
+    fn end_press(&mut self, tolerance: f32) -> Option<Command> {
+        let press = self.press.take()?;
+        let tap = !press.moved && press.at.distance(&self.pointer) <= tolerance;
+        Some(if press.secondary && tap {
+            Command::Context { at: press.at }
+        } else {
+            Command::Release { at: self.pointer, tap }
+        })
+    }
+
+    /// Returns the midpoint and distance of the first two fingers.
+    fn pinch(&self) -> Option<(Pos, f32)> {
+        let mut touches = self.touches.values();
+        let (a, b) = (*touches.next()?, *touches.next()?);
+        Some(((a + b) * 0.5, a.distance(&b)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(input: &mut Input, id: u64, phase: TouchPhase, x: f32, y: f32) -> Vec<Command> {
+        input.touch(id, phase, Pos::new(x, y))
+    }
+
+    #[test]
+    fn test_mouse_drag_and_click() {
+        let mut input = Input::default();
+        input.mouse_moved(Pos::new(10.0, 10.0));
+        assert_eq!(
+            input.mouse_pressed(MouseButton::Left, false),
+            vec![Command::Press { at: Pos::new(10.0, 10.0), intent: Intent::Primary { shift: false }, device: Device::Mouse }]
+        );
+        input.mouse_moved(Pos::new(80.0, 10.0));
+        assert_eq!(
+            input.mouse_released(MouseButton::Left),
+            vec![Command::Release { at: Pos::new(80.0, 10.0), tap: false }]
+        );
+
+        input.mouse_pressed(MouseButton::Left, false);
+        input.mouse_moved(Pos::new(82.0, 11.0));
+        assert_eq!(
+            input.mouse_released(MouseButton::Left),
+            vec![Command::Release { at: Pos::new(82.0, 11.0), tap: true }]
+        );
+    }
+
+    #[test]
+    fn test_release_without_press_does_nothing() {
+        // Presses on the UI never reach the input, so neither do their releases
+        let mut input = Input::default();
+        assert!(input.mouse_released(MouseButton::Left).is_empty());
+        assert!(input.mouse_released(MouseButton::Right).is_empty());
+    }
+
+    #[test]
+    fn test_right_click_is_context_and_right_drag_moves() {
+        let mut input = Input::default();
+        input.mouse_pressed(MouseButton::Right, false);
+        assert_eq!(input.mouse_released(MouseButton::Right), vec![Command::Context { at: Pos::new(0.0, 0.0) }]);
+
+        input.mouse_pressed(MouseButton::Right, false);
+        input.mouse_moved(Pos::new(30.0, 0.0));
+        assert_eq!(
+            input.mouse_released(MouseButton::Right),
+            vec![Command::Release { at: Pos::new(30.0, 0.0), tap: false }]
+        );
+    }
+
+    #[test]
+    fn test_middle_drag_pans() {
+        let mut input = Input::default();
+        input.mouse_pressed(MouseButton::Middle, false);
+        assert_eq!(input.mouse_moved(Pos::new(5.0, -3.0)), vec![Command::Pan { by: Pos::new(5.0, -3.0) }]);
+        input.mouse_released(MouseButton::Middle);
+        assert!(input.mouse_moved(Pos::new(50.0, 0.0)).is_empty());
+    }
+
+    #[test]
+    fn test_finger_drag_matches_mouse_drag() {
+        let mut input = Input::default();
+        assert_eq!(
+            touch(&mut input, 1, TouchPhase::Started, 10.0, 10.0),
+            vec![Command::Press { at: Pos::new(10.0, 10.0), intent: Intent::Primary { shift: false }, device: Device::Touch }]
+        );
+        touch(&mut input, 1, TouchPhase::Moved, 100.0, 10.0);
+        assert_eq!(input.pointer, Pos::new(100.0, 10.0));
+        assert_eq!(
+            touch(&mut input, 1, TouchPhase::Ended, 100.0, 10.0),
+            vec![Command::Release { at: Pos::new(100.0, 10.0), tap: false }]
+        );
+    }
+
+    #[test]
+    fn test_taps_tolerate_finger_jitter() {
+        let mut input = Input::default();
+        touch(&mut input, 1, TouchPhase::Started, 0.0, 0.0);
+        touch(&mut input, 1, TouchPhase::Moved, 8.0, 5.0);
+        assert_eq!(
+            touch(&mut input, 1, TouchPhase::Ended, 8.0, 5.0),
+            vec![Command::Release { at: Pos::new(8.0, 5.0), tap: true }]
+        );
+    }
+
+    #[test]
+    fn test_long_press_opens_context_action() {
+        let mut input = Input::default();
+        touch(&mut input, 1, TouchPhase::Started, 20.0, 20.0);
+        assert!(input.tick(0.3).is_empty());
+        assert_eq!(input.tick(0.3), vec![Command::Abort, Command::Context { at: Pos::new(20.0, 20.0) }]);
+        assert!(input.tick(1.0).is_empty());
+        assert!(touch(&mut input, 1, TouchPhase::Ended, 20.0, 20.0).is_empty());
+
+        // Moving the finger is a drag, not a long press
+        touch(&mut input, 1, TouchPhase::Started, 20.0, 20.0);
+        touch(&mut input, 1, TouchPhase::Moved, 80.0, 20.0);
+        assert!(input.tick(1.0).is_empty());
+    }
+
+    #[test]
+    fn test_two_fingers_pinch_and_pan() {
+        let mut input = Input::default();
+        touch(&mut input, 1, TouchPhase::Started, -10.0, 0.0);
+        assert_eq!(touch(&mut input, 2, TouchPhase::Started, 10.0, 0.0), vec![Command::Abort]);
+
+        assert_eq!(
+            touch(&mut input, 2, TouchPhase::Moved, 30.0, 0.0),
+            vec![
+                Command::Pan { by: Pos::new(10.0, 0.0) },
+                Command::Zoom { at: Pos::new(10.0, 0.0), factor: 2.0 },
+            ]
+        );
+
+        // Lifting the fingers neither releases nor presses anything
+        assert!(touch(&mut input, 2, TouchPhase::Ended, 30.0, 0.0).is_empty());
+        assert!(touch(&mut input, 1, TouchPhase::Moved, 0.0, 0.0).is_empty());
+        assert!(input.tick(1.0).is_empty());
+        assert!(touch(&mut input, 1, TouchPhase::Ended, 0.0, 0.0).is_empty());
+        assert!(!touch(&mut input, 1, TouchPhase::Started, 0.0, 0.0).is_empty());
+    }
+
+    #[test]
+    fn test_unknown_fingers_are_ignored() {
+        let mut input = Input::default();
+        touch(&mut input, 1, TouchPhase::Started, 0.0, 0.0);
+
+        // A finger that started on the UI neither moves, pinches nor releases
+        assert!(touch(&mut input, 2, TouchPhase::Moved, 50.0, 0.0).is_empty());
+        assert!(touch(&mut input, 2, TouchPhase::Ended, 50.0, 0.0).is_empty());
+        assert_eq!(input.pointer, Pos::new(0.0, 0.0));
+        assert_eq!(
+            touch(&mut input, 1, TouchPhase::Ended, 0.0, 0.0),
+            vec![Command::Release { at: Pos::new(0.0, 0.0), tap: true }]
+        );
+    }
+}
@@ -0,0 +1,358 @@
+use nannou::prelude::*;
+use nannou::winit::event::ModifiersState;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// File the key bindings are saved to.
+pub const KEYMAP_FILE: &str = "hexbattle-keys.yaml";
+
+/// Keys that can be bound, looked up by their name in the bindings file.
+const KEYS: &[Key] = &[
+    Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5, Key::Key6, Key::Key7, Key::Key8, Key::Key9, Key::Key0,
+    Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I, Key::J, Key::K, Key::L, Key::M,
+    Key::N, Key::O, Key::P, Key::Q, Key::R, Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+    Key::Escape, Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8, Key::F9, Key::F10,
+    Key::F11, Key::F12, Key::Insert, Key::Home, Key::Delete, Key::End, Key::PageDown, Key::PageUp,
+    Key::Left, Key::Up, Key::Right, Key::Down, Key::Back, Key::Return, Key::Space, Key::Tab,
+    Key::Minus, Key::Equals, Key::Plus, Key::Comma, Key::Period, Key::Slash,
+    Key::NumpadAdd, Key::NumpadSubtract,
+];
+
+/// Something the user can do from the keyboard.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    PlaceTool,
+    ConnectTool,
+    RectSelectTool,
+    LassoTool,
+    Undo,
+    Redo,
+    Delete,
+    SelectAll,
+    Copy,
+    Cut,
+    Paste,
+    Cancel,
+    Randomize,
+    RandomizePlanar,
+    Triangulate,
+    Clear,
+    ToggleWiggle,
+    ZoomIn,
+    ZoomOut,
+    ResetView,
+    NewBoard,
+    Save,
+    Load,
+    FocusLeft,
+    FocusRight,
+    FocusUp,
+    FocusDown,
+    Confirm,
+}
+
+impl Action {
+    /// Every action, in the order of the bindings panel.
+    pub const ALL: [Action; 28] = [
+        Action::PlaceTool,
+        Action::ConnectTool,
+        Action::RectSelectTool,
+        Action::LassoTool,
+        Action::Undo,
+        Action::Redo,
+        Action::Delete,
+        Action::SelectAll,
+        Action::Copy,
+        Action::Cut,
+        Action::Paste,
+        Action::Cancel,
+        Action::Randomize,
+        Action::RandomizePlanar,
+        Action::Triangulate,
+        Action::Clear,
+        Action::ToggleWiggle,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::ResetView,
+        Action::NewBoard,
+        Action::Save,
+        Action::Load,
+        Action::FocusLeft,
+        Action::FocusRight,
+        Action::FocusUp,
+        Action::FocusDown,
+        Action::Confirm,
+    ];
+
+    /// Returns a short description for the bindings panel.
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::PlaceTool => "Place tool",
+            Action::ConnectTool => "Connect tool",
+            Action::RectSelectTool => "Rectangle select tool",
+            Action::LassoTool => "Lasso tool",
+            Action::Undo => "Undo",
+            Action::Redo => "Redo",
+            Action::Delete => "Delete",
+            Action::SelectAll => "Select all",
+            Action::Copy => "Copy",
+            Action::Cut => "Cut",
+            Action::Paste => "Paste",
+            Action::Cancel => "Cancel / deselect",
+            Action::Randomize => "Randomize connections",
+            Action::RandomizePlanar => "Randomize connections without crossings",
+            Action::Triangulate => "Triangulate",
+            Action::Clear => "Clear connections",
+            Action::ToggleWiggle => "Toggle wiggle",
+            Action::ZoomIn => "Zoom in",
+            Action::ZoomOut => "Zoom out",
+            Action::ResetView => "Reset view",
+            Action::NewBoard => "New board",
+            Action::Save => "Save board",
+            Action::Load => "Load board",
+            Action::FocusLeft => "Focus anchor to the left",
+            Action::FocusRight => "Focus anchor to the right",
+            Action::FocusUp => "Focus anchor above",
+            Action::FocusDown => "Focus anchor below",
+            Action::Confirm => "Start or finish edge at focus",
+        }
+    }
+}
+
+/// A key with modifiers, bound to an action.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Binding {
+    /// The bound action
+    pub action: Action,
+    /// Name of the key, e.g. `Z`, `Return` or `Key1`
+    pub key: String,
+    /// Whether Ctrl (or Cmd on macOS) must be held
+    #[serde(default)]
+    pub ctrl: bool,
+    /// Whether Shift must be held
+    #[serde(default)]
+    pub shift: bool,
+    /// Whether Alt must be held
+    #[serde(default)]
+    pub alt: bool,
+}
+
+impl Binding {
+    /// Binds a key without modifiers.
+    pub fn new(action: Action, key: Key) -> Self {
+        Self {
+            action,
+            key: key_name(key),
+            ctrl: false,
+            shift: false,
+            alt: false,
+        }
+    }
+
+    /// Binds a key with the modifiers currently held.
+    pub fn with_mods(action: Action, key: Key, mods: &ModifiersState) -> Self {
+        Self {
+            ctrl: mods.ctrl() || mods.logo(),
+            shift: mods.shift(),
+            alt: mods.alt(),
+            ..Self::new(action, key)
+        }
+    }
+
+    fn ctrl(self) -> Self {
+        Self { ctrl: true, ..self }
+    }
+
+    fn shift(self) -> Self {
+        Self { shift: true, ..self }
+    }
+
+    fn alt(self) -> Self {
+        Self { alt: true, ..self }
+    }
+
+    /// Returns `true` if the key and exactly these modifiers are pressed.
+    pub fn matches(&self, key: Key, mods: &ModifiersState) -> bool {
+        self.key == key_name(key)
+            && self.ctrl == (mods.ctrl() || mods.logo())
+            && self.shift == mods.shift()
+            && self.alt == mods.alt()
+    }
+
+    fn same_chord(&self, other: &Binding) -> bool {
+        self.key == other.key && self.ctrl == other.ctrl && self.shift == other.shift && self.alt == other.alt
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        write!(f, "{}", self.key.strip_prefix("Key").filter(|rest| !rest.is_empty()).unwrap_or(&self.key))
+    }
+}
+
+/// Maps keys to actions.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Keymap {
+    /// All bindings; an action may have several
+    pub bindings: Vec<Binding>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        use Action::*;
+        Self {
+            bindings: vec![
+                Binding::new(PlaceTool, Key::P),
+                Binding::new(ConnectTool, Key::C),
+                Binding::new(RectSelectTool, Key::R),
+                Binding::new(LassoTool, Key::L),
+                Binding::new(Undo, Key::Z).ctrl(),
+                Binding::new(Redo, Key::Z).ctrl().shift(),
+                Binding::new(Redo, Key::Y).ctrl(),
+                Binding::new(Delete, Key::Delete),
+                Binding::new(Delete, Key::Back),
+                Binding::new(SelectAll, Key::A).ctrl(),
+                Binding::new(Copy, Key::C).ctrl(),
+                Binding::new(Cut, Key::X).ctrl(),
+                Binding::new(Paste, Key::V).ctrl(),
+                Binding::new(Cancel, Key::Escape),
+                Binding::new(Randomize, Key::R).shift(),
+                Binding::new(RandomizePlanar, Key::R).alt(),
+                Binding::new(Triangulate, Key::T),
+                Binding::new(Clear, Key::C).shift(),
+                Binding::new(ToggleWiggle, Key::W),
+                Binding::new(ZoomIn, Key::Plus),
+                Binding::new(ZoomIn, Key::Equals),
+                Binding::new(ZoomIn, Key::NumpadAdd),
+                Binding::new(ZoomOut, Key::Minus),
+                Binding::new(ZoomOut, Key::NumpadSubtract),
+                Binding::new(ResetView, Key::Key0),
+                Binding::new(NewBoard, Key::N).ctrl(),
+                Binding::new(Save, Key::S).ctrl(),
+                Binding::new(Load, Key::O).ctrl(),
+                Binding::new(FocusLeft, Key::Left),
+                Binding::new(FocusRight, Key::Right),
+                Binding::new(FocusUp, Key::Up),
+                Binding::new(FocusDown, Key::Down),
+                Binding::new(Confirm, Key::Return),
+                Binding::new(Confirm, Key::Space),
+            ],
+        }
+    }
+}
+
+impl Keymap {
+    /// Returns the action bound to a key pressed with the given modifiers.
+    pub fn action(&self, key: Key, mods: &ModifiersState) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|binding| binding.matches(key, mods))
+            .map(|binding| binding.action)
+    }
+
+    /// Returns the bindings of an action.
+    pub fn bindings_of(&self, action: Action) -> impl Iterator<Item = &Binding> {
+        self.bindings.iter().filter(move |binding| binding.action == action)
+    }
+
+    /// Makes `binding` the only binding of its action. Whatever else was bound
+    /// to the same keys is unbound.
+    pub fn rebind(&mut self, binding: Binding) {
+        self.bindings
+            .retain(|other| other.action != binding.action && !other.same_chord(&binding));
+        self.bindings.push(binding);
+    }
+
+    /// Serializes the bindings to YAML.
+    pub fn to_yaml(&self) -> Result<String, String> {
+        serde_yaml::to_string(self).map_err(|err| err.to_string())
+    }
+
+    /// Reads bindings from YAML, rejecting unknown key names.
+    pub fn from_yaml(yaml: &str) -> Result<Self, String> {
+        let keymap: Keymap = serde_yaml::from_str(yaml).map_err(|err| err.to_string())?;
+        match keymap.bindings.iter().find(|binding| key_from_name(&binding.key).is_none()) {
+            Some(binding) => Err(format!("unknown key '{}' for {:?}", binding.key, binding.action)),
+            None => Ok(keymap),
+        }
+    }
+}
+
+/// Returns the name a key is stored under in the bindings file.
+pub fn key_name(key: Key) -> String {
+    format!("{:?}", key)
+}
+
+/// Looks up a bindable key by name.
+pub fn key_from_name(name: &str) -> Option<Key> {
+    KEYS.iter().copied().find(|key| key_name(*key) == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_bindings_respect_modifiers() {
+        let keymap = Keymap::default();
+        let none = ModifiersState::empty();
+        let ctrl = ModifiersState::CTRL;
+        let ctrl_shift = ModifiersState::CTRL | ModifiersState::SHIFT;
+
+        assert_eq!(keymap.action(Key::C, &none), Some(Action::ConnectTool));
+        assert_eq!(keymap.action(Key::C, &ctrl), Some(Action::Copy));
+        assert_eq!(keymap.action(Key::Z, &ctrl), Some(Action::Undo));
+        assert_eq!(keymap.action(Key::Z, &ctrl_shift), Some(Action::Redo));
+        assert_eq!(keymap.action(Key::Z, &none), None);
+    }
+
+    #[test]
+    fn test_every_action_has_a_default_binding() {
+        let keymap = Keymap::default();
+        for action in Action::ALL {
+            assert!(keymap.bindings_of(action).next().is_some(), "{:?} is unbound", action);
+        }
+    }
+
+    #[test]
+    fn test_rebind_replaces_action_and_chord() {
+        let mut keymap = Keymap::default();
+        keymap.rebind(Binding::new(Action::ToggleWiggle, Key::P));
+
+        let none = ModifiersState::empty();
+        assert_eq!(keymap.action(Key::P, &none), Some(Action::ToggleWiggle));
+        assert_eq!(keymap.action(Key::W, &none), None);
+        assert_eq!(keymap.bindings_of(Action::PlaceTool).count(), 0);
+    }
+
+    #[test]
+    fn test_yaml_round_trip() {
+        let mut keymap = Keymap::default();
+        keymap.rebind(Binding::with_mods(Action::Save, Key::F5, &ModifiersState::ALT));
+        let yaml = keymap.to_yaml().unwrap();
+        assert_eq!(Keymap::from_yaml(&yaml).unwrap(), keymap);
+    }
+
+    #[test]
+    fn test_yaml_modifiers_default_to_off() {
+        let keymap = Keymap::from_yaml("bindings:\n  - action: Undo\n    key: U\n").unwrap();
+        assert_eq!(keymap.action(Key::U, &ModifiersState::empty()), Some(Action::Undo));
+        assert!(Keymap::from_yaml("bindings:\n  - action: Undo\n    key: Hyper\n").is_err());
+    }
+
+    #[test]
+    fn test_binding_labels() {
+        assert_eq!(Binding::new(Action::Redo, Key::Z).ctrl().shift().to_string(), "Ctrl+Shift+Z");
+        assert_eq!(Binding::new(Action::ResetView, Key::Key0).to_string(), "0");
+        assert_eq!(Binding::new(Action::Confirm, Key::Return).to_string(), "Return");
+    }
+}
@@ -0,0 +1,433 @@
+use crate::board::BOARD_SIZE;
+use crate::edge::Edge;
+use crate::{LineSegment, Pos};
+use std::collections::{HashSet, VecDeque};
+
+/// Anchors moving less than this in a step count as settled.
+pub(crate) const TOLERANCE: f32 = 0.5;
+/// Most steps a run to convergence takes.
+pub(crate) const MAX_STEPS: usize = 500;
+/// Farthest an anchor may move in the first step, in ideal edge lengths.
+const START_TEMPERATURE: f32 = 2.0;
+/// How much of the temperature is left after each step.
+const COOLING: f32 = 0.95;
+/// Closest two anchors are taken to be, so forces stay finite.
+const MIN_DISTANCE: f32 = 1.0;
+/// How often a move that would add crossings is halved before it is given up.
+const PLANAR_RETRIES: usize = 3;
+
+/// How the anchors are pushed apart and pulled together.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Algorithm {
+    /// Every pair repels and edges attract, with the steps cooling down
+    FruchtermanReingold,
+    /// Eades' model: edges are logarithmic springs and unconnected pairs repel
+    SpringElectrical,
+    /// Anchors are placed so their distances match the graph distances
+    KamadaKawai,
+}
+
+impl Algorithm {
+    /// All algorithms, in the order they are offered in the UI.
+    pub(crate) const ALL: [Algorithm; 3] =
+        [Algorithm::FruchtermanReingold, Algorithm::SpringElectrical, Algorithm::KamadaKawai];
+
+    /// Returns the name shown in the UI.
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Algorithm::FruchtermanReingold => "Fruchterman-Reingold",
+            Algorithm::SpringElectrical => "Spring-electrical",
+            Algorithm::KamadaKawai => "Kamada-Kawai",
+        }
+    }
+}
+
+/// How a layout is run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct LayoutOptions {
+    pub(crate) algorithm: Algorithm,
+    /// Whether the selected anchors stay where they are
+    pub(crate) pin_selection: bool,
+    /// Whether the anchors are kept inside the board
+    pub(crate) keep_inside: bool,
+    /// Whether moves that would make edges cross more are rejected
+    pub(crate) planar: bool,
+}
+
+impl Default for LayoutOptions {
+    fn default() -> Self {
+        Self { algorithm: Algorithm::FruchtermanReingold, pin_selection: true, keep_inside: true, planar: false }
+    }
+}
+
+/// A force-directed layout, stepped one frame at a time or run until the
+/// anchors settle.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Layout {
+    pub(crate) options: LayoutOptions,
+    /// Farthest an anchor may move in the next step, or `None` before the first
+    temperature: Option<f32>,
+    /// Graph distances for Kamada-Kawai, kept until the edges change
+    distances: Option<Distances>,
+}
+
+/// The hop distances between all anchors of a graph.
+#[derive(Clone, Debug)]
+struct Distances {
+    /// The number of anchors
+    count: usize,
+    /// The ends of the edges the distances were found for
+    ends: Vec<(usize, usize)>,
+    /// The distance of every pair, row by row
+    hops: Vec<f32>,
+}
+
+impl Layout {
+    pub(crate) fn new(options: LayoutOptions) -> Self {
+        Self { options, ..Self::default() }
+    }
+
+    /// Heats the layout up again, so the next step starts a new run.
+    pub(crate) fn restart(&mut self) {
+        self.temperature = None;
+    }
+
+    /// Computes where the anchors move in one step.
+    ///
+    /// # Arguments
+    /// * `positions` - Where the anchors are
+    /// * `edges` - The edges pulling them together
+    /// * `pinned` - Whether each anchor has to stay where it is
+    ///
+    /// # Returns
+    /// The new position of every anchor
+    pub(crate) fn step(&mut self, positions: &[Pos], edges: &[Edge], pinned: &[bool]) -> Vec<Pos> {
+        let ideal = ideal_length(positions.len());
+        let temperature = *self.temperature.get_or_insert(ideal * START_TEMPERATURE);
+        let displacements = match self.options.algorithm {
+            Algorithm::FruchtermanReingold => fruchterman_reingold(positions, edges, ideal),
+            Algorithm::SpringElectrical => spring_electrical(positions, edges, ideal),
+            Algorithm::KamadaKawai => {
+                let distances = self.distances(positions.len(), edges);
+                kamada_kawai(positions, distances, ideal)
+            }
+        };
+
+        let half = BOARD_SIZE / 2.0;
+        let targets: Vec<Pos> = positions
+            .iter()
+            .zip(displacements)
+            .enumerate()
+            .map(|(index, (pos, displacement))| {
+                if pinned.get(index).copied().unwrap_or(false) {
+                    return *pos;
+                }
+                let length = displacement.x.hypot(displacement.y);
+                let mut target = *pos + displacement * (temperature / length.max(temperature));
+                if self.options.keep_inside {
+                    target = Pos::new(target.x.clamp(-half, half), target.y.clamp(-half, half));
+                }
+                target
+            })
+            .collect();
+        self.temperature = Some(temperature * COOLING);
+
+        if self.options.planar {
+            keep_crossings(positions, targets, edges)
+        } else {
+            targets
+        }
+    }
+
+    /// Steps the layout until no anchor moves more than `TOLERANCE`, or for
+    /// at most `MAX_STEPS` steps.
+    ///
+    /// # Returns
+    /// The settled positions and the number of steps taken
+    pub(crate) fn settle(&mut self, positions: &[Pos], edges: &[Edge], pinned: &[bool]) -> (Vec<Pos>, usize) {
+        self.restart();
+        let mut positions = positions.to_vec();
+        for steps in 1..=MAX_STEPS {
+            let next = self.step(&positions, edges, pinned);
+            let moved = farthest_move(&positions, &next);
+            positions = next;
+            if moved < TOLERANCE {
+                return (positions, steps);
+            }
+        }
+        (positions, MAX_STEPS)
+    }
+
+    /// Returns the hop distances between all anchors, found again only when the edges changed.
+    fn distances(&mut self, count: usize, edges: &[Edge]) -> &[f32] {
+        let ends: Vec<(usize, usize)> = edges.iter().map(|edge| (edge.from, edge.to)).collect();
+        let stale = !matches!(&self.distances, Some(known) if known.count == count && known.ends == ends);
+        if stale {
+            let hops = hop_distances(count, &ends);
+            self.distances = Some(Distances { count, ends, hops });
+        }
+        &self.distances.as_ref().unwrap().hops
+    }
+}
+
+/// Returns the farthest any anchor moved between two sets of positions.
+pub(crate) fn farthest_move(before: &[Pos], after: &[Pos]) -> f32 {
+    before.iter().zip(after).map(|(a, b)| a.distance(b)).fold(0.0, f32::max)
+}
+
+/// Returns the edge length the layout aims for: the side of the square each
+/// anchor would get if they shared the board evenly.
+fn ideal_length(count: usize) -> f32 {
+    BOARD_SIZE / (count.max(1) as f32).sqrt()
+}
+
+/// Returns the unit vector from `to` towards `from` and the distance between them.
+///
+/// Anchors on top of each other are pushed apart in a direction picked from
+/// their indices, so they do not stay stuck together.
+fn direction(from: Pos, to: Pos, salt: usize) -> (Pos, f32) {
+    let delta = from - to;
+    let distance = delta.x.hypot(delta.y);
+    if distance < MIN_DISTANCE {
+        let angle = salt as f32;
+        return (Pos::new(angle.cos(), angle.sin()), MIN_DISTANCE);
+    }
+    (delta * (1.0 / distance), distance)
+}
+
+/// Every pair repels with `ideal² / d` and every edge attracts with `d² / ideal`.
+fn fruchterman_reingold(positions: &[Pos], edges: &[Edge], ideal: f32) -> Vec<Pos> {
+    let mut displacements = vec![Pos::new(0.0, 0.0); positions.len()];
+    for a in 0..positions.len() {
+        for b in a + 1..positions.len() {
+            let (unit, distance) = direction(positions[a], positions[b], a + b);
+            let push = unit * (ideal * ideal / distance);
+            displacements[a] = displacements[a] + push;
+            displacements[b] = displacements[b] - push;
+        }
+    }
+    for edge in edges {
+        let (unit, distance) = direction(positions[edge.from], positions[edge.to], edge.from + edge.to);
+        let pull = unit * (distance * distance / ideal);
+        displacements[edge.from] = displacements[edge.from] - pull;
+        displacements[edge.to] = displacements[edge.to] + pull;
+    }
+    displacements
+}
+
+/// Edges are springs with force `ideal · ln(d / ideal)`, which is at rest at
+/// the ideal length, and unconnected pairs repel with `ideal³ / d²`.
+fn spring_electrical(positions: &[Pos], edges: &[Edge], ideal: f32) -> Vec<Pos> {
+    let connected: HashSet<(usize, usize)> =
+        edges.iter().map(|edge| (edge.from.min(edge.to), edge.from.max(edge.to))).collect();
+    let mut displacements = vec![Pos::new(0.0, 0.0); positions.len()];
+    for a in 0..positions.len() {
+        for b in a + 1..positions.len() {
+            if connected.contains(&(a, b)) {
+                continue;
+            }
+            let (unit, distance) = direction(positions[a], positions[b], a + b);
+            let push = unit * (ideal.powi(3) / (distance * distance));
+            displacements[a] = displacements[a] + push;
+            displacements[b] = displacements[b] - push;
+        }
+    }
+    for &(from, to) in &connected {
+        let (unit, distance) = direction(positions[from], positions[to], from + to);
+        let pull = unit * (ideal * (distance / ideal).ln());
+        displacements[from] = displacements[from] - pull;
+        displacements[to] = displacements[to] + pull;
+    }
+    displacements
+}
+
+/// Takes one Newton-Raphson step on every anchor towards the minimum of the
+/// Kamada-Kawai spring energy, where each pair is a spring of rest length
+/// `ideal` per hop between them and strength falling with the square of the hops.
+fn kamada_kawai(positions: &[Pos], distances: &[f32], ideal: f32) -> Vec<Pos> {
+    let count = positions.len();
+    (0..count)
+        .map(|m| {
+            let (mut ex, mut ey, mut exx, mut exy, mut eyy) = (0.0, 0.0, 0.0, 0.0, 0.0);
+            for i in (0..count).filter(|i| *i != m) {
+                let hops = distances[m * count + i];
+                let (rest, strength) = (ideal * hops, 1.0 / (hops * hops));
+                let (unit, distance) = direction(positions[m], positions[i], m + i);
+                let (dx, dy) = (unit.x * distance, unit.y * distance);
+                let cube = distance.powi(3);
+                ex += strength * (dx - rest * dx / distance);
+                ey += strength * (dy - rest * dy / distance);
+                exx += strength * (1.0 - rest * dy * dy / cube);
+                exy += strength * rest * dx * dy / cube;
+                eyy += strength * (1.0 - rest * dx * dx / cube);
+            }
+            let determinant = exx * eyy - exy * exy;
+            if determinant.abs() < f32::EPSILON {
+                return Pos::new(0.0, 0.0);
+            }
+            Pos::new((-ex * eyy + ey * exy) / determinant, (-ey * exx + ex * exy) / determinant)
+        })
+        .collect()
+}
+
+/// Returns the number of edges between every pair of anchors, row by row.
+///
+/// Anchors in different components are put one hop farther apart than the
+/// farthest connected pair, so they keep some distance.
+fn hop_distances(count: usize, ends: &[(usize, usize)]) -> Vec<f32> {
+    let mut neighbours = vec![Vec::new(); count];
+    for &(from, to) in ends {
+        neighbours[from].push(to);
+        neighbours[to].push(from);
+    }
+    let mut distances = vec![f32::INFINITY; count * count];
+    for source in 0..count {
+        let row = &mut distances[source * count..(source + 1) * count];
+        row[source] = 0.0;
+        let mut queue = VecDeque::from([source]);
+        while let Some(anchor) = queue.pop_front() {
+            for &next in &neighbours[anchor] {
+                if row[next].is_infinite() {
+                    row[next] = row[anchor] + 1.0;
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    let farthest = distances.iter().copied().filter(|hops| hops.is_finite()).fold(0.0, f32::max);
+    for hops in distances.iter_mut().filter(|hops| hops.is_infinite()) {
+        *hops = farthest + 1.0;
+    }
+    distances
+}
+
+/// Moves the anchors to their targets one at a time, halving a move that
+/// would make the anchor's edges cross more edges, and giving it up after
+/// `PLANAR_RETRIES` halvings.
+fn keep_crossings(positions: &[Pos], targets: Vec<Pos>, edges: &[Edge]) -> Vec<Pos> {
+    let mut incident = vec![Vec::new(); positions.len()];
+    for (index, edge) in edges.iter().enumerate() {
+        incident[edge.from].push(index);
+        incident[edge.to].push(index);
+    }
+    let mut placed = positions.to_vec();
+    let crossings = |placed: &[Pos], anchor: usize| -> usize {
+        let line = |edge: &Edge| LineSegment::new(placed[edge.from], placed[edge.to]);
+        incident[anchor]
+            .iter()
+            .map(|own| {
+                let own_line = line(&edges[*own]);
+                edges
+                    .iter()
+                    .enumerate()
+                    .filter(|(other, edge)| other != own && own_line.line_segments_intersect(&line(edge)))
+                    .count()
+            })
+            .sum()
+    };
+
+    for (anchor, target) in targets.into_iter().enumerate() {
+        if target == positions[anchor] || incident[anchor].is_empty() {
+            placed[anchor] = target;
+            continue;
+        }
+        let before = crossings(&placed, anchor);
+        let origin = placed[anchor];
+        let mut offset = target - origin;
+        for _ in 0..=PLANAR_RETRIES {
+            placed[anchor] = origin + offset;
+            if crossings(&placed, anchor) <= before {
+                break;
+            }
+            placed[anchor] = origin;
+            offset = offset * 0.5;
+        }
+    }
+    placed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crossings(positions: &[Pos], edges: &[Edge]) -> usize {
+        let lines: Vec<LineSegment> =
+            edges.iter().map(|edge| LineSegment::new(positions[edge.from], positions[edge.to])).collect();
+        (0..lines.len())
+            .map(|a| (a + 1..lines.len()).filter(|b| lines[a].line_segments_intersect(&lines[*b])).count())
+            .sum()
+    }
+
+    /// A cycle of `count` anchors bunched up on a short line, in scrambled order.
+    fn bunched_cycle(count: usize) -> (Vec<Pos>, Vec<Edge>) {
+        let positions = (0..count).map(|index| Pos::new((index * 7 % count) as f32 * 3.0, index as f32)).collect();
+        let edges = (0..count).map(|index| Edge::new(index, (index + 1) % count)).collect();
+        (positions, edges)
+    }
+
+    #[test]
+    fn test_every_algorithm_settles_inside_the_board() {
+        for algorithm in Algorithm::ALL {
+            let (positions, edges) = bunched_cycle(8);
+            let mut layout = Layout::new(LayoutOptions { algorithm, pin_selection: false, ..LayoutOptions::default() });
+            let (settled, steps) = layout.settle(&positions, &edges, &[]);
+            assert!(steps < MAX_STEPS, "{} did not settle", algorithm.label());
+            let half = BOARD_SIZE / 2.0;
+            assert!(settled.iter().all(|pos| pos.x.abs() <= half && pos.y.abs() <= half));
+
+            // The anchors spread out from their bunch
+            let closest = (0..8)
+                .flat_map(|a| (a + 1..8).map(move |b| (a, b)))
+                .map(|(a, b)| settled[a].distance(&settled[b]))
+                .fold(f32::INFINITY, f32::min);
+            assert!(closest > 20.0, "{} left anchors {} apart", algorithm.label(), closest);
+        }
+    }
+
+    #[test]
+    fn test_pinned_anchors_stay() {
+        let (positions, edges) = bunched_cycle(6);
+        let pinned = [true, false, false, true, false, false];
+        let mut layout = Layout::default();
+        let (settled, _) = layout.settle(&positions, &edges, &pinned);
+        assert_eq!((settled[0], settled[3]), (positions[0], positions[3]));
+        assert_ne!(settled[1], positions[1]);
+    }
+
+    #[test]
+    fn test_planar_layout_never_adds_crossings() {
+        // A wheel squashed flat with its hub off to one side, which the forces
+        // would like to pull through the rim
+        let mut positions = vec![Pos::new(-150.0, 2.0)];
+        positions.extend((0..8).map(|index| {
+            let angle = index as f32 / 8.0 * std::f32::consts::TAU;
+            Pos::new(angle.cos() * 200.0, angle.sin() * 10.0)
+        }));
+        let mut edges: Vec<Edge> = (1..=8).map(|rim| Edge::new(0, rim)).collect();
+        edges.extend((1..=8).map(|rim| Edge::new(rim, rim % 8 + 1)));
+        assert_eq!(crossings(&positions, &edges), 0);
+        // Left alone the forces do pull the hub through the rim
+        let mut free = Layout::default();
+        let mut current = positions.clone();
+        assert!((0..100).any(|_| {
+            current = free.step(&current, &edges, &[]);
+            crossings(&current, &edges) > 0
+        }));
+
+        for algorithm in Algorithm::ALL {
+            let mut layout = Layout::new(LayoutOptions { algorithm, planar: true, ..LayoutOptions::default() });
+            let mut current = positions.clone();
+            for _ in 0..100 {
+                current = layout.step(&current, &edges, &[]);
+                assert_eq!(crossings(&current, &edges), 0, "{} added a crossing", algorithm.label());
+            }
+        }
+    }
+
+    #[test]
+    fn test_hop_distances_separate_components() {
+        let distances = hop_distances(4, &[(0, 1), (1, 2)]);
+        assert_eq!(&distances[0..4], &[0.0, 1.0, 2.0, 3.0]);
+        assert_eq!(distances[3 * 4 + 3], 0.0);
+    }
+}
@@ -0,0 +1,197 @@
+use crate::board::Board;
+use crate::edge::Edge;
+use crate::puzzle::{self, Difficulty};
+use crate::{planar, Anchor, Pos};
+use nannou::rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Smallest half side of the square the solved layout is scattered over.
+const SOLVED_EXTENT: f32 = 300.0;
+/// Smallest distance between anchors of the solved layout.
+const SOLVED_SPACING: f32 = 70.0;
+/// Perturbations tried per anchor before the generator settles for fewer crossings.
+const ATTEMPTS_PER_ANCHOR: usize = 50;
+
+/// What kind of level to generate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Params {
+    pub(crate) anchors: usize,
+    /// Edges per anchor in the solution, between one and just under three;
+    /// at least two edges are kept at every anchor
+    pub(crate) density: f32,
+    /// Crossings the scrambled board should have, which sets how hard it is
+    pub(crate) crossings: usize,
+}
+
+impl From<Difficulty> for Params {
+    /// Returns the parameters matching an untangle puzzle tier.
+    fn from(difficulty: Difficulty) -> Self {
+        let anchors = difficulty.anchor_count();
+        Self { anchors, density: 2.0, crossings: anchors }
+    }
+}
+
+/// An anchor moved by the generator.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Move {
+    pub(crate) anchor: usize,
+    pub(crate) from: Pos,
+    pub(crate) to: Pos,
+}
+
+/// A generated level: a scrambled board and the moves that solve it.
+///
+/// Saved levels read as plain boards, which ignore the extra fields.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Level {
+    #[serde(flatten)]
+    pub(crate) board: Board,
+    /// The seed the level was generated from
+    pub(crate) seed: u64,
+    /// Crossings on the scrambled board
+    pub(crate) crossings: usize,
+    /// Moves putting the anchors back where they were, one per moved anchor
+    pub(crate) solution: Vec<Move>,
+}
+
+impl Level {
+    /// Serializes the level to YAML.
+    pub(crate) fn to_yaml(&self) -> Result<String, String> {
+        serde_yaml::to_string(self).map_err(|err| err.to_string())
+    }
+
+    /// Returns how many moves solving the level takes at most.
+    pub(crate) fn moves(&self) -> usize {
+        self.solution.len()
+    }
+}
+
+/// Scatters anchors and connects them without crossings.
+///
+/// The anchors are a thinned-out greedy triangulation, keeping about
+/// `density` edges per anchor and at least two at every anchor.
+pub(crate) fn solved(count: usize, density: f32, rng: &mut impl Rng) -> Board {
+    // Larger boards get more room, so the anchors still fit apart
+    let extent = SOLVED_EXTENT.max(SOLVED_SPACING * 0.75 * (count as f32).sqrt());
+    let mut anchors: Vec<Anchor> = Vec::with_capacity(count);
+    while anchors.len() < count {
+        let pos = Pos::new(rng.gen_range(-extent..extent), rng.gen_range(-extent..extent));
+        if anchors.iter().all(|anchor| anchor.pos.distance(&pos) >= SOLVED_SPACING) {
+            anchors.push(Anchor::new(pos));
+        }
+    }
+
+    // A full triangulation is hard to read, so drop edges down to the
+    // density, keeping at least two at every anchor
+    let mut pairs = planar::triangulate(&anchors, &[]);
+    pairs.shuffle(rng);
+    let mut degrees = vec![0; count];
+    for &(from, to) in &pairs {
+        degrees[from] += 1;
+        degrees[to] += 1;
+    }
+    let mut edges = Vec::new();
+    let mut surplus = pairs.len().saturating_sub((density * count as f32).round() as usize);
+    for (from, to) in pairs {
+        if surplus > 0 && degrees[from] > 2 && degrees[to] > 2 {
+            surplus -= 1;
+            degrees[from] -= 1;
+            degrees[to] -= 1;
+        } else {
+            edges.push(Edge::new(from, to));
+        }
+    }
+    Board { anchors, edges }
+}
+
+/// Generates a level with a known solution. The same parameters and seed
+/// always give the same level.
+///
+/// Starting from a solved board, anchors are moved one at a time to random
+/// spots. A move is kept if it brings the crossings closer to the number asked
+/// for and undone otherwise, until the number is hit or the attempts run out.
+/// Putting the moved anchors back solves the level.
+pub(crate) fn generate(params: Params, seed: u64) -> Level {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut board = solved(params.anchors, params.density, &mut rng);
+    let solution: Vec<Pos> = board.anchors.iter().map(|anchor| anchor.pos).collect();
+    let (min, max) = solution.iter().fold((Pos::new(0.0, 0.0), Pos::new(0.0, 0.0)), |(min, max), pos| {
+        (Pos::new(min.x.min(pos.x), min.y.min(pos.y)), Pos::new(max.x.max(pos.x), max.y.max(pos.y)))
+    });
+
+    let mut crossings = 0;
+    let mut moved = BTreeSet::new();
+    for _ in 0..params.anchors * ATTEMPTS_PER_ANCHOR {
+        if crossings == params.crossings {
+            break;
+        }
+        let anchor = rng.gen_range(0..params.anchors);
+        let pos = Pos::new(rng.gen_range(min.x..=max.x), rng.gen_range(min.y..=max.y));
+        // Anchors on top of each other would be hard to tell apart
+        let crowded = board.anchors.iter().enumerate().any(|(other, anchor_at)| {
+            other != anchor && anchor_at.pos.distance(&pos) < SOLVED_SPACING / 2.0
+        });
+        if crowded {
+            continue;
+        }
+
+        let origin = std::mem::replace(&mut board.anchors[anchor].pos, pos);
+        let now = puzzle::crossings(&board.anchors, &board.edges);
+        if now.abs_diff(params.crossings) < crossings.abs_diff(params.crossings) {
+            crossings = now;
+            moved.insert(anchor);
+        } else {
+            board.anchors[anchor].pos = origin;
+        }
+    }
+
+    let solution = moved
+        .into_iter()
+        .map(|anchor| Move { anchor, from: board.anchors[anchor].pos, to: solution[anchor] })
+        .collect();
+    Level { board, seed, crossings, solution }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solution_untangles_the_level() {
+        let params = Params { anchors: 15, density: 2.0, crossings: 10 };
+        let level = generate(params, 3);
+        assert_eq!(level.board.anchors.len(), 15);
+        assert_eq!(level.crossings, 10);
+        assert_eq!(puzzle::crossings(&level.board.anchors, &level.board.edges), level.crossings);
+        assert!(level.moves() > 0 && level.moves() <= 15);
+
+        let mut board = level.board.clone();
+        for step in &level.solution {
+            assert_eq!(board.anchors[step.anchor].pos, step.from);
+            board.anchors[step.anchor].pos = step.to;
+        }
+        assert_eq!(puzzle::crossings(&board.anchors, &board.edges), 0);
+    }
+
+    #[test]
+    fn test_levels_are_reproducible_and_load_as_boards() {
+        let params = Params::from(Difficulty::Medium);
+        assert_eq!(generate(params, 11), generate(params, 11));
+        assert_ne!(generate(params, 11).board, generate(params, 12).board);
+
+        let level = generate(params, 11);
+        let board = Board::from_yaml(&level.to_yaml().unwrap()).unwrap();
+        assert_eq!(board, level.board);
+    }
+
+    #[test]
+    fn test_density_sets_edge_count() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let sparse = solved(30, 1.0, &mut rng);
+        let dense = solved(30, 2.5, &mut rng);
+        assert!(sparse.edges.len() < dense.edges.len());
+        assert!(dense.edges.len() <= 75);
+        assert_eq!(puzzle::crossings(&dense.anchors, &dense.edges), 0);
+    }
+}
@@ -3,6 +3,7 @@
 use nannou::prelude::*;
 use nannou::{
     app::App,
+    rand::{self, seq::SliceRandom},
     wgpu::{DeviceDescriptor, Limits},
 };
 #[cfg(target_family = "wasm")]
@@ -12,6 +13,25 @@ use nannou::{
 };
 use nannou_egui::{self, egui, Egui};
 
+use board::{Board, BOARD_FILE, BOARD_SIZE};
+use analysis::{Graph, Metric};
+use anchor::{AnchorKind, AnchorProps};
+use camera::Camera;
+use crossings::Crossings;
+use edge::{Edge, EdgeKind, EdgeProps};
+use exchange::Format;
+use game::{Game, Goal, Rules};
+use history::{Edit, History};
+use input::{Command, Device, Input, Intent};
+use keymap::{Action, Keymap, KEYMAP_FILE};
+use layout::{Algorithm, Layout};
+use puzzle::{Difficulty, Puzzle};
+use serde::{Deserialize, Serialize};
+use selection::{Clipboard, Selection, Shape};
+use snap::{Grid, Guide, Snap};
+use solver::{Hint, Objective, Solver};
+use spanning::{Challenge, Terminals};
+
 #[cfg(target_family = "wasm")]
 use std::sync::RwLock;
 #[cfg(target_family = "wasm")]
@@ -19,15 +39,45 @@ use wasm_bindgen::prelude::*;
 
 use std::{
     borrow::BorrowMut,
-    sync::{Arc, Mutex, atomic::{AtomicU32, Ordering}},
-    ops::{Mul, Sub},
+    cell::{Ref, RefCell},
+    sync::{Arc, Mutex, atomic::{AtomicBool, AtomicU32, Ordering}},
+    ops::{Add, Mul, Sub},
 };
 
 // Global volume control
 static VOLUME: AtomicU32 = AtomicU32::new(0x3F400000); // 0.75 in f32 bits
 
+// How strongly the drag line and the anchor glow follow the output amplitude
+const JITTER_GAIN: f32 = 60.0;
+const GLOW_GAIN: f32 = 200.0;
+
+pub mod anchor;
+pub mod analysis;
 pub mod audio;
+pub mod board;
+pub mod camera;
+#[cfg(not(target_family = "wasm"))]
+pub mod cli;
 pub mod console;
+pub mod crossings;
+pub mod edge;
+pub mod exchange;
+pub mod files;
+pub mod game;
+pub mod history;
+pub mod input;
+pub mod keymap;
+pub mod layout;
+pub mod levels;
+pub mod music;
+pub mod planar;
+pub mod puzzle;
+pub mod recording;
+pub mod selection;
+pub mod snap;
+pub mod solver;
+pub mod spanning;
+pub mod spectrum;
 pub mod task;
 
 #[cfg(target_family = "wasm")]
@@ -105,44 +155,319 @@ async fn create_window(app: &App) {
     };
 }
 
-fn key_pressed(_app: &App, _m: &mut Model, key: Key) {
-    match key {
-        Key::Space => {}
-        // Raise the frequency when the up key is pressed.
-        Key::Up => {}
-        // Lower the frequency when the down key is pressed.
-        Key::Down => {}
-        _ => (),
+fn key_pressed(app: &App, m: &mut Model, key: Key) {
+    // The bindings panel is waiting for the key to bind an action to
+    if let Some(action) = m.rebinding.take() {
+        m.keymap.rebind(keymap::Binding::with_mods(action, key, &app.keys.mods));
+        return;
+    }
+    // Keys typed into a text field are not bindings
+    if m.egui.as_ref().is_some_and(|egui| egui.ctx().wants_keyboard_input()) {
+        return;
+    }
+
+    if let Some(action) = m.keymap.action(key, &app.keys.mods) {
+        perform(m, action);
+    }
+}
+
+/// Carries out an action triggered from the keyboard.
+fn perform(m: &mut Model, action: Action) {
+    let busy = m.interaction.dragged_anchor.is_some() || m.interaction.moving_anchor.is_some();
+    let edits_graph = matches!(
+        action,
+        Action::Delete
+            | Action::Cut
+            | Action::Paste
+            | Action::Randomize
+            | Action::RandomizePlanar
+            | Action::Triangulate
+            | Action::Clear
+            | Action::NewBoard
+            | Action::Load
+    );
+    let allowed = match action {
+        _ if edits_graph => m.may_edit(),
+        Action::Undo | Action::Redo => m.may_move(),
+        Action::Confirm => m.may_draw(),
+        _ => true,
+    };
+    if !allowed {
+        return;
+    }
+    match action {
+        Action::PlaceTool => m.tool = Tool::Place,
+        Action::ConnectTool => m.tool = Tool::Connect,
+        Action::RectSelectTool => m.tool = Tool::RectSelect,
+        Action::LassoTool => m.tool = Tool::Lasso,
+        Action::Undo => {
+            m.interaction.undo();
+        }
+        Action::Redo => {
+            m.interaction.redo();
+        }
+        Action::Delete if !busy => {
+            if !m.interaction.selection.is_empty() {
+                m.interaction.delete_selection();
+            } else if m.interaction.remove_at(m.pointer()).is_none() {
+                if let Some(focused) = m.interaction.focused {
+                    m.interaction.remove_anchor(focused);
+                }
+            }
+        }
+        Action::Delete => {}
+        Action::SelectAll => m.interaction.select_all(),
+        Action::Copy => {
+            m.interaction.copy_selection();
+        }
+        Action::Cut => {
+            m.interaction.copy_selection();
+            m.interaction.delete_selection();
+        }
+        // Paste the copied anchors centred on the mouse
+        Action::Paste => {
+            let offset = m.pointer() - m.interaction.clipboard.centroid();
+            m.interaction.paste(offset);
+        }
+        Action::Cancel => m.interaction.cancel(),
+        Action::Randomize => m.interaction.randomize_edges(),
+        Action::RandomizePlanar => m.interaction.randomize_planar_edges(),
+        Action::Triangulate => {
+            let template = m.new_edge_props();
+            m.status = Some(format!("Added {} edges", m.interaction.triangulate(&template)));
+        }
+        Action::Clear => m.interaction.clear_edges(),
+        Action::ToggleWiggle => m.wiggle_anchors = !m.wiggle_anchors,
+        Action::ZoomIn => m.camera.zoom_at(Pos::new(0.0, 0.0), camera::ZOOM_STEP * camera::ZOOM_STEP),
+        Action::ZoomOut => m.camera.zoom_at(Pos::new(0.0, 0.0), 1.0 / (camera::ZOOM_STEP * camera::ZOOM_STEP)),
+        Action::ResetView => m.camera = Camera::default(),
+        Action::NewBoard => {
+            m.interaction.reset(random_anchors());
+            m.puzzle = None;
+        }
+        Action::Save => m.status = Some(save_board(&m.interaction)),
+        Action::Load => m.status = Some(load_board(&mut m.interaction, &mut m.puzzle)),
+        Action::FocusLeft => m.interaction.move_focus(Pos::new(-1.0, 0.0), m.camera.center),
+        Action::FocusRight => m.interaction.move_focus(Pos::new(1.0, 0.0), m.camera.center),
+        Action::FocusUp => m.interaction.move_focus(Pos::new(0.0, 1.0), m.camera.center),
+        Action::FocusDown => m.interaction.move_focus(Pos::new(0.0, -1.0), m.camera.center),
+        Action::Confirm => {
+            let template = m.new_edge_props();
+            if m.interaction.confirm_focus(&template).is_some() {
+                edge_drawn(m);
+            }
+        }
+    }
+}
+
+/// Replaces the board with a newly generated untangle puzzle.
+fn start_puzzle(interaction: &mut InteractionState, puzzle: &mut Option<Puzzle>, difficulty: Difficulty) {
+    interaction.load_board(puzzle::generate(difficulty, &mut rand::thread_rng()));
+    interaction.free_moves = true;
+    *puzzle = Some(Puzzle::new(difficulty, interaction.moves));
+}
+
+/// Clears the edges and challenges the player to connect the terminals with
+/// the shortest total length.
+///
+/// # Returns
+/// The challenge, or `None` if there are fewer than two terminals to connect
+fn start_challenge(interaction: &mut InteractionState, terminals: Terminals) -> Option<Challenge> {
+    let challenge = Challenge::new(&interaction.anchors, terminals)?;
+    interaction.load_board(Board { anchors: interaction.anchors.clone(), edges: Vec::new() });
+    Some(challenge)
+}
+
+/// Stops the animated layout, if it runs, recording its moves as one edit.
+fn stop_layout(interaction: &mut InteractionState, origins: &mut Option<Vec<Pos>>) {
+    if let Some(origins) = origins.take() {
+        interaction.record_layout(&origins);
+    }
+}
+
+/// Saves the board, returning a message for the UI.
+fn save_board(interaction: &InteractionState) -> String {
+    match interaction.to_board().to_yaml().and_then(|yaml| files::store(BOARD_FILE, &yaml)) {
+        Ok(location) => format!("Saved board to {}", location),
+        Err(err) => format!("Saving the board failed: {}", err),
+    }
+}
+
+/// Loads the saved board, ending the puzzle it replaces, and returns a
+/// message for the UI.
+fn load_board(interaction: &mut InteractionState, puzzle: &mut Option<Puzzle>) -> String {
+    match files::load(BOARD_FILE).and_then(|yaml| Board::from_yaml(&yaml)) {
+        Ok(board) => {
+            interaction.load_board(board);
+            *puzzle = None;
+            "Loaded board".to_string()
+        }
+        Err(err) => format!("Loading the board failed: {}", err),
+    }
+}
+
+/// Writes the board to a file in another tool's format, returning a message for the UI.
+fn export_board(interaction: &InteractionState, name: &str, format: Format) -> String {
+    let text = exchange::export(&interaction.to_board(), format);
+    match text.and_then(|text| files::save(name, text.as_bytes(), format.mime())) {
+        Ok(location) => format!("Exported board to {}", location),
+        Err(err) => format!("Exporting the board failed: {}", err),
+    }
+}
+
+/// Reads the board from a picked file in another tool's format, ending the
+/// puzzle it replaces, and returns a message for the UI with what was wrong
+/// with it. The format follows the file's extension if it has a known one.
+fn import_board(
+    interaction: &mut InteractionState,
+    puzzle: &mut Option<Puzzle>,
+    picked: Result<(String, String), String>,
+    format: Format,
+) -> String {
+    let board = picked.and_then(|(name, text)| exchange::import(&text, Format::of_file(&name).unwrap_or(format)));
+    match board {
+        Ok((board, report)) => {
+            interaction.load_board(board);
+            *puzzle = None;
+            format!("Imported {}", report)
+        }
+        Err(err) => format!("Importing the board failed: {}", err),
     }
 }
 
 fn event(app: &App, m: &mut Model, event: WindowEvent) {
-    match event {
-        WindowEvent::MousePressed(MouseButton::Left) => {
-            let mouse_pos = Pos::new(app.mouse.x, app.mouse.y);
-            let drag_result = m.interaction.try_start_drag(mouse_pos);
-            
-            if drag_result.is_some() {
+    let window = app.window_rect();
+    let commands = match event {
+        WindowEvent::MouseMoved(position) => m.input.mouse_moved(Pos::new(position.x, position.y)),
+        // Presses, touches and the wheel on a window are for egui. Releases are
+        // dropped as well, as `Input` only reports those of presses it saw
+        WindowEvent::MousePressed(_) | WindowEvent::MouseWheel(..) if m.ui_at(window, m.input.pointer) => Vec::new(),
+        WindowEvent::Touch(touch)
+            if touch.phase == TouchPhase::Started && m.ui_at(window, Pos::new(touch.position.x, touch.position.y)) =>
+        {
+            Vec::new()
+        }
+        WindowEvent::MousePressed(button) => m.input.mouse_pressed(button, app.keys.mods.shift()),
+        WindowEvent::MouseReleased(button) => m.input.mouse_released(button),
+        WindowEvent::MouseWheel(delta, _) => m.input.wheel(match delta {
+            MouseScrollDelta::LineDelta(_, y) => y,
+            MouseScrollDelta::PixelDelta(position) => position.y as f32 / 50.0,
+        }),
+        WindowEvent::Touch(touch) => m.input.touch(touch.id, touch.phase, Pos::new(touch.position.x, touch.position.y)),
+        _ => Vec::new(),
+    };
+    for command in commands {
+        execute(app, m, command);
+    }
+}
+
+/// Carries out a command from the mouse or a touch screen.
+fn execute(app: &App, m: &mut Model, command: Command) {
+    // Browsers only allow audio after a user gesture, so the stream is opened
+    // on the first press. It keeps running silently when no output is available.
+    if let Command::Press { .. } = command {
+        if m.audio.is_none() && m.output.status == audio::Status::Idle {
+            m.audio = m.output.open(&m.controls);
+        }
+    }
+
+    match command {
+        Command::Zoom { at, factor } => m.camera.zoom_at(at, factor),
+        Command::Pan { by } => m.camera.pan_by(by),
+        Command::Abort => {
+            m.interaction.abort_gesture();
+            silence_drag(m);
+        }
+        // Puzzles are solved by moving anchors only, with any button or finger
+        Command::Context { .. } if !m.may_edit() => {}
+        Command::Press { at, .. } if !m.may_draw() && m.tool.shape().is_none() => {
+            m.interaction.try_start_move(m.camera.to_world(at));
+        }
+        // Games are played by drawing edges only
+        Command::Press { intent: Intent::Move, .. } if !m.may_move() => {}
+        Command::Press { intent: Intent::Primary { shift: true }, .. } if !m.may_move() && m.tool.shape().is_none() => {}
+        // Right click or long press deletes the anchor or edge under the pointer
+        Command::Context { at } => {
+            m.interaction.cancel_move();
+            m.interaction.remove_at(m.camera.to_world(at));
+        }
+        // Shift-drag or right-drag moves an anchor instead of drawing an edge
+        Command::Press { at, intent: Intent::Move, .. } => {
+            m.interaction.try_start_move(m.camera.to_world(at));
+        }
+        Command::Press { at, intent: Intent::Primary { shift: true }, .. } if m.tool.shape().is_none() => {
+            m.interaction.try_start_move(m.camera.to_world(at));
+        }
+        // Selection tools move the selection when dragging one of its anchors
+        // and draw a rectangle or lasso otherwise
+        Command::Press { at, .. } if m.tool.shape().is_some() => {
+            let pos = m.camera.to_world(at);
+            if !(m.may_move() && m.interaction.try_start_selection_move(pos)) {
+                m.interaction.start_selection(pos);
+            }
+        }
+        // The place tool places on release, so a pinch can still begin with a touch
+        Command::Press { .. } if m.tool == Tool::Place => {}
+        Command::Press { at, .. } => {
+            if m.interaction.try_start_drag(m.camera.to_world(at)).is_some() {
                 let current_vol = f32::from_bits(VOLUME.load(Ordering::Relaxed));
-                m.freq = Arc::new(Mutex::new(FreqWrapper { value: 100.0, volume: current_vol }));
-                m.audio = Some(audio::beep(m.freq.clone()));
+                let window = app.window_rect();
+                let (pan, gain) = audio::placement(at.x, at.y, window.w() / 2.0, window.h() / 2.0);
+                if let Ok(mut voice) = m.controls.freq.lock() {
+                    *voice = FreqWrapper { value: 100.0, volume: current_vol, pan, gain };
+                }
                 m.last_drag_length = Some(100.0);
             }
         }
-        WindowEvent::MouseReleased(MouseButton::Left) => {
-            let mouse_pos = Pos::new(app.mouse.x, app.mouse.y);
-            m.interaction.try_end_drag(mouse_pos);
-            // Signal audio to fade out by setting frequency to 0
-            if let Ok(mut freq) = m.freq.lock() {
-                freq.value = 0.0;
+        Command::Release { at, .. } if m.interaction.moving_anchor.is_some() => {
+            m.interaction.try_end_move(m.camera.to_world(at));
+        }
+        Command::Release { at, .. } if m.interaction.moving_selection.is_some() => {
+            m.interaction.try_end_selection_move(m.camera.to_world(at));
+        }
+        Command::Release { at, .. } if m.interaction.selecting.is_some() => {
+            if let Some(shape) = m.tool.shape() {
+                m.interaction.extend_selection_path(m.camera.to_world(at));
+                m.interaction.end_selection(shape, app.keys.mods.shift());
+            }
+        }
+        Command::Release { at, tap } if m.tool == Tool::Place => {
+            if tap && m.may_edit() {
+                m.interaction.try_place_anchor(m.camera.to_world(at));
             }
-            
-            // Clean up audio immediately - the fade-out will happen in the audio system
-            m.audio = None;
-            m.last_drag_length = None;
         }
-        _ => (),
+        // Clicking an anchor or edge without drawing an edge pins it in the
+        // inspector, clicking empty space unpins it
+        Command::Release { at, tap } => {
+            let pos = m.camera.to_world(at);
+            let template = m.new_edge_props();
+            if m.interaction.try_end_drag(pos, &template).is_some() {
+                edge_drawn(m);
+            } else if tap {
+                m.inspected = m.interaction.hit_test(pos);
+            }
+            silence_drag(m);
+        }
+    }
+}
+
+/// Lets a running game check the edge just drawn for a win.
+fn edge_drawn(m: &mut Model) {
+    if let Some(game) = m.game.as_mut() {
+        game.edge_drawn(&m.interaction.anchors, &m.interaction.edges);
+        if let Some(win) = &game.winner {
+            m.status = Some(format!("Player {} wins!", win.player + 1));
+        }
+    }
+}
+
+/// Fades out the voice of the edge being drawn.
+fn silence_drag(m: &mut Model) {
+    // Signal audio to fade out by setting frequency to 0
+    if let Ok(mut freq) = m.controls.freq.lock() {
+        freq.value = 0.0;
     }
+    m.last_drag_length = None;
 }
 
 fn raw_window_event(_app: &App, model: &mut Model, event: &nannou::winit::event::WindowEvent) {
@@ -157,7 +482,7 @@ fn raw_window_event(_app: &App, model: &mut Model, event: &nannou::winit::event:
 /// This struct provides basic geometric operations like distance calculation,
 /// vector arithmetic (subtraction and scalar multiplication), and conversion
 /// to the graphics system's vector type.
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, Copy, PartialEq, Serialize, Deserialize)]
 struct Pos {
     /// X coordinate in the 2D space
     x: f32,
@@ -194,6 +519,21 @@ impl Into<Vec2> for Pos {
     }
 }
 
+impl Add for Pos {
+    type Output = Pos;
+
+    /// Implements vector addition for positions.
+    ///
+    /// # Arguments
+    /// * `other` - The position to add to this one
+    ///
+    /// # Returns
+    /// A new position with the coordinates of both positions summed
+    fn add(self, other: Self) -> Self::Output {
+        Pos::new(self.x + other.x, self.y + other.y)
+    }
+}
+
 impl Sub for Pos {
     type Output = Pos;
 
@@ -306,7 +646,7 @@ mod tests {
     #[test]
     fn test_anchor_creation() {
         let pos = Pos::new(1.0, 2.0);
-        let anchor = Anchor { pos };
+        let anchor = Anchor::new(pos);
         assert_eq!(anchor.pos.x, 1.0);
         assert_eq!(anchor.pos.y, 2.0);
     }
@@ -316,7 +656,7 @@ mod tests {
 /// 
 /// This struct provides functionality for line manipulation and intersection testing,
 /// as well as visual rendering with effects.
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, Copy, PartialEq)]
 struct LineSegment {
     /// Starting point of the line segment
     start: Pos,
@@ -383,6 +723,15 @@ impl LineSegment {
     /// # Returns
     /// `true` if the lines intersect, `false` otherwise
     fn line_segments_intersect(&self, other: &LineSegment) -> bool {
+        self.intersection(other).is_some()
+    }
+
+    /// Finds where this line segment crosses another, in the same way
+    /// `line_segments_intersect` tests whether they do.
+    ///
+    /// # Returns
+    /// The crossing point, or `None` if the segments do not cross
+    fn intersection(&self, other: &LineSegment) -> Option<Pos> {
         let LineSegment { start: a, end: b } = self.shorten_by_fixed_amount(2.0);
         let LineSegment { start: c, end: d } = other.shorten_by_fixed_amount(2.0);
 
@@ -397,7 +746,7 @@ impl LineSegment {
         let determinant = a1 * b2 - a2 * b1;
 
         if determinant == 0.0 {
-            return false;
+            return None;
         }
 
         let x = (b2 * c1 - b1 * c2) / determinant;
@@ -407,10 +756,27 @@ impl LineSegment {
             c.x <= a.x.max(b.x) && c.x >= a.x.min(b.x) && c.y <= a.y.max(b.y) && c.y >= a.y.min(b.y)
         };
 
-        let is_intersecting =
-            is_on_segment(&a, &b, Pos::new(x, y)) && is_on_segment(&c, &d, Pos::new(x, y));
+        let point = Pos::new(x, y);
+        (is_on_segment(&a, &b, point) && is_on_segment(&c, &d, point)).then_some(point)
+    }
+
+    /// Calculates the shortest distance between a point and this line segment.
+    ///
+    /// # Arguments
+    /// * `point` - The point to measure from
+    ///
+    /// # Returns
+    /// The distance to the closest point of the segment, including its ends
+    fn distance_to(&self, point: &Pos) -> f32 {
+        let direction = self.end - self.start;
+        let length_squared = direction.x * direction.x + direction.y * direction.y;
+        if length_squared == 0.0 {
+            return self.start.distance(point);
+        }
 
-        is_intersecting
+        let offset = *point - self.start;
+        let t = ((offset.x * direction.x + offset.y * direction.y) / length_squared).clamp(0.0, 1.0);
+        (self.start + direction * t).distance(point)
     }
 
     /// Draws the line segment with an outline effect and animated distortion.
@@ -425,15 +791,27 @@ impl LineSegment {
     /// * `color` - The color of the main line
     /// * `outline` - The color of the outline
     fn draw_with_outline(&self, draw: &nannou::draw::Draw, color: Rgb8, outline: Rgb8) {
+        self.draw_with_jitter(draw, color, outline, 2.5);
+    }
+
+    /// Draws the line segment like `draw_with_outline`, with a custom amount of distortion.
+    ///
+    /// # Arguments
+    /// * `draw` - The drawing context
+    /// * `color` - The color of the main line
+    /// * `outline` - The color of the outline
+    /// * `jitter` - The maximum random displacement of the intermediate points
+    fn draw_with_jitter(&self, draw: &nannou::draw::Draw, color: Rgb8, outline: Rgb8, jitter: f32) {
         let mut points: Vec<Point2> = vec![self.start.into()];
 
         // Add random points to make the line look like its moving
         let delta_vector = self.end - self.start;
+        let offset = || if jitter > 0.0 { random_range(-jitter, jitter) } else { 0.0 };
         for i in 1..10 {
             let pos_along_vector = delta_vector * (i as f32 / 10.0);
             points.push(Point2::new(
-                self.start.x + pos_along_vector.x + random_range(-2.5, 2.5),
-                self.start.y + pos_along_vector.y + random_range(-2.5, 2.5),
+                self.start.x + pos_along_vector.x + offset(),
+                self.start.y + pos_along_vector.y + offset(),
             ));
         }
 
@@ -445,6 +823,40 @@ impl LineSegment {
             .color(outline);
         draw.polyline().weight(2.0).points(points).color(color);
     }
+
+    /// Draws the line segment as dashes with an outline, without distortion.
+    ///
+    /// # Arguments
+    /// * `draw` - The drawing context
+    /// * `color` - The color of the dashes
+    /// * `outline` - The color of the outline
+    /// * `dash` - The length of each dash and of each gap between them
+    fn draw_dashed(&self, draw: &nannou::draw::Draw, color: Rgb8, outline: Rgb8, dash: f32) {
+        let length = self.start.distance(&self.end);
+        let direction = (self.end - self.start) * (1.0 / length.max(f32::EPSILON));
+        let mut along = 0.0;
+        while along < length {
+            let start = self.start + direction * along;
+            let end = self.start + direction * (along + dash).min(length);
+            draw.line().start(start.into()).end(end.into()).weight(4.0).color(outline);
+            draw.line().start(start.into()).end(end.into()).weight(2.0).color(color);
+            along += 2.0 * dash;
+        }
+    }
+}
+
+/// Colors of the players, used for what they own.
+const PLAYER_COLORS: [Rgb8; 4] = [DODGERBLUE, CORAL, LIMEGREEN, ORCHID];
+
+/// Returns the color of a player.
+fn player_color(player: u8) -> Rgb8 {
+    PLAYER_COLORS[player as usize % PLAYER_COLORS.len()]
+}
+
+/// Returns the color of a player for the UI.
+fn player_color32(player: u8) -> egui::Color32 {
+    let color = player_color(player);
+    egui::Color32::from_rgb(color.red, color.green, color.blue)
 }
 
 /// Represents a node in the graph that can be connected to other nodes via edges.
@@ -454,10 +866,24 @@ impl LineSegment {
 /// - Dragged to create connections
 /// - Connected to other anchors via edges
 /// - Removed along with their connected edges
-#[derive(Clone, Debug)]
+///
+/// Saved boards store the position inline, next to the properties, so boards
+/// saved before anchors had properties still load.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 struct Anchor {
     /// The position of this anchor in 2D space
+    #[serde(flatten)]
     pos: Pos,
+    /// The game data of the anchor
+    #[serde(default)]
+    props: AnchorProps,
+}
+
+impl Anchor {
+    /// Creates a neutral anchor at the given position.
+    fn new(pos: Pos) -> Self {
+        Self { pos, props: AnchorProps::default() }
+    }
 }
 
 /// Wraps a frequency value for audio feedback during interactions.
@@ -470,6 +896,46 @@ pub struct FreqWrapper {
     value: f32,
     /// The current volume level (0.0 to 1.0)
     volume: f32,
+    /// Stereo position of the voice (-1.0 left to 1.0 right)
+    pan: f32,
+    /// Distance attenuation of the voice (0.0 to 1.0)
+    gain: f32,
+}
+
+/// Screen distance within which the cursor counts as being on an anchor.
+const ANCHOR_HIT_TOLERANCE: f32 = 10.0;
+/// Screen distance within which the cursor counts as being on an edge.
+const EDGE_HIT_TOLERANCE: f32 = 6.0;
+/// Default minimum distance between a placed anchor and all others.
+const DEFAULT_MIN_SPACING: f32 = 50.0;
+
+/// An element of the graph under the cursor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Hit {
+    /// The anchor with the given index
+    Anchor(usize),
+    /// The edge with the given index
+    Edge(usize),
+}
+
+/// The selected anchors being moved together, and where they came from.
+#[derive(Clone, Debug)]
+struct SelectionMove {
+    /// Where the move started
+    start: Pos,
+    /// The anchor the selection was grabbed by, which is the one snapped
+    grabbed: usize,
+    /// Index and original position of each selected anchor
+    origins: Vec<(usize, Pos)>,
+}
+
+/// An anchor being moved to a new position, and where it came from.
+#[derive(Clone, Copy, Debug)]
+struct AnchorMove {
+    /// Index of the moved anchor
+    index: usize,
+    /// Position of the anchor before the move started
+    origin: Pos,
 }
 
 /// Manages the interactive state of the graph, including anchors (nodes) and edges,
@@ -480,8 +946,40 @@ struct InteractionState {
     anchors: Vec<Anchor>,
     /// Index of the currently dragged anchor, if any
     dragged_anchor: Option<usize>,
-    /// List of edges, each represented as a pair of anchor indices (from, to)
-    edges: Vec<(usize, usize)>,
+    /// List of edges between pairs of anchor indices, with their properties
+    edges: Vec<Edge>,
+    /// Number of edges that were rejected because they would cross another edge
+    blocked_attempts: usize,
+    /// The anchor currently being moved, if any
+    moving_anchor: Option<AnchorMove>,
+    /// Recorded edits that can be undone and redone
+    history: History,
+    /// The selected anchors, and with them the edges between them
+    selection: Selection,
+    /// The anchors and edges copied last
+    clipboard: Clipboard,
+    /// Path of the rectangle or lasso being drawn, if any
+    selecting: Option<Vec<Pos>>,
+    /// The selection currently being moved, if any
+    moving_selection: Option<SelectionMove>,
+    /// Snapping applied to placed, moved and pasted anchors
+    snap: Snap,
+    /// Minimum distance between a placed anchor and all others
+    min_spacing: f32,
+    /// Zoom of the camera, which scales the hover tolerances
+    zoom: f32,
+    /// How much larger the hover tolerances are for the pointing device in use
+    pointer_scale: f32,
+    /// The anchor focused for keyboard play, if any
+    focused: Option<usize>,
+    /// Whether the edge being drawn was started from the keyboard
+    keyboard_link: bool,
+    /// Whether moves may add crossings, as untangling needs
+    free_moves: bool,
+    /// The pairs of crossing edges, brought up to date when queried
+    crossings: RefCell<Crossings>,
+    /// Number of finished moves that changed where anchors are
+    moves: usize,
 }
 
 impl InteractionState {
@@ -491,6 +989,22 @@ impl InteractionState {
             anchors: Vec::new(),
             dragged_anchor: None,
             edges: Vec::new(),
+            blocked_attempts: 0,
+            moving_anchor: None,
+            history: History::default(),
+            selection: Selection::default(),
+            clipboard: Clipboard::default(),
+            selecting: None,
+            moving_selection: None,
+            snap: Snap::default(),
+            min_spacing: DEFAULT_MIN_SPACING,
+            zoom: 1.0,
+            pointer_scale: 1.0,
+            focused: None,
+            keyboard_link: false,
+            free_moves: false,
+            moves: 0,
+            crossings: RefCell::default(),
         }
     }
 
@@ -503,62 +1017,108 @@ impl InteractionState {
             anchors,
             dragged_anchor: None,
             edges: Vec::new(),
+            blocked_attempts: 0,
+            moving_anchor: None,
+            history: History::default(),
+            selection: Selection::default(),
+            clipboard: Clipboard::default(),
+            selecting: None,
+            moving_selection: None,
+            snap: Snap::default(),
+            min_spacing: DEFAULT_MIN_SPACING,
+            zoom: 1.0,
+            pointer_scale: 1.0,
+            focused: None,
+            keyboard_link: false,
+            free_moves: false,
+            moves: 0,
+            crossings: RefCell::default(),
         }
     }
 
-    /// Attempts to start dragging at the given position.
-    /// If no anchor exists at the position, creates a new one.
+    /// Attempts to start dragging an edge from the anchor at the given position.
     ///
     /// # Arguments
     /// * `pos` - The position where the drag operation starts
     ///
     /// # Returns
     /// * `Some(index)` if an existing anchor was selected for dragging
-    /// * `None` if a new anchor was created
+    /// * `None` if there is no anchor at the position
     fn try_start_drag(&mut self, pos: Pos) -> Option<usize> {
-        let drag_idx = self.anchors.iter()
-            .enumerate()
-            .find(|(_idx, anchor)| anchor.pos.distance(&pos) < 10.0)
-            .map(|(idx, _)| idx);
-
-        if drag_idx.is_none() {
-            self.anchors.push(Anchor { pos });
-        }
-        
+        let drag_idx = self.anchor_at(pos);
         self.dragged_anchor = drag_idx;
+        self.keyboard_link = false;
         drag_idx
     }
 
+    /// Attempts to place a new anchor at the given position, after snapping.
+    ///
+    /// # Arguments
+    /// * `pos` - The position of the new anchor
+    ///
+    /// # Returns
+    /// * `Some(index)` of the new anchor
+    /// * `None` if the anchor would be closer than `min_spacing` to another one
+    fn try_place_anchor(&mut self, pos: Pos) -> Option<usize> {
+        let (pos, _) = self.snap_position(pos, None);
+        if !self.can_place(pos) {
+            return None;
+        }
+
+        let anchor = Anchor::new(pos);
+        self.anchors.push(anchor.clone());
+        self.history.record(Edit::Add { anchors: vec![anchor], edges: Vec::new() });
+        Some(self.anchors.len() - 1)
+    }
+
+    /// Returns `true` if no anchor is closer than `min_spacing` to the position.
+    fn can_place(&self, pos: Pos) -> bool {
+        self.anchors.iter().all(|anchor| anchor.pos.distance(&pos) >= self.min_spacing)
+    }
+
+    /// Returns the distance within which the cursor is on an anchor.
+    /// Zooming out makes anchors smaller on screen, so the distance grows,
+    /// and fingers are less precise than a mouse, so it grows for them too.
+    fn hover_tolerance(&self) -> f32 {
+        ANCHOR_HIT_TOLERANCE * self.pointer_scale / self.zoom
+    }
+
     /// Attempts to end a drag operation at the given position, potentially creating a new edge.
     ///
     /// # Arguments
     /// * `pos` - The position where the drag operation ends
+    /// * `template` - The properties of the new edge, set by the current tool
     ///
     /// # Returns
     /// * `Some((from, to))` if a valid edge was created
-    /// * `None` if no edge was created (invalid connection or intersecting with existing edges)
-    fn try_end_drag(&mut self, pos: Pos) -> Option<(usize, usize)> {
-        let dragged_on_anchor_idx = self.anchors.iter()
-            .enumerate()
-            .find(|(_, anchor)| anchor.pos.distance(&pos) < 10.0)
-            .map(|(idx, _)| idx);
+    /// * `None` if no edge was created (invalid connection, an anchor at
+    ///   capacity, or intersecting with existing edges)
+    fn try_end_drag(&mut self, pos: Pos, template: &EdgeProps) -> Option<(usize, usize)> {
+        self.finish_drag(self.anchor_at(pos), template)
+    }
 
+    /// Ends the drag on the given anchor, creating the edge if it is valid.
+    fn finish_drag(&mut self, dragged_on_anchor_idx: Option<usize>, template: &EdgeProps) -> Option<(usize, usize)> {
         let new_edge = if let (Some(from), Some(to)) = (self.dragged_anchor, dragged_on_anchor_idx) {
-            if from != to && !self.edges.contains(&(from, to)) {
+            let valid = from != to
+                && !self.edges.iter().any(|edge| edge.connects(from, to))
+                && self.accepts_edge(from)
+                && self.accepts_edge(to);
+            if valid {
                 let new_line = LineSegment::new(
                     self.anchors[from].pos,
                     self.anchors[to].pos,
                 );
 
-                let intersecting = self.edges.iter().any(|(a, b)| {
-                    let line = LineSegment::new(self.anchors[*a].pos, self.anchors[*b].pos);
-                    line.line_segments_intersect(&new_line)
-                });
+                let intersecting = (0..self.edges.len()).any(|edge| self.edge_line(edge).line_segments_intersect(&new_line));
 
                 if !intersecting {
-                    self.edges.push((from, to));
+                    let edge = Edge::with_props(from, to, template.clone());
+                    self.edges.push(edge.clone());
+                    self.history.record(Edit::Add { anchors: Vec::new(), edges: vec![edge] });
                     Some((from, to))
                 } else {
+                    self.blocked_attempts += 1;
                     None
                 }
             } else {
@@ -569,9 +1129,103 @@ impl InteractionState {
         };
 
         self.dragged_anchor = None;
+        self.keyboard_link = false;
         new_edge
     }
 
+    /// Moves the keyboard focus to the closest anchor in the given direction.
+    ///
+    /// Anchors within 45 degrees of the direction are considered. Without a
+    /// focus yet, the anchor closest to `center` is focused instead.
+    ///
+    /// # Arguments
+    /// * `direction` - The direction to move the focus in
+    /// * `center` - Where to start when nothing is focused, usually the view centre
+    fn move_focus(&mut self, direction: Pos, center: Pos) {
+        let closest_to = |state: &Self, origin: Pos, filter: &dyn Fn(Pos) -> bool| {
+            (0..state.anchors.len())
+                .filter(|index| filter(state.anchors[*index].pos - origin))
+                .min_by(|a, b| {
+                    let distance = |index: &usize| state.anchors[*index].pos.distance(&origin);
+                    distance(a).total_cmp(&distance(b))
+                })
+        };
+
+        let focused = match self.focused {
+            Some(focused) => {
+                let origin = self.anchors[focused].pos;
+                closest_to(self, origin, &|offset| {
+                    let along = offset.x * direction.x + offset.y * direction.y;
+                    let across = offset.x * direction.y - offset.y * direction.x;
+                    along > 0.0 && across.abs() <= along
+                })
+                .or(Some(focused))
+            }
+            None => closest_to(self, center, &|_| true),
+        };
+        self.focused = focused;
+    }
+
+    /// Starts an edge at the focused anchor, or finishes the edge being drawn there.
+    ///
+    /// # Returns
+    /// * `Some((from, to))` if an edge was created
+    /// * `None` if an edge was started, or no edge could be created
+    fn confirm_focus(&mut self, template: &EdgeProps) -> Option<(usize, usize)> {
+        let focused = self.focused?;
+        if self.dragged_anchor.is_none() {
+            self.dragged_anchor = Some(focused);
+            self.keyboard_link = true;
+            return None;
+        }
+        self.finish_drag(Some(focused), template)
+    }
+
+    /// Aborts whatever is in progress and clears the selection.
+    fn cancel(&mut self) {
+        self.abort_gesture();
+        self.selection.clear();
+    }
+
+    /// Aborts the drag, move or rubber band in progress, keeping the selection.
+    fn abort_gesture(&mut self) {
+        self.dragged_anchor = None;
+        self.keyboard_link = false;
+        self.cancel_move();
+        if let Some(SelectionMove { origins, .. }) = self.moving_selection.take() {
+            for (index, origin) in origins {
+                self.anchors[index].pos = origin;
+            }
+        }
+        self.selecting = None;
+    }
+
+    /// Replaces the board with new anchors and no edges, keeping the settings.
+    fn reset(&mut self, anchors: Vec<Anchor>) {
+        *self = Self {
+            snap: self.snap.clone(),
+            min_spacing: self.min_spacing,
+            zoom: self.zoom,
+            pointer_scale: self.pointer_scale,
+            clipboard: std::mem::take(&mut self.clipboard),
+            ..Self::with_anchors(anchors)
+        };
+    }
+
+    /// Returns the saved form of the board.
+    fn to_board(&self) -> Board {
+        Board {
+            anchors: self.anchors.clone(),
+            edges: self.edges.clone(),
+        }
+    }
+
+    /// Replaces the board with a saved one.
+    fn load_board(&mut self, board: Board) {
+        self.reset(board.anchors);
+        self.edges = board.edges;
+    }
+
     /// Checks if the current drag operation would create an intersecting edge.
     ///
     /// # Arguments
@@ -583,114 +1237,1108 @@ impl InteractionState {
     fn is_dragging_intersecting(&self, current_pos: Pos) -> bool {
         if let Some(anchor) = self.dragged_anchor {
             let line = LineSegment::new(self.anchors[anchor].pos, current_pos);
-            self.edges.iter().any(|(a, b)| {
-                let edge = LineSegment::new(self.anchors[*a].pos, self.anchors[*b].pos);
-                edge.line_segments_intersect(&line)
-            })
+            (0..self.edges.len()).any(|edge| self.edge_line(edge).line_segments_intersect(&line))
         } else {
             false
         }
     }
 
-    /// Removes all edges from the graph while keeping the anchors.
-    fn clear_edges(&mut self) {
-        self.edges.clear();
+    /// Returns the index of the anchor within reach of the given position, if any.
+    fn anchor_at(&self, pos: Pos) -> Option<usize> {
+        self.anchors.iter().position(|anchor| anchor.pos.distance(&pos) < self.hover_tolerance())
     }
 
-    /// Creates random edges between existing anchors.
-    ///
-    /// This function:
-    /// - Guarantees at least one edge is created if there are 2 or more anchors
-    /// - Prevents self-loops (edges from an anchor to itself)
-    /// - Avoids duplicate edges
-    /// - May create additional random edges between anchors
+    /// Returns the line segment of the edge with the given index.
+    fn edge_line(&self, index: usize) -> LineSegment {
+        let edge = &self.edges[index];
+        LineSegment::new(self.anchors[edge.from].pos, self.anchors[edge.to].pos)
+    }
+
+    /// Counts the crossings between the edges of an anchor and all other edges.
     ///
-    /// Does nothing if there are fewer than 2 anchors.
-    fn randomize_edges(&mut self) {
-        if self.anchors.len() < 2 {
-            return;
-        }
+    /// # Arguments
+    /// * `index` - The index of the anchor
+    fn incident_crossings(&self, index: usize) -> usize {
+        self.incident_edges(index).map(|edge| self.edge_crossings(edge)).sum()
+    }
 
-        self.edges.clear();
-        
-        // Ensure at least one edge is created
-        let i = random_range(0, self.anchors.len());
-        loop {
-            let j = random_range(0, self.anchors.len());
-            if i != j {
-                self.edges.push((i, j));
-                break;
-            }
-        }
+    /// Counts the edges crossing the edge with the given index.
+    fn edge_crossings(&self, index: usize) -> usize {
+        self.crossings().degree(index)
+    }
 
-        // Add more random edges
-        for i in 0..self.anchors.len() {
-            let j = random_range(0, self.anchors.len());
-            if i != j && !self.edges.contains(&(i, j)) {
-                self.edges.push((i, j));
-            }
-        }
+    /// Brings the crossing pairs up to date with the edges that were added,
+    /// removed or moved since the last sync. Called once per frame, and by
+    /// edits that compare crossings before and after.
+    fn sync_crossings(&self) {
+        let lines = (0..self.edges.len()).map(|edge| self.edge_line(edge)).collect();
+        self.crossings.borrow_mut().sync(lines);
     }
 
-    /// Returns the number of edges in the graph.
-    fn edge_count(&self) -> usize {
-        self.edges.len()
+    /// Returns the pairs of crossing edges as of the last sync.
+    fn crossings(&self) -> Ref<'_, Crossings> {
+        self.crossings.borrow()
     }
 
-    /// Returns the number of anchors in the graph.
-    fn anchor_count(&self) -> usize {
-        self.anchors.len()
+    /// Returns the edge crossed by the most other edges, if any edges cross.
+    fn most_tangled_edge(&self) -> Option<usize> {
+        // Edges removed since the last sync may still be counted
+        self.crossings().most_tangled().filter(|edge| *edge < self.edges.len())
     }
 
-    /// Removes an anchor and all its connected edges.
+    /// Returns the points where edges cross.
+    fn crossing_points(&self) -> Vec<Pos> {
+        self.crossings().points().collect()
+    }
+
+    /// Returns the number of edges connected to an anchor.
+    fn degree(&self, index: usize) -> usize {
+        self.incident_edges(index).count()
+    }
+
+    /// Returns `true` if the anchor has room for another edge.
+    fn accepts_edge(&self, index: usize) -> bool {
+        self.anchors[index].props.accepts_edge(self.degree(index))
+    }
+
+    /// Returns the indices of all edges connected to an anchor.
+    fn incident_edges(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        self.edges
+            .iter()
+            .enumerate()
+            .filter(move |(_, edge)| edge.touches(index))
+            .map(|(edge, _)| edge)
+    }
+
+    /// Attempts to start moving the anchor at the given position.
     ///
     /// # Arguments
-    /// * `index` - The index of the anchor to remove
+    /// * `pos` - The position where the move starts
     ///
     /// # Returns
-    /// * `true` if the anchor was successfully removed
-    /// * `false` if the index was invalid
-    fn remove_anchor(&mut self, index: usize) -> bool {
-        if index >= self.anchors.len() {
-            return false;
+    /// * `Some(index)` of the anchor being moved
+    /// * `None` if there is no anchor at the position
+    fn try_start_move(&mut self, pos: Pos) -> Option<usize> {
+        let index = self.anchor_at(pos)?;
+        self.moving_anchor = Some(AnchorMove {
+            index,
+            origin: self.anchors[index].pos,
+        });
+        Some(index)
+    }
+
+    /// Aborts the move in progress and returns the anchor to where it started.
+    fn cancel_move(&mut self) {
+        if let Some(AnchorMove { index, origin }) = self.moving_anchor.take() {
+            self.anchors[index].pos = origin;
         }
+    }
 
-        // Remove all edges connected to this anchor
-        self.edges.retain(|(from, to)| *from != index && *to != index);
-        
-        // Update edge indices for anchors after the removed one
-        for (from, to) in &mut self.edges {
-            if *from > index {
-                *from -= 1;
-            }
-            if *to > index {
-                *to -= 1;
-            }
+    /// Places the anchor being moved at the given position as a live preview.
+    fn update_move(&mut self, pos: Pos) {
+        if let Some(AnchorMove { index, .. }) = self.moving_anchor {
+            self.anchors[index].pos = self.snap_position(pos, Some(&|other| other == index)).0;
         }
+    }
 
-        self.anchors.remove(index);
-        true
+    /// Snaps a position according to the snap settings.
+    ///
+    /// # Arguments
+    /// * `pos` - The position to snap
+    /// * `exclude` - Anchors not to align with, usually the ones being moved
+    ///
+    /// # Returns
+    /// The snapped position and the alignment guides to draw
+    fn snap_position(&self, pos: Pos, exclude: Option<&dyn Fn(usize) -> bool>) -> (Pos, Vec<Guide>) {
+        let others = self.anchors
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !exclude.is_some_and(|exclude| exclude(*index)))
+            .map(|(_, anchor)| anchor.pos);
+        self.snap.apply(pos, others)
     }
-}
 
-struct Model {
+    /// Returns the alignment guides of the anchor being moved, or of the
+    /// anchor that would be placed at `placing`.
+    fn snap_guides(&self, placing: Option<Pos>) -> Vec<Guide> {
+        if let Some(AnchorMove { index, .. }) = self.moving_anchor {
+            return self.snap_position(self.anchors[index].pos, Some(&|other| other == index)).1;
+        }
+        if let Some(SelectionMove { grabbed, .. }) = self.moving_selection {
+            let pos = self.anchors[grabbed].pos;
+            return self.snap_position(pos, Some(&|other| self.selection.contains(other))).1;
+        }
+        match placing {
+            Some(pos) => self.snap_position(pos, None).1,
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns the edges of the anchor being moved that cross another edge.
+    fn move_crossings(&self) -> Vec<usize> {
+        let Some(AnchorMove { index, .. }) = self.moving_anchor else {
+            return Vec::new();
+        };
+        self.incident_edges(index).filter(|edge| self.edge_crossings(*edge) > 0).collect()
+    }
+
+    /// Attempts to end a move at the given position.
+    ///
+    /// A move that would make the anchor's edges cross more edges than before
+    /// is rejected and the anchor snaps back to where the move started, unless
+    /// `free_moves` is set. Accepted moves are recorded so they can be undone.
+    ///
+    /// # Arguments
+    /// * `pos` - The position where the move ends
+    ///
+    /// # Returns
+    /// * `Some(index)` of the moved anchor if the move was accepted
+    /// * `None` if no move was in progress or the move was rejected
+    fn try_end_move(&mut self, pos: Pos) -> Option<usize> {
+        let AnchorMove { index, origin } = self.moving_anchor.take()?;
+        let (pos, _) = self.snap_position(pos, Some(&|other| other == index));
+
+        self.anchors[index].pos = origin;
+        self.sync_crossings();
+        let crossings_before = self.incident_crossings(index);
+        self.anchors[index].pos = pos;
+        self.sync_crossings();
+        if !self.free_moves && self.incident_crossings(index) > crossings_before {
+            self.anchors[index].pos = origin;
+            return None;
+        }
+
+        if pos != origin {
+            self.history.record(Edit::MoveAnchor { index, from: origin, to: pos });
+            self.moves += 1;
+        }
+        Some(index)
+    }
+
+    /// Reverts the most recent recorded edit.
+    ///
+    /// # Returns
+    /// `true` if an edit was reverted
+    fn undo(&mut self) -> bool {
+        match self.history.undo() {
+            Some(edit) => {
+                self.apply(&edit, false);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Applies the most recently reverted edit again.
+    ///
+    /// # Returns
+    /// `true` if an edit was applied
+    fn redo(&mut self) -> bool {
+        match self.history.redo() {
+            Some(edit) => {
+                self.apply(&edit, true);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Applies an edit, or reverts it if `forward` is `false`.
+    fn apply(&mut self, edit: &Edit, forward: bool) {
+        match edit {
+            Edit::MoveAnchor { index, from, to } => {
+                self.anchors[*index].pos = if forward { *to } else { *from };
+            }
+            Edit::RemoveAnchor { index, anchor, edges } => {
+                if forward {
+                    self.detach_anchor(*index);
+                } else {
+                    self.anchors.insert(*index, anchor.clone());
+                    self.selection.anchor_inserted(*index);
+                    self.focused = self.focused.map(|focused| if focused >= *index { focused + 1 } else { focused });
+                    for edge in &mut self.edges {
+                        if edge.from >= *index {
+                            edge.from += 1;
+                        }
+                        if edge.to >= *index {
+                            edge.to += 1;
+                        }
+                    }
+                    for (position, edge) in edges {
+                        self.edges.insert(*position, edge.clone());
+                    }
+                }
+            }
+            Edit::RemoveEdge { index, edge } => {
+                if forward {
+                    self.edges.remove(*index);
+                } else {
+                    self.edges.insert(*index, edge.clone());
+                }
+            }
+            Edit::ChangeAnchor { index, from, to } => {
+                self.anchors[*index].props = if forward { to.clone() } else { from.clone() };
+            }
+            Edit::ChangeEdge { index, from, to } => {
+                self.edges[*index].props = if forward { to.clone() } else { from.clone() };
+            }
+            Edit::Add { anchors, edges } => {
+                if forward {
+                    self.anchors.extend(anchors.iter().cloned());
+                    self.edges.extend(edges.iter().cloned());
+                } else {
+                    self.edges.truncate(self.edges.len() - edges.len());
+                    self.anchors.truncate(self.anchors.len() - anchors.len());
+                    self.selection.truncate(self.anchors.len());
+                    self.focused = self.focused.filter(|focused| *focused < self.anchors.len());
+                    self.dragged_anchor = None;
+                    self.moving_anchor = None;
+                    self.moving_selection = None;
+                }
+            }
+            Edit::Group(edits) => {
+                if forward {
+                    edits.iter().for_each(|edit| self.apply(edit, true));
+                } else {
+                    edits.iter().rev().for_each(|edit| self.apply(edit, false));
+                }
+            }
+        }
+    }
+
+    /// Counts the pairs of edges that cross each other.
+    fn crossing_count(&self) -> usize {
+        self.crossings().count()
+    }
+
+    /// Returns the board as a graph for analysis.
+    fn graph(&self) -> Graph<'_> {
+        Graph::new(self.anchors.iter().map(|anchor| anchor.pos).collect(), &self.edges)
+    }
+
+    /// Counts the groups of anchors connected by edges. An anchor without
+    /// edges is a group of its own.
+    fn component_count(&self) -> usize {
+        self.graph().component_count()
+    }
+
+    /// Counts the faces the edges divide the plane into, including the
+    /// unbounded outer face, using Euler's formula V - E + F = 1 + C.
+    ///
+    /// # Returns
+    /// The number of faces, or `None` if edges cross and the drawing has no faces
+    fn face_count(&self) -> Option<usize> {
+        (self.crossing_count() == 0).then(|| self.edges.len() + self.component_count() + 1 - self.anchors.len())
+    }
+
+    /// Starts drawing a selection rectangle or lasso at the given position.
+    fn start_selection(&mut self, pos: Pos) {
+        self.selecting = Some(vec![pos]);
+    }
+
+    /// Adds a point to the selection rectangle or lasso being drawn.
+    fn extend_selection_path(&mut self, pos: Pos) {
+        if let Some(path) = &mut self.selecting {
+            if !path.last().is_some_and(|last| last.distance(&pos) < 2.0) {
+                path.push(pos);
+            }
+        }
+    }
+
+    /// Finishes the selection rectangle or lasso and selects the anchors inside.
+    ///
+    /// # Arguments
+    /// * `shape` - How the drawn path is interpreted
+    /// * `extend` - Whether to add to the selection instead of replacing it
+    ///
+    /// # Returns
+    /// The number of selected anchors
+    fn end_selection(&mut self, shape: Shape, extend: bool) -> usize {
+        let Some(path) = self.selecting.take() else {
+            return self.selection.len();
+        };
+        if !extend {
+            self.selection.clear();
+        }
+        let inside: Vec<usize> = (0..self.anchors.len())
+            .filter(|index| shape.contains(&path, self.anchors[*index].pos))
+            .collect();
+        self.selection.extend(inside);
+        self.selection.len()
+    }
+
+    /// Selects every anchor and edge.
+    fn select_all(&mut self) {
+        self.selection.extend(0..self.anchors.len());
+    }
+
+    /// Returns the indices of the edges between selected anchors.
+    fn selected_edges(&self) -> Vec<usize> {
+        (0..self.edges.len())
+            .filter(|edge| self.selection.contains_edge(self.edges[*edge].ends()))
+            .collect()
+    }
+
+    /// Removes the selected anchors together with their edges as a single edit.
+    ///
+    /// # Returns
+    /// The number of removed anchors
+    fn delete_selection(&mut self) -> usize {
+        let selected: Vec<usize> = self.selection.iter().rev().collect();
+        // Removing from the highest index down keeps the other indices valid
+        let edits: Vec<Edit> = selected
+            .iter()
+            .map(|index| {
+                let anchor = self.anchors[*index].clone();
+                let edges = self.detach_anchor(*index);
+                Edit::RemoveAnchor { index: *index, anchor, edges }
+            })
+            .collect();
+        if !edits.is_empty() {
+            self.history.record(Edit::Group(edits));
+        }
+        selected.len()
+    }
+
+    /// Attempts to start moving the selection by one of its anchors.
+    ///
+    /// # Returns
+    /// `true` if a selected anchor is at the position and the move started
+    fn try_start_selection_move(&mut self, pos: Pos) -> bool {
+        let Some(grabbed) = self.anchor_at(pos).filter(|index| self.selection.contains(*index)) else {
+            return false;
+        };
+        self.moving_selection = Some(SelectionMove {
+            start: pos,
+            grabbed,
+            origins: self.selection.iter().map(|index| (index, self.anchors[index].pos)).collect(),
+        });
+        true
+    }
+
+    /// Offsets the selection being moved as a live preview.
+    fn update_selection_move(&mut self, pos: Pos) {
+        let Some(offset) = self.selection_offset(pos) else {
+            return;
+        };
+        if let Some(SelectionMove { origins, .. }) = &self.moving_selection {
+            for (index, origin) in origins {
+                self.anchors[*index].pos = *origin + offset;
+            }
+        }
+    }
+
+    /// Returns how far the selection being moved is offset when the mouse is
+    /// at `pos`, snapping the anchor it was grabbed by.
+    fn selection_offset(&self, pos: Pos) -> Option<Pos> {
+        let SelectionMove { start, grabbed, origins } = self.moving_selection.as_ref()?;
+        let origin = origins.iter().find(|(index, _)| index == grabbed).map(|(_, origin)| *origin)?;
+        let (snapped, _) = self.snap_position(origin + (pos - *start), Some(&|other| self.selection.contains(other)));
+        Some(snapped - origin)
+    }
+
+    /// Attempts to end moving the selection at the given position.
+    ///
+    /// Like single anchor moves, a move that adds crossings is rejected and
+    /// the selection snaps back. Accepted moves are recorded as a single edit.
+    ///
+    /// # Returns
+    /// `true` if the move was accepted
+    fn try_end_selection_move(&mut self, pos: Pos) -> bool {
+        let Some(offset) = self.selection_offset(pos) else {
+            return false;
+        };
+        let Some(SelectionMove { origins, .. }) = self.moving_selection.take() else {
+            return false;
+        };
+
+        let place = |state: &mut Self, offset: Pos| {
+            for (index, origin) in &origins {
+                state.anchors[*index].pos = *origin + offset;
+            }
+        };
+        place(self, Pos::new(0.0, 0.0));
+        self.sync_crossings();
+        let crossings_before = self.crossing_count();
+        place(self, offset);
+        self.sync_crossings();
+        if !self.free_moves && self.crossing_count() > crossings_before {
+            place(self, Pos::new(0.0, 0.0));
+            return false;
+        }
+
+        let edits: Vec<Edit> = origins
+            .iter()
+            .filter(|(index, origin)| self.anchors[*index].pos != *origin)
+            .map(|(index, origin)| Edit::MoveAnchor { index: *index, from: *origin, to: self.anchors[*index].pos })
+            .collect();
+        if !edits.is_empty() {
+            self.history.record(Edit::Group(edits));
+            self.moves += 1;
+        }
+        true
+    }
+
+    /// Copies the selected anchors and the edges between them.
+    ///
+    /// # Returns
+    /// The number of copied anchors
+    fn copy_selection(&mut self) -> usize {
+        let selected: Vec<usize> = self.selection.iter().collect();
+        let local = |anchor: usize| selected.binary_search(&anchor).ok();
+        self.clipboard = Clipboard {
+            anchors: selected.iter().map(|index| self.anchors[*index].clone()).collect(),
+            edges: self.edges
+                .iter()
+                .filter_map(|edge| Some(Edge::with_props(local(edge.from)?, local(edge.to)?, edge.props.clone())))
+                .collect(),
+        };
+        selected.len()
+    }
+
+    /// Pastes the copied anchors and their edges, shifted by `offset`, and selects them.
+    ///
+    /// The offset is snapped so that the first pasted anchor lands on the
+    /// grid, which keeps copies of anchors on the grid on the grid.
+    ///
+    /// # Returns
+    /// The number of pasted anchors
+    fn paste(&mut self, offset: Pos) -> usize {
+        let Some(first) = self.clipboard.anchors.first().map(|anchor| anchor.pos) else {
+            return 0;
+        };
+        let offset = self.snap_position(first + offset, None).0 - first;
+
+        let base = self.anchors.len();
+        let anchors: Vec<Anchor> = self
+            .clipboard
+            .anchors
+            .iter()
+            .map(|anchor| Anchor { pos: anchor.pos + offset, ..anchor.clone() })
+            .collect();
+        let edges: Vec<Edge> = self
+            .clipboard
+            .edges
+            .iter()
+            .map(|edge| Edge::with_props(base + edge.from, base + edge.to, edge.props.clone()))
+            .collect();
+        let edit = Edit::Add { anchors, edges };
+        self.apply(&edit, true);
+        self.history.record(edit);
+
+        self.selection.clear();
+        self.selection.extend(base..self.anchors.len());
+        self.clipboard.anchors.len()
+    }
+
+    /// Connects selected anchors wherever the new edge would not cross any
+    /// other edge and both anchors have capacity left, shortest pairs first.
+    ///
+    /// # Arguments
+    /// * `template` - The properties of the new edges
+    ///
+    /// # Returns
+    /// The number of added edges
+    fn connect_selection(&mut self, template: &EdgeProps) -> usize {
+        let selected: Vec<usize> = self.selection.iter().collect();
+        let mut pairs: Vec<(usize, usize)> = selected
+            .iter()
+            .enumerate()
+            .flat_map(|(i, from)| selected[i + 1..].iter().map(move |to| (*from, *to)))
+            .collect();
+        planar::shortest_first(&self.anchors, &mut pairs);
+        let pairs = planar::connect(&self.anchors, &self.edges, pairs, usize::MAX);
+        self.add_edges(pairs, template)
+    }
+
+    /// Completes the board to a maximal planar graph, keeping the existing
+    /// edges: afterwards no more edges fit without crossing.
+    ///
+    /// # Arguments
+    /// * `template` - The properties of the new edges
+    ///
+    /// # Returns
+    /// The number of added edges
+    fn triangulate(&mut self, template: &EdgeProps) -> usize {
+        let pairs = planar::triangulate(&self.anchors, &self.edges);
+        self.add_edges(pairs, template)
+    }
+
+    /// Adds edges between the pairs of anchors as one undoable edit.
+    ///
+    /// # Returns
+    /// The number of added edges
+    fn add_edges(&mut self, pairs: Vec<(usize, usize)>, template: &EdgeProps) -> usize {
+        let added: Vec<Edge> = pairs.into_iter().map(|(from, to)| Edge::with_props(from, to, template.clone())).collect();
+        let count = added.len();
+        if count > 0 {
+            self.edges.extend(added.iter().cloned());
+            self.history.record(Edit::Add { anchors: Vec::new(), edges: added });
+        }
+        count
+    }
+
+    /// Moves the anchors by one step of the layout, pinning the selected
+    /// anchors if the layout asks for it. The move is not recorded, see
+    /// `record_layout`.
+    ///
+    /// # Returns
+    /// The farthest any anchor moved
+    fn layout_step(&mut self, layout: &mut Layout) -> f32 {
+        let positions: Vec<Pos> = self.anchors.iter().map(|anchor| anchor.pos).collect();
+        let pinned = self.layout_pins(layout);
+        let next = layout.step(&positions, &self.edges, &pinned);
+        for (anchor, pos) in self.anchors.iter_mut().zip(&next) {
+            anchor.pos = *pos;
+        }
+        layout::farthest_move(&positions, &next)
+    }
+
+    /// Runs the layout until the anchors settle, as one undoable edit.
+    ///
+    /// # Returns
+    /// The number of steps taken
+    fn run_layout(&mut self, layout: &mut Layout) -> usize {
+        let positions: Vec<Pos> = self.anchors.iter().map(|anchor| anchor.pos).collect();
+        let pinned = self.layout_pins(layout);
+        let (settled, steps) = layout.settle(&positions, &self.edges, &pinned);
+        for (anchor, pos) in self.anchors.iter_mut().zip(settled) {
+            anchor.pos = pos;
+        }
+        self.record_layout(&positions);
+        steps
+    }
+
+    /// Returns whether each anchor stays where it is during a layout.
+    fn layout_pins(&self, layout: &Layout) -> Vec<bool> {
+        (0..self.anchors.len()).map(|index| layout.options.pin_selection && self.selection.contains(index)).collect()
+    }
+
+    /// Records the moves from where the anchors were before a layout ran as
+    /// one undoable edit. Nothing is recorded if anchors were added or
+    /// removed in the meantime, since the positions no longer match up.
+    ///
+    /// # Returns
+    /// `true` if any anchor moved
+    fn record_layout(&mut self, origins: &[Pos]) -> bool {
+        if origins.len() != self.anchors.len() {
+            return false;
+        }
+        let edits: Vec<Edit> = origins
+            .iter()
+            .enumerate()
+            .filter(|(index, origin)| self.anchors[*index].pos != **origin)
+            .map(|(index, origin)| Edit::MoveAnchor { index, from: *origin, to: self.anchors[index].pos })
+            .collect();
+        if edits.is_empty() {
+            return false;
+        }
+        self.history.record(Edit::Group(edits));
+        true
+    }
+
+    /// Returns the edge closest to the given position, if any is within `tolerance`.
+    ///
+    /// # Arguments
+    /// * `pos` - The position to test
+    /// * `tolerance` - The maximum distance between the position and the edge
+    fn edge_at(&self, pos: Pos, tolerance: f32) -> Option<usize> {
+        (0..self.edges.len())
+            .map(|edge| (edge, self.edge_line(edge).distance_to(&pos)))
+            .filter(|(_, distance)| *distance <= tolerance)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(edge, _)| edge)
+    }
+
+    /// Returns the anchor or edge under the given position. Anchors take precedence.
+    fn hit_test(&self, pos: Pos) -> Option<Hit> {
+        self.anchor_at(pos)
+            .map(Hit::Anchor)
+            .or_else(|| self.edge_at(pos, EDGE_HIT_TOLERANCE * self.pointer_scale / self.zoom).map(Hit::Edge))
+    }
+
+    /// Returns `true` if the anchor or edge is on the board.
+    fn exists(&self, hit: Hit) -> bool {
+        match hit {
+            Hit::Anchor(index) => index < self.anchors.len(),
+            Hit::Edge(index) => index < self.edges.len(),
+        }
+    }
+
+    /// Removes the anchor or edge under the given position.
+    ///
+    /// # Returns
+    /// What was removed, if anything
+    fn remove_at(&mut self, pos: Pos) -> Option<Hit> {
+        let hit = self.hit_test(pos)?;
+        match hit {
+            Hit::Anchor(index) => self.remove_anchor(index),
+            Hit::Edge(index) => self.remove_edge(index),
+        };
+        Some(hit)
+    }
+
+    /// Removes a single edge, keeping its anchors.
+    ///
+    /// # Arguments
+    /// * `index` - The index of the edge to remove
+    ///
+    /// # Returns
+    /// * `true` if the edge was removed
+    /// * `false` if the index was invalid
+    fn remove_edge(&mut self, index: usize) -> bool {
+        if index >= self.edges.len() {
+            return false;
+        }
+
+        let edge = self.edges.remove(index);
+        self.history.record(Edit::RemoveEdge { index, edge });
+        true
+    }
+
+    /// Moves an anchor to a position typed in, unless that would make its
+    /// edges cross more edges than before. Moves of the same anchor are
+    /// undone together, and count as one move, until the history is sealed.
+    ///
+    /// # Returns
+    /// * `true` if the anchor was moved
+    /// * `false` if the index was invalid or the move would add crossings
+    fn set_anchor_position(&mut self, index: usize, pos: Pos) -> bool {
+        if index >= self.anchors.len() {
+            return false;
+        }
+        let origin = self.anchors[index].pos;
+        self.sync_crossings();
+        let before = self.incident_crossings(index);
+        self.anchors[index].pos = pos;
+        self.sync_crossings();
+        if !self.free_moves && self.incident_crossings(index) > before {
+            self.anchors[index].pos = origin;
+            return false;
+        }
+        if !self.history.record_merged(Edit::MoveAnchor { index, from: origin, to: pos }) {
+            self.moves += 1;
+        }
+        true
+    }
+
+    /// Changes the properties of an anchor. Changes to the same anchor are
+    /// undone together until the history is sealed.
+    ///
+    /// # Returns
+    /// * `true` if the properties changed
+    /// * `false` if the index was invalid, the properties were the same, or
+    ///   they would not allow the edges the anchor already has
+    fn set_anchor_props(&mut self, index: usize, props: AnchorProps) -> bool {
+        if index >= self.anchors.len() || self.anchors[index].props == props || !props.allows_degree(self.degree(index)) {
+            return false;
+        }
+        let from = std::mem::replace(&mut self.anchors[index].props, props.clone());
+        self.history.record_merged(Edit::ChangeAnchor { index, from, to: props });
+        true
+    }
+
+    /// Changes the properties of an edge. Changes to the same edge are
+    /// undone together until the history is sealed.
+    ///
+    /// # Returns
+    /// * `true` if the properties changed
+    /// * `false` if the index was invalid or the properties were the same
+    fn set_edge_props(&mut self, index: usize, props: EdgeProps) -> bool {
+        let Some(edge) = self.edges.get_mut(index) else {
+            return false;
+        };
+        if edge.props == props {
+            return false;
+        }
+        let from = std::mem::replace(&mut edge.props, props.clone());
+        self.history.record_merged(Edit::ChangeEdge { index, from, to: props });
+        true
+    }
+
+    /// Removes all edges from the graph while keeping the anchors.
+    fn clear_edges(&mut self) {
+        self.edges.clear();
+        self.history.clear();
+    }
+
+    /// Creates random edges between existing anchors.
+    ///
+    /// This function:
+    /// - Guarantees at least one edge is created if there are 2 or more anchors
+    /// - Prevents self-loops (edges from an anchor to itself)
+    /// - Avoids duplicate edges
+    /// - May create additional random edges between anchors
+    ///
+    /// Does nothing if there are fewer than 2 anchors.
+    fn randomize_edges(&mut self) {
+        if self.anchors.len() < 2 {
+            return;
+        }
+
+        self.edges.clear();
+        self.history.clear();
+        
+        // Ensure at least one edge is created
+        let i = random_range(0, self.anchors.len());
+        loop {
+            let j = random_range(0, self.anchors.len());
+            if i != j {
+                self.edges.push(Edge::new(i, j));
+                break;
+            }
+        }
+
+        // Add more random edges
+        for i in 0..self.anchors.len() {
+            let j = random_range(0, self.anchors.len());
+            if i != j && !self.edges.iter().any(|edge| *edge == (i, j)) {
+                self.edges.push(Edge::new(i, j));
+            }
+        }
+    }
+
+    /// Creates random edges between nearby anchors, without any crossings.
+    ///
+    /// Like `randomize_edges` it replaces all edges and creates about one edge
+    /// per anchor, but only between an anchor and its closest neighbours and
+    /// only where the edge crosses none of the others.
+    fn randomize_planar_edges(&mut self) {
+        self.edges.clear();
+        self.history.clear();
+
+        let mut pairs = planar::nearest_pairs(&self.anchors, 6);
+        pairs.shuffle(&mut rand::thread_rng());
+        self.edges = planar::connect(&self.anchors, &[], pairs, self.anchors.len())
+            .into_iter()
+            .map(|(from, to)| Edge::new(from, to))
+            .collect();
+    }
+
+    /// Returns the number of edges in the graph.
+    fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    /// Returns the number of anchors in the graph.
+    fn anchor_count(&self) -> usize {
+        self.anchors.len()
+    }
+
+    /// Returns how close the board is to being full, from 0.0 to 1.0.
+    ///
+    /// A planar graph on n anchors has at most 3n - 6 edges, so the game
+    /// ends at the latest when the edge count reaches that bound.
+    fn completion(&self) -> f32 {
+        if self.anchors.len() < 3 {
+            return if self.edges.is_empty() { 0.0 } else { 1.0 };
+        }
+        let max_edges = 3 * self.anchors.len() - 6;
+        (self.edges.len() as f32 / max_edges as f32).min(1.0)
+    }
+
+    /// Removes an anchor and all its connected edges.
+    ///
+    /// The removal is recorded, so undoing it restores the anchor, its edges
+    /// and the indices of all other anchors.
+    ///
+    /// # Arguments
+    /// * `index` - The index of the anchor to remove
+    ///
+    /// # Returns
+    /// * `true` if the anchor was successfully removed
+    /// * `false` if the index was invalid
+    fn remove_anchor(&mut self, index: usize) -> bool {
+        if index >= self.anchors.len() {
+            return false;
+        }
+
+        let anchor = self.anchors[index].clone();
+        let edges = self.detach_anchor(index);
+        self.history.record(Edit::RemoveAnchor { index, anchor, edges });
+        true
+    }
+
+    /// Removes an anchor and its edges and shifts the indices of later anchors.
+    ///
+    /// # Returns
+    /// The removed edges with their former positions in the edge list, in ascending order
+    fn detach_anchor(&mut self, index: usize) -> Vec<(usize, Edge)> {
+        let removed: Vec<(usize, Edge)> = self.edges
+            .iter()
+            .cloned()
+            .enumerate()
+            .filter(|(_, edge)| edge.touches(index))
+            .collect();
+
+        // Remove all edges connected to this anchor
+        self.edges.retain(|edge| !edge.touches(index));
+
+        // Update edge indices for anchors after the removed one
+        for edge in &mut self.edges {
+            if edge.from > index {
+                edge.from -= 1;
+            }
+            if edge.to > index {
+                edge.to -= 1;
+            }
+        }
+
+        self.anchors.remove(index);
+        self.selection.anchor_removed(index);
+        self.focused = match self.focused {
+            Some(focused) if focused == index => None,
+            Some(focused) if focused > index => Some(focused - 1),
+            focused => focused,
+        };
+        // Drags and moves refer to the old indices
+        self.dragged_anchor = None;
+        self.moving_anchor = None;
+        self.moving_selection = None;
+        removed
+    }
+}
+
+struct Model {
     interaction: InteractionState,
     audio: Option<audio::Handle>,
+    output: audio::Output,
     last_drag_length: Option<f32>,
-    freq: Arc<Mutex<FreqWrapper>>,
+    controls: audio::Controls,
+    conductor: music::Conductor,
+    recorder: recording::Recorder,
+    /// Outcome of the last recording export, shown in the Settings window
+    recording_status: Option<String>,
     egui: Option<Egui>,
     wiggle_anchors: bool,
+    /// Turns mouse and touch events into editor commands
+    input: Input,
+    /// What dragging with the left mouse button does
+    tool: Tool,
+    /// Properties given to the edges drawn with the connect tool
+    edge_template: EdgeProps,
+    /// The anchor or edge pinned in the inspector by clicking it, if any
+    inspected: Option<Hit>,
+    /// Which analysis result is drawn on the board
+    highlight: Highlight,
+    /// The anchors picked as the start and end of the highlighted path
+    path_ends: (Option<usize>, Option<usize>),
+    /// How the highlighted path is measured
+    metric: Metric,
+    /// How the next game is set up
+    rules: Rules,
+    /// The game being played, if any
+    game: Option<Game>,
+    /// How hard the next untangle puzzle is
+    difficulty: Difficulty,
+    /// The untangle puzzle being solved, if any
+    puzzle: Option<Puzzle>,
+    /// What the next hint works towards
+    objective: Objective,
+    /// The search behind the hint shown, until the board changes
+    solver: Option<Solver>,
+    /// Which anchors the next spanning tree challenge connects
+    terminals: Terminals,
+    /// The spanning tree challenge being played, if any
+    challenge: Option<Challenge>,
+    /// The force-directed layout and how it is run
+    layout: Layout,
+    /// Where the anchors were when the animated layout started, while it runs
+    layout_origins: Option<Vec<Pos>>,
+    /// Outcome of the last layout run, shown in the Layout window
+    layout_status: Option<String>,
+    /// The part of the board shown in the window
+    camera: Camera,
+    /// Which keys trigger which actions
+    keymap: Keymap,
+    /// The action waiting for a key press to be bound to
+    rebinding: Option<Action>,
+    /// Outcome of the last board or bindings save or load
+    status: Option<String>,
+    /// The format boards are imported and exported in
+    exchange_format: Format,
+    /// The file boards are exported to, and imported from natively
+    exchange_file: String,
+    /// The file picked to import, until it has been read
+    import: Option<files::Pick>,
+    analyser: spectrum::Analyser,
+    overlay: Overlay,
+}
+
+impl Model {
+    /// Returns the board position under the mouse or finger.
+    fn pointer(&self) -> Pos {
+        self.camera.to_world(self.input.pointer)
+    }
+
+    /// Returns `true` if an egui window lies at a screen position, or egui is
+    /// using the pointer, as while dragging a slider.
+    fn ui_at(&self, window: Rect, at: Pos) -> bool {
+        self.egui.as_ref().is_some_and(|egui| {
+            let ctx = egui.ctx();
+            // egui measures from the top left corner, downwards
+            ctx.is_using_pointer() || ctx.layer_id_at(egui::pos2(at.x - window.left(), window.top() - at.y)).is_some()
+        })
+    }
+
+    /// Returns `true` while an untangle puzzle is being solved, which only allows moving anchors.
+    fn solving(&self) -> bool {
+        self.puzzle.as_ref().is_some_and(|puzzle| !puzzle.solved)
+    }
+
+    /// Returns `true` while a game is running, which only allows the player
+    /// to move to draw an edge.
+    fn playing(&self) -> bool {
+        self.game.as_ref().is_some_and(|game| !game.is_over())
+    }
+
+    /// Returns `true` if the board may be changed other than by moving
+    /// anchors or drawing an edge. Solving a puzzle only allows moves, as
+    /// other edits could remove crossings, and a game only allows drawing.
+    fn may_edit(&self) -> bool {
+        !self.solving() && !self.playing()
+    }
+
+    /// Returns `true` if anchors may be moved and edits undone. A game allows
+    /// neither, as moves would reroute edges and undoing would keep the turn.
+    fn may_move(&self) -> bool {
+        !self.playing()
+    }
+
+    /// Returns `true` if edges may be drawn one at a time, which solving a
+    /// puzzle does not allow.
+    fn may_draw(&self) -> bool {
+        !self.solving()
+    }
+
+    /// Returns the properties of the next edge drawn: the template, claimed
+    /// by the player to move while a game is running.
+    fn new_edge_props(&self) -> EdgeProps {
+        match &self.game {
+            Some(game) if !game.is_over() => game.claim(&self.edge_template),
+            _ => self.edge_template.clone(),
+        }
+    }
+}
+
+/// What dragging with the left mouse button does.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Tool {
+    /// Place anchors on empty space
+    Place,
+    /// Draw edges between anchors
+    Connect,
+    /// Select the anchors inside a rectangle
+    RectSelect,
+    /// Select the anchors inside a freehand outline
+    Lasso,
+}
+
+impl Tool {
+    /// Returns the selection shape drawn by this tool, if it selects.
+    fn shape(self) -> Option<Shape> {
+        match self {
+            Tool::Place | Tool::Connect => None,
+            Tool::RectSelect => Some(Shape::Rect),
+            Tool::Lasso => Some(Shape::Lasso),
+        }
+    }
+}
+
+/// Analysis result drawn on the board.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Highlight {
+    /// No analysis is drawn
+    None,
+    /// Anchors are tinted by their connected component
+    Components,
+    /// Bridges and articulation points are marked
+    Cuts,
+    /// A cycle is marked, if there is one
+    Cycle,
+    /// The shortest path between the picked anchors is marked
+    Path,
+}
+
+/// Audio visualisation drawn on top of the board.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Overlay {
+    /// No visualisation
+    None,
+    /// Waveform of the most recent samples
+    Oscilloscope,
+    /// Levels of the logarithmic frequency bands
+    Spectrum,
 }
 
 fn model() -> Model {
-    let rect = Rect::from_w_h(1024.0, 1024.0);
+    let recording_flag = Arc::new(AtomicBool::new(false));
+
+    // Fall back to the default bindings when none were saved or they are unreadable
+    let keymap = files::load(KEYMAP_FILE)
+        .and_then(|yaml| Keymap::from_yaml(&yaml))
+        .unwrap_or_default();
+
+    Model {
+        egui: None,
+        interaction: InteractionState::with_anchors(random_anchors()),
+        audio: None,
+        output: audio::Output::new(),
+        last_drag_length: None,
+        controls: audio::Controls {
+            freq: Arc::new(Mutex::new(FreqWrapper {
+                value: 0.0,
+                volume: f32::from_bits(VOLUME.load(Ordering::Relaxed)),
+                pan: 0.0,
+                gain: 1.0,
+            })),
+            music: Arc::new(Mutex::new(music::MusicParams::default())),
+            recording: recording_flag.clone(),
+        },
+        recorder: recording::Recorder::new(recording_flag),
+        recording_status: None,
+        conductor: music::Conductor::new(),
+        wiggle_anchors: false,
+        input: Input::default(),
+        tool: Tool::Connect,
+        edge_template: EdgeProps::default(),
+        inspected: None,
+        highlight: Highlight::None,
+        path_ends: (None, None),
+        metric: Metric::Hops,
+        rules: Rules::default(),
+        game: None,
+        difficulty: Difficulty::Easy,
+        puzzle: None,
+        objective: Objective::Untangle,
+        solver: None,
+        terminals: Terminals::All,
+        challenge: None,
+        layout: Layout::default(),
+        layout_origins: None,
+        layout_status: None,
+        camera: Camera::default(),
+        keymap,
+        rebinding: None,
+        status: None,
+        exchange_format: Format::GraphMl,
+        exchange_file: format!("hexbattle-board.{}", Format::GraphMl.extension()),
+        import: None,
+        analyser: spectrum::Analyser::new(),
+        overlay: Overlay::None,
+    }
+}
+
+/// Scatters anchors over the board, keeping them at least `DEFAULT_MIN_SPACING` apart.
+fn random_anchors() -> Vec<Anchor> {
+    let rect = Rect::from_w_h(BOARD_SIZE, BOARD_SIZE);
     let anchors_amount = (rect.w() * rect.h() / 1000.0).round() as usize;
     let mut anchors: Vec<Anchor> = (0..anchors_amount)
-        .map(|_| Anchor {
-            pos: Pos::new(
+        .map(|_| {
+            Anchor::new(Pos::new(
                 random_range(rect.left(), rect.right()),
                 random_range(rect.top(), rect.bottom()),
-            ),
+            ))
         })
         .collect();
 
@@ -701,20 +2349,13 @@ fn model() -> Model {
                 continue;
             }
 
-            if anchors[i].pos.distance(&anchors[j].pos) < 50.0 {
+            if anchors[i].pos.distance(&anchors[j].pos) < DEFAULT_MIN_SPACING {
                 anchors.remove(j);
             }
         }
     }
 
-    Model {
-        egui: None,
-        interaction: InteractionState::with_anchors(anchors),
-        audio: None,
-        last_drag_length: None,
-        freq: Arc::new(Mutex::new(FreqWrapper { value: 100.0, volume: f32::from_bits(VOLUME.load(Ordering::Relaxed)) })),
-        wiggle_anchors: false,
-    }
+    anchors
 }
 
 #[cfg(test)]
@@ -723,9 +2364,9 @@ mod interaction_tests {
 
     fn setup_test_state() -> InteractionState {
         let anchors = vec![
-            Anchor { pos: Pos::new(0.0, 0.0) },
-            Anchor { pos: Pos::new(100.0, 0.0) },
-            Anchor { pos: Pos::new(50.0, 100.0) },
+            Anchor::new(Pos::new(0.0, 0.0)),
+            Anchor::new(Pos::new(100.0, 0.0)),
+            Anchor::new(Pos::new(50.0, 100.0)),
         ];
         InteractionState::with_anchors(anchors)
     }
@@ -735,9 +2376,225 @@ mod interaction_tests {
         let mut state = InteractionState::new();
         let result = state.try_start_drag(Pos::new(50.0, 50.0));
         assert!(result.is_none());
-        assert_eq!(state.anchors.len(), 1);
-        assert_eq!(state.anchors[0].pos.x, 50.0);
-        assert_eq!(state.anchors[0].pos.y, 50.0);
+        // Missing an anchor does not place a new one
+        assert_eq!(state.anchors.len(), 0);
+        assert_eq!(state.dragged_anchor, None);
+    }
+
+    #[test]
+    fn test_place_anchor() {
+        let mut state = InteractionState::new();
+        assert_eq!(state.try_place_anchor(Pos::new(50.0, 50.0)), Some(0));
+        assert_eq!(state.anchors[0].pos, Pos::new(50.0, 50.0));
+        assert_eq!(state.dragged_anchor, None);
+    }
+
+    #[test]
+    fn test_place_anchor_enforces_min_spacing() {
+        let mut state = setup_test_state();
+        assert_eq!(state.try_place_anchor(Pos::new(30.0, 30.0)), None);
+        assert_eq!(state.anchor_count(), 3);
+        assert_eq!(state.try_place_anchor(Pos::new(0.0, DEFAULT_MIN_SPACING)), Some(3));
+
+        state.min_spacing = 10.0;
+        assert_eq!(state.try_place_anchor(Pos::new(30.0, 30.0)), Some(4));
+    }
+
+    #[test]
+    fn test_min_spacing_applies_after_snapping() {
+        let mut state = setup_test_state();
+        state.snap = Snap { grid: Grid::Square, spacing: 100.0, align: false };
+        // Far enough from (100, 0) as clicked, but snaps right onto it
+        assert_eq!(state.try_place_anchor(Pos::new(140.0, 40.0)), None);
+        assert_eq!(state.try_place_anchor(Pos::new(160.0, 40.0)), Some(3));
+        assert_eq!(state.anchors[3].pos, Pos::new(200.0, 0.0));
+    }
+
+    #[test]
+    fn test_hover_tolerance_grows_when_zoomed_out() {
+        let mut state = setup_test_state();
+        assert_eq!(state.anchor_at(Pos::new(15.0, 0.0)), None);
+        assert_eq!(state.hit_test(Pos::new(50.0, 9.0)), None);
+
+        state.zoom = 0.5;
+        assert_eq!(state.anchor_at(Pos::new(15.0, 0.0)), Some(0));
+        assert_eq!(state.try_start_drag(Pos::new(15.0, 0.0)), Some(0));
+        assert_eq!(state.try_end_drag(Pos::new(85.0, 0.0), &EdgeProps::default()), Some((0, 1)));
+        assert_eq!(state.hit_test(Pos::new(50.0, 9.0)), Some(Hit::Edge(0)));
+
+        // Zoomed in, the cursor has to be closer
+        state.zoom = 2.0;
+        assert_eq!(state.anchor_at(Pos::new(6.0, 0.0)), None);
+    }
+
+    #[test]
+    fn test_new_edges_copy_the_template() {
+        let mut state = setup_test_state();
+        let template = EdgeProps { kind: EdgeKind::Wall, owner: Some(1), weight: 4.0, label: None };
+        state.try_start_drag(Pos::new(0.0, 0.0));
+        state.try_end_drag(Pos::new(100.0, 0.0), &template);
+        assert_eq!(state.edges[0].props, template);
+
+        // Copies keep the properties of the copied edges
+        state.select_all();
+        state.copy_selection();
+        state.paste(Pos::new(0.0, 300.0));
+        assert_eq!(state.edges[1], (3, 4));
+        assert_eq!(state.edges[1].props, template);
+    }
+
+    #[test]
+    fn test_edge_property_changes_undo_together() {
+        let mut state = setup_test_state();
+        state.try_start_drag(Pos::new(0.0, 0.0));
+        state.try_end_drag(Pos::new(100.0, 0.0), &EdgeProps::default());
+
+        for weight in [2.0, 3.0, 4.0] {
+            assert!(state.set_edge_props(0, EdgeProps { weight, ..EdgeProps::default() }));
+        }
+        assert!(!state.set_edge_props(0, EdgeProps { weight: 4.0, ..EdgeProps::default() }));
+        assert!(!state.set_edge_props(5, EdgeProps::default()));
+
+        state.undo();
+        assert_eq!(state.edges[0].props, EdgeProps::default());
+        state.redo();
+        assert_eq!(state.edges[0].props.weight, 4.0);
+        // The next undo removes the edge itself
+        state.undo();
+        state.undo();
+        assert!(state.edges.is_empty());
+    }
+
+    #[test]
+    fn test_full_anchors_reject_edges() {
+        let mut state = setup_test_state();
+        state.anchors[0].props.capacity = Some(1);
+        let template = EdgeProps::default();
+
+        state.try_start_drag(Pos::new(0.0, 0.0));
+        assert_eq!(state.try_end_drag(Pos::new(100.0, 0.0), &template), Some((0, 1)));
+        state.try_start_drag(Pos::new(50.0, 100.0));
+        assert_eq!(state.try_end_drag(Pos::new(0.0, 0.0), &template), None);
+        // A full anchor is not a crossing
+        assert_eq!(state.blocked_attempts, 0);
+
+        state.anchors[2].props.kind = AnchorKind::Blocker;
+        state.try_start_drag(Pos::new(100.0, 0.0));
+        assert_eq!(state.try_end_drag(Pos::new(50.0, 100.0), &template), None);
+
+        state.select_all();
+        assert_eq!(state.connect_selection(&template), 0);
+        assert_eq!(state.edge_count(), 1);
+    }
+
+    #[test]
+    fn test_anchor_properties_cannot_drop_below_degree() {
+        let mut state = setup_test_state();
+        state.try_start_drag(Pos::new(0.0, 0.0));
+        state.try_end_drag(Pos::new(100.0, 0.0), &EdgeProps::default());
+
+        let full = AnchorProps { capacity: Some(0), ..AnchorProps::default() };
+        assert!(!state.set_anchor_props(0, full));
+        let blocker = AnchorProps { kind: AnchorKind::Blocker, ..AnchorProps::default() };
+        assert!(!state.set_anchor_props(0, blocker.clone()));
+        assert!(state.set_anchor_props(2, blocker));
+
+        let base = AnchorProps { kind: AnchorKind::Base, owner: Some(1), capacity: Some(1), hit_points: 300 };
+        assert!(state.set_anchor_props(0, base.clone()));
+        assert_eq!(state.anchors[0].props, base);
+        state.undo();
+        assert_eq!(state.anchors[0].props, AnchorProps::default());
+
+        // Copies keep the properties of the copied anchors
+        state.redo();
+        state.selection.extend([0]);
+        state.copy_selection();
+        state.paste(Pos::new(0.0, 300.0));
+        assert_eq!(state.anchors[3].props, base);
+    }
+
+    #[test]
+    fn test_components_and_faces() {
+        let mut state = setup_test_state();
+        state.anchors.push(Anchor::new(Pos::new(300.0, 300.0)));
+        assert_eq!(state.component_count(), 4);
+        assert_eq!(state.face_count(), Some(1));
+
+        state.edges = vec![Edge::new(0, 1), Edge::new(1, 2), Edge::new(2, 0)];
+        state.sync_crossings();
+        assert_eq!(state.component_count(), 2);
+        assert_eq!(state.face_count(), Some(2));
+
+        // Crossing edges do not divide the plane into faces
+        state.anchors.push(Anchor::new(Pos::new(50.0, -50.0)));
+        state.edges.push(Edge::new(2, 4));
+        state.sync_crossings();
+        assert_eq!(state.component_count(), 2);
+        assert_eq!(state.face_count(), None);
+    }
+
+    #[test]
+    fn test_typed_positions_cannot_add_crossings() {
+        let mut state = setup_test_state();
+        state.anchors.push(Anchor::new(Pos::new(50.0, 50.0)));
+        state.edges = vec![Edge::new(0, 1), Edge::new(2, 3)];
+
+        // Below the edge (0, 1), the edge (2, 3) would cross it
+        assert!(!state.set_anchor_position(3, Pos::new(50.0, -50.0)));
+        assert_eq!(state.anchors[3].pos, Pos::new(50.0, 50.0));
+
+        // Typing a position step by step undoes in one go, and is one move
+        assert!(state.set_anchor_position(3, Pos::new(60.0, 50.0)));
+        assert!(state.set_anchor_position(3, Pos::new(70.0, 50.0)));
+        assert_eq!(state.moves, 1);
+        state.undo();
+        assert_eq!(state.anchors[3].pos, Pos::new(50.0, 50.0));
+        assert!(!state.history.can_undo());
+    }
+
+    #[test]
+    fn test_edits_merge_only_while_being_made() {
+        let mut state = setup_edge_state();
+
+        // A drag and a position typed afterwards undo in two steps
+        state.try_start_move(Pos::new(50.0, 100.0));
+        state.try_end_move(Pos::new(20.0, 150.0));
+        assert!(state.set_anchor_position(2, Pos::new(30.0, 150.0)));
+        assert!(state.set_anchor_position(2, Pos::new(40.0, 150.0)));
+        state.undo();
+        assert_eq!(state.anchors[2].pos, Pos::new(20.0, 150.0));
+        state.undo();
+        assert_eq!(state.anchors[2].pos, Pos::new(50.0, 100.0));
+
+        // Property changes made with separate interactions undo separately
+        let weighted = |weight| EdgeProps { weight, ..EdgeProps::default() };
+        assert!(state.set_edge_props(0, weighted(2.0)));
+        state.history.seal();
+        assert!(state.set_edge_props(0, weighted(3.0)));
+        state.undo();
+        assert_eq!(state.edges[0].props.weight, 2.0);
+        state.undo();
+        assert_eq!(state.edges[0].props, EdgeProps::default());
+
+        // Nor does a change merge into one that was undone and redone
+        assert!(state.set_edge_props(0, weighted(2.0)));
+        state.undo();
+        state.redo();
+        assert!(state.set_edge_props(0, weighted(3.0)));
+        state.undo();
+        assert_eq!(state.edges[0].props.weight, 2.0);
+    }
+
+    #[test]
+    fn test_fingers_reach_further_than_the_mouse() {
+        let mut state = setup_test_state();
+        assert_eq!(state.anchor_at(Pos::new(20.0, 0.0)), None);
+
+        state.pointer_scale = input::TOUCH_TOLERANCE_SCALE;
+        assert_eq!(state.anchor_at(Pos::new(20.0, 0.0)), Some(0));
+        state.try_start_drag(Pos::new(20.0, 0.0));
+        state.try_end_drag(Pos::new(80.0, 0.0), &EdgeProps::default());
+        assert_eq!(state.hit_test(Pos::new(50.0, 12.0)), Some(Hit::Edge(0)));
     }
 
     #[test]
@@ -753,7 +2610,7 @@ mod interaction_tests {
     fn test_create_valid_edge() {
         let mut state = setup_test_state();
         state.try_start_drag(Pos::new(1.0, 1.0)); // Start dragging first anchor
-        let result = state.try_end_drag(Pos::new(99.0, 1.0)); // End near second anchor
+        let result = state.try_end_drag(Pos::new(99.0, 1.0), &EdgeProps::default()); // End near second anchor
         assert_eq!(result, Some((0, 1)));
         assert_eq!(state.edges.len(), 1);
         assert_eq!(state.edges[0], (0, 1));
@@ -763,7 +2620,7 @@ mod interaction_tests {
     fn test_prevent_self_edge() {
         let mut state = setup_test_state();
         state.try_start_drag(Pos::new(1.0, 1.0)); // Start dragging first anchor
-        let result = state.try_end_drag(Pos::new(1.0, 1.0)); // End on same anchor
+        let result = state.try_end_drag(Pos::new(1.0, 1.0), &EdgeProps::default()); // End on same anchor
         assert!(result.is_none());
         assert_eq!(state.edges.len(), 0);
     }
@@ -773,10 +2630,15 @@ mod interaction_tests {
         let mut state = setup_test_state();
         // Create first edge
         state.try_start_drag(Pos::new(1.0, 1.0));
-        state.try_end_drag(Pos::new(99.0, 1.0));
+        state.try_end_drag(Pos::new(99.0, 1.0), &EdgeProps::default());
         // Try to create same edge again
         state.try_start_drag(Pos::new(1.0, 1.0));
-        let result = state.try_end_drag(Pos::new(99.0, 1.0));
+        let result = state.try_end_drag(Pos::new(99.0, 1.0), &EdgeProps::default());
+        assert!(result.is_none());
+        assert_eq!(state.edges.len(), 1);
+        // Nor drawn the other way round, which lies on top of it without crossing
+        state.try_start_drag(Pos::new(99.0, 1.0));
+        let result = state.try_end_drag(Pos::new(1.0, 1.0), &EdgeProps::default());
         assert!(result.is_none());
         assert_eq!(state.edges.len(), 1);
     }
@@ -786,20 +2648,44 @@ mod interaction_tests {
         let mut state = setup_test_state();
         // Create first edge from (0,0) to (100,0)
         state.try_start_drag(Pos::new(1.0, 1.0));
-        state.try_end_drag(Pos::new(99.0, 1.0));
+        state.try_end_drag(Pos::new(99.0, 1.0), &EdgeProps::default());
         // Try to create intersecting edge from (50,100) to (50,-100)
         state.try_start_drag(Pos::new(50.0, 100.0));
-        let result = state.try_end_drag(Pos::new(50.0, -100.0));
+        let result = state.try_end_drag(Pos::new(50.0, -100.0), &EdgeProps::default());
         assert!(result.is_none());
         assert_eq!(state.edges.len(), 1);
     }
 
+    #[test]
+    fn test_blocked_attempts_are_counted() {
+        let mut state = setup_test_state();
+        state.try_start_drag(Pos::new(1.0, 1.0));
+        state.try_end_drag(Pos::new(99.0, 1.0), &EdgeProps::default());
+        assert_eq!(state.blocked_attempts, 0);
+
+        // Connect the top anchor to a new one below the horizontal edge
+        state.try_place_anchor(Pos::new(50.0, -100.0));
+        state.try_start_drag(Pos::new(50.0, 100.0));
+        assert!(state.try_end_drag(Pos::new(50.0, -100.0), &EdgeProps::default()).is_none());
+        assert_eq!(state.blocked_attempts, 1);
+    }
+
+    #[test]
+    fn test_completion() {
+        let mut state = setup_test_state();
+        assert_eq!(state.completion(), 0.0);
+
+        // A triangle is the maximal planar graph on three anchors
+        state.edges = vec![Edge::new(0, 1), Edge::new(1, 2), Edge::new(2, 0)];
+        assert_eq!(state.completion(), 1.0);
+    }
+
     #[test]
     fn test_clear_edges() {
         let mut state = setup_test_state();
         // Create an edge
         state.try_start_drag(Pos::new(1.0, 1.0));
-        state.try_end_drag(Pos::new(99.0, 1.0));
+        state.try_end_drag(Pos::new(99.0, 1.0), &EdgeProps::default());
         assert_eq!(state.edges.len(), 1);
         // Clear edges
         state.clear_edges();
@@ -812,7 +2698,7 @@ mod interaction_tests {
         state.randomize_edges();
         assert!(!state.edges.is_empty());
         // Check that no edge connects an anchor to itself
-        for (from, to) in &state.edges {
+        for &Edge { from, to, .. } in &state.edges {
             assert_ne!(from, to);
         }
     }
@@ -822,7 +2708,7 @@ mod interaction_tests {
         let mut state = setup_test_state();
         // Create horizontal edge from (0,0) to (100,0)
         state.try_start_drag(Pos::new(1.0, 1.0));
-        state.try_end_drag(Pos::new(99.0, 1.0));
+        state.try_end_drag(Pos::new(99.0, 1.0), &EdgeProps::default());
         
         // Start new drag from top point
         state.try_start_drag(Pos::new(50.0, 100.0));
@@ -839,11 +2725,11 @@ mod interaction_tests {
         assert_eq!(state.edge_count(), 0);
 
         state.try_start_drag(Pos::new(1.0, 1.0));
-        state.try_end_drag(Pos::new(99.0, 1.0));
+        state.try_end_drag(Pos::new(99.0, 1.0), &EdgeProps::default());
         assert_eq!(state.edge_count(), 1);
 
-        // Add a new anchor by dragging from empty space
-        state.try_start_drag(Pos::new(200.0, 200.0));
+        // Add a new anchor by placing it on empty space
+        state.try_place_anchor(Pos::new(200.0, 200.0));
         assert_eq!(state.anchor_count(), 4);
     }
 
@@ -860,9 +2746,9 @@ mod interaction_tests {
         let mut state = setup_test_state();
         // Create two edges: (0,1) and (1,2)
         state.try_start_drag(Pos::new(1.0, 1.0));
-        state.try_end_drag(Pos::new(99.0, 1.0));
+        state.try_end_drag(Pos::new(99.0, 1.0), &EdgeProps::default());
         state.try_start_drag(Pos::new(99.0, 1.0));
-        state.try_end_drag(Pos::new(50.0, 100.0));
+        state.try_end_drag(Pos::new(50.0, 100.0), &EdgeProps::default());
         assert_eq!(state.edge_count(), 2);
 
         // Remove middle anchor (index 1)
@@ -876,7 +2762,7 @@ mod interaction_tests {
         let mut state = setup_test_state();
         // Create edge from last to first anchor: (2,0)
         state.try_start_drag(Pos::new(50.0, 100.0));
-        state.try_end_drag(Pos::new(1.0, 1.0));
+        state.try_end_drag(Pos::new(1.0, 1.0), &EdgeProps::default());
         assert_eq!(state.edge_count(), 1);
 
         // Remove middle anchor (index 1)
@@ -901,6 +2787,497 @@ mod interaction_tests {
         assert_eq!(result, Some(0)); // Should still work with updated indices
     }
 
+    fn setup_edge_state() -> InteractionState {
+        // Edge (0,1) along the x axis and a free anchor pair above it
+        let mut state = InteractionState::with_anchors(vec![
+            Anchor::new(Pos::new(0.0, 0.0)),
+            Anchor::new(Pos::new(100.0, 0.0)),
+            Anchor::new(Pos::new(50.0, 100.0)),
+            Anchor::new(Pos::new(80.0, 100.0)),
+        ]);
+        state.edges = vec![Edge::new(0, 1), Edge::new(2, 3)];
+        state
+    }
+
+    #[test]
+    fn test_move_anchor() {
+        let mut state = setup_edge_state();
+        assert_eq!(state.try_start_move(Pos::new(51.0, 101.0)), Some(2));
+        state.update_move(Pos::new(20.0, 150.0));
+        state.sync_crossings();
+        assert!(state.move_crossings().is_empty());
+        assert_eq!(state.try_end_move(Pos::new(20.0, 150.0)), Some(2));
+        assert_eq!(state.anchors[2].pos, Pos::new(20.0, 150.0));
+        assert!(state.moving_anchor.is_none());
+    }
+
+    #[test]
+    fn test_move_on_empty_space() {
+        let mut state = setup_edge_state();
+        assert!(state.try_start_move(Pos::new(300.0, 300.0)).is_none());
+        assert!(state.try_end_move(Pos::new(20.0, 150.0)).is_none());
+    }
+
+    #[test]
+    fn test_move_creating_crossing_snaps_back() {
+        let mut state = setup_edge_state();
+        state.try_start_move(Pos::new(50.0, 100.0));
+        // Pulling the anchor below the x axis makes edge (2,3) cross edge (0,1)
+        state.update_move(Pos::new(50.0, -100.0));
+        state.sync_crossings();
+        assert_eq!(state.move_crossings(), vec![1]);
+        assert!(state.try_end_move(Pos::new(50.0, -100.0)).is_none());
+        assert_eq!(state.anchors[2].pos, Pos::new(50.0, 100.0));
+        assert!(!state.history.can_undo());
+    }
+
+    #[test]
+    fn test_free_moves_may_add_crossings() {
+        let mut state = setup_edge_state();
+        state.free_moves = true;
+        state.try_start_move(Pos::new(50.0, 100.0));
+        assert_eq!(state.try_end_move(Pos::new(50.0, -100.0)), Some(2));
+        assert_eq!(state.crossing_count(), 1);
+        assert_eq!(state.moves, 1);
+
+        // Putting an anchor back where it was is not a move
+        state.try_start_move(Pos::new(0.0, 0.0));
+        assert_eq!(state.try_end_move(Pos::new(0.0, 0.0)), Some(0));
+        assert_eq!(state.moves, 1);
+    }
+
+    #[test]
+    fn test_crossings_follow_edits() {
+        let mut state = setup_edge_state();
+        assert_eq!(state.crossing_count(), 0);
+        assert_eq!(state.most_tangled_edge(), None);
+
+        // Edge (2,3) dips below edge (0,1), and so does a new edge from anchor 2
+        state.anchors[3].pos = Pos::new(80.0, -100.0);
+        state.anchors.push(Anchor::new(Pos::new(0.0, -50.0)));
+        state.edges.push(Edge::new(4, 2));
+        // Queries read the crossings as of the last sync, which each frame does once
+        assert_eq!(state.crossing_count(), 0);
+        state.sync_crossings();
+        assert_eq!(state.crossing_count(), 2);
+        assert_eq!(state.most_tangled_edge(), Some(0));
+        assert_eq!(state.edge_crossings(0), 2);
+        assert_eq!(state.crossing_points().len(), 2);
+        let point = state.crossing_points()[0];
+        assert!(point.y.abs() < 1e-3 && point.x > 50.0 && point.x < 80.0);
+
+        state.remove_edge(1);
+        state.sync_crossings();
+        assert_eq!(state.crossing_count(), 1);
+        assert!(state.undo());
+        state.sync_crossings();
+        assert_eq!(state.crossing_count(), 2);
+    }
+
+    #[test]
+    fn test_layout_pins_selection_and_undoes_in_one_step() {
+        let mut state = setup_edge_state();
+        let before: Vec<Pos> = state.anchors.iter().map(|anchor| anchor.pos).collect();
+        state.selection.extend([0]);
+        let mut layout = Layout::default();
+        assert!(state.run_layout(&mut layout) < layout::MAX_STEPS);
+        assert_eq!(state.anchors[0].pos, before[0]);
+        assert_ne!(state.anchors[2].pos, before[2]);
+
+        assert!(state.undo());
+        let after: Vec<Pos> = state.anchors.iter().map(|anchor| anchor.pos).collect();
+        assert_eq!(after, before);
+    }
+
+    #[test]
+    fn test_move_reducing_crossings_is_allowed() {
+        let mut state = setup_edge_state();
+        state.anchors[2].pos = Pos::new(50.0, -100.0);
+        state.sync_crossings();
+        assert_eq!(state.incident_crossings(2), 1);
+
+        state.try_start_move(Pos::new(50.0, -100.0));
+        assert_eq!(state.try_end_move(Pos::new(50.0, 100.0)), Some(2));
+        assert_eq!(state.incident_crossings(2), 0);
+    }
+
+    #[test]
+    fn test_undo_and_redo_move() {
+        let mut state = setup_edge_state();
+        state.try_start_move(Pos::new(50.0, 100.0));
+        state.try_end_move(Pos::new(20.0, 150.0));
+
+        assert!(state.undo());
+        assert_eq!(state.anchors[2].pos, Pos::new(50.0, 100.0));
+        assert!(!state.undo());
+
+        assert!(state.redo());
+        assert_eq!(state.anchors[2].pos, Pos::new(20.0, 150.0));
+        assert!(!state.redo());
+    }
+
+    #[test]
+    fn test_edge_hit_test() {
+        let state = setup_edge_state();
+        assert_eq!(state.edge_at(Pos::new(50.0, 4.0), EDGE_HIT_TOLERANCE), Some(0));
+        assert_eq!(state.edge_at(Pos::new(50.0, 20.0), EDGE_HIT_TOLERANCE), None);
+        // Past the end of the segment the distance is measured to the end point
+        assert_eq!(state.edge_at(Pos::new(105.0, 0.0), EDGE_HIT_TOLERANCE), Some(0));
+        assert_eq!(state.edge_at(Pos::new(110.0, 0.0), EDGE_HIT_TOLERANCE), None);
+    }
+
+    #[test]
+    fn test_hit_test_prefers_anchors() {
+        let state = setup_edge_state();
+        assert_eq!(state.hit_test(Pos::new(2.0, 0.0)), Some(Hit::Anchor(0)));
+        assert_eq!(state.hit_test(Pos::new(50.0, 2.0)), Some(Hit::Edge(0)));
+        assert_eq!(state.hit_test(Pos::new(300.0, 300.0)), None);
+    }
+
+    #[test]
+    fn test_remove_edge_and_undo() {
+        let mut state = setup_edge_state();
+        assert_eq!(state.remove_at(Pos::new(50.0, 2.0)), Some(Hit::Edge(0)));
+        assert_eq!(state.edges, vec![(2, 3)]);
+        assert_eq!(state.anchor_count(), 4);
+        assert!(!state.remove_edge(5));
+
+        assert!(state.undo());
+        assert_eq!(state.edges, vec![(0, 1), (2, 3)]);
+    }
+
+    #[test]
+    fn test_remove_anchor_undo_restores_indices() {
+        let mut state = setup_edge_state();
+        state.edges = vec![Edge::new(0, 1), Edge::new(2, 3), Edge::new(1, 3)];
+        assert_eq!(state.remove_at(Pos::new(100.0, 0.0)), Some(Hit::Anchor(1)));
+        assert_eq!(state.edges, vec![(1, 2)]);
+
+        assert!(state.undo());
+        assert_eq!(state.anchor_count(), 4);
+        assert_eq!(state.anchors[1].pos, Pos::new(100.0, 0.0));
+        assert_eq!(state.edges, vec![(0, 1), (2, 3), (1, 3)]);
+
+        assert!(state.redo());
+        assert_eq!(state.edges, vec![(1, 2)]);
+    }
+
+    #[test]
+    fn test_line_segment_distance_to() {
+        let line = LineSegment::new(Pos::new(0.0, 0.0), Pos::new(10.0, 0.0));
+        assert_eq!(line.distance_to(&Pos::new(5.0, 3.0)), 3.0);
+        assert_eq!(line.distance_to(&Pos::new(-3.0, 4.0)), 5.0);
+        let point = LineSegment::new(Pos::new(1.0, 1.0), Pos::new(1.0, 1.0));
+        assert_eq!(point.distance_to(&Pos::new(1.0, 4.0)), 3.0);
+    }
+
+    #[test]
+    fn test_rect_selection_selects_edges_between_anchors() {
+        let mut state = setup_edge_state();
+        state.start_selection(Pos::new(-10.0, -10.0));
+        state.extend_selection_path(Pos::new(110.0, 50.0));
+        assert_eq!(state.end_selection(Shape::Rect, false), 2);
+        assert_eq!(state.selected_edges(), vec![0]);
+
+        // Shift extends the selection instead of replacing it
+        state.start_selection(Pos::new(40.0, 90.0));
+        state.extend_selection_path(Pos::new(60.0, 110.0));
+        assert_eq!(state.end_selection(Shape::Rect, true), 3);
+    }
+
+    #[test]
+    fn test_delete_selection_is_undone_at_once() {
+        let mut state = setup_edge_state();
+        state.edges.push(Edge::new(1, 2));
+        state.selection.extend([0, 2]);
+        assert_eq!(state.delete_selection(), 2);
+        assert_eq!(state.anchor_count(), 2);
+        assert!(state.edges.is_empty());
+        assert!(state.selection.is_empty());
+
+        assert!(state.undo());
+        assert_eq!(state.anchor_count(), 4);
+        assert_eq!(state.edges, vec![(0, 1), (2, 3), (1, 2)]);
+        assert_eq!(state.anchors[2].pos, Pos::new(50.0, 100.0));
+    }
+
+    #[test]
+    fn test_selection_survives_anchor_removal() {
+        let mut state = setup_edge_state();
+        state.selection.extend([2, 3]);
+        state.remove_anchor(0);
+        assert_eq!(state.selection.iter().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(state.selected_edges(), vec![0]);
+
+        assert!(state.undo());
+        assert_eq!(state.selection.iter().collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(state.selected_edges(), vec![1]);
+    }
+
+    #[test]
+    fn test_copy_paste_keeps_inner_edges() {
+        let mut state = setup_edge_state();
+        state.edges.push(Edge::new(1, 2));
+        state.selection.extend([2, 3]);
+        assert_eq!(state.copy_selection(), 2);
+        assert_eq!(state.paste(Pos::new(0.0, 100.0)), 2);
+
+        assert_eq!(state.anchor_count(), 6);
+        assert_eq!(state.anchors[4].pos, Pos::new(50.0, 200.0));
+        assert_eq!(state.edges.last().map(Edge::ends), Some((4, 5)));
+        assert_eq!(state.edge_count(), 4);
+        assert_eq!(state.selection.iter().collect::<Vec<_>>(), vec![4, 5]);
+
+        assert!(state.undo());
+        assert_eq!(state.anchor_count(), 4);
+        assert_eq!(state.edge_count(), 3);
+        assert!(state.selection.is_empty());
+    }
+
+    #[test]
+    fn test_move_selection_and_reject_crossings() {
+        let mut state = setup_edge_state();
+        state.selection.extend([2, 3]);
+        assert!(state.try_start_selection_move(Pos::new(50.0, 100.0)));
+        state.update_selection_move(Pos::new(50.0, 150.0));
+        assert_eq!(state.anchors[3].pos, Pos::new(80.0, 150.0));
+        assert!(state.try_end_selection_move(Pos::new(50.0, 150.0)));
+
+        // Moving one end of the edge (2, 3) across the edge (0, 1) is rejected
+        state.selection.clear();
+        state.selection.extend([2]);
+        assert!(state.try_start_selection_move(Pos::new(50.0, 150.0)));
+        assert!(!state.try_end_selection_move(Pos::new(50.0, -100.0)));
+        assert_eq!(state.anchors[2].pos, Pos::new(50.0, 150.0));
+
+        assert!(state.undo());
+        assert_eq!(state.anchors[2].pos, Pos::new(50.0, 100.0));
+        assert_eq!(state.anchors[3].pos, Pos::new(80.0, 100.0));
+
+        // Only selected anchors start a selection move
+        assert!(!state.try_start_selection_move(Pos::new(0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_connect_selection_adds_only_non_crossing_edges() {
+        let mut state = InteractionState::with_anchors(vec![
+            Anchor::new(Pos::new(0.0, 0.0)),
+            Anchor::new(Pos::new(100.0, 0.0)),
+            Anchor::new(Pos::new(100.0, 100.0)),
+            Anchor::new(Pos::new(0.0, 100.0)),
+        ]);
+        state.select_all();
+        // The four sides and one of the two diagonals
+        assert_eq!(state.connect_selection(&EdgeProps::default()), 5);
+        state.sync_crossings();
+        assert_eq!(state.crossing_count(), 0);
+        assert_eq!(state.connect_selection(&EdgeProps::default()), 0);
+
+        assert!(state.undo());
+        assert!(state.edges.is_empty());
+    }
+
+    #[test]
+    fn test_drawn_edges_and_placed_anchors_can_be_undone() {
+        let mut state = InteractionState::new();
+        state.try_place_anchor(Pos::new(0.0, 0.0));
+        state.try_place_anchor(Pos::new(100.0, 0.0));
+        state.try_start_drag(Pos::new(0.0, 0.0));
+        state.try_end_drag(Pos::new(100.0, 0.0), &EdgeProps::default());
+        assert_eq!(state.edge_count(), 1);
+
+        assert!(state.undo());
+        assert_eq!(state.edge_count(), 0);
+        assert!(state.undo());
+        assert_eq!(state.anchor_count(), 1);
+        assert!(state.redo());
+        assert!(state.redo());
+        assert_eq!(state.edges, vec![(0, 1)]);
+    }
+
+    #[test]
+    fn test_snapping_applies_to_placed_moved_and_pasted_anchors() {
+        let mut state = InteractionState::new();
+        state.snap = Snap { grid: Grid::Square, spacing: 20.0, align: false };
+
+        state.try_place_anchor(Pos::new(13.0, 28.0));
+        assert_eq!(state.anchors[0].pos, Pos::new(20.0, 20.0));
+
+        state.try_start_move(Pos::new(20.0, 20.0));
+        state.update_move(Pos::new(57.0, 3.0));
+        assert_eq!(state.anchors[0].pos, Pos::new(60.0, 0.0));
+        state.try_end_move(Pos::new(57.0, 3.0));
+        assert_eq!(state.anchors[0].pos, Pos::new(60.0, 0.0));
+
+        state.select_all();
+        state.copy_selection();
+        state.paste(Pos::new(33.0, 9.0));
+        assert_eq!(state.anchors[1].pos, Pos::new(100.0, 0.0));
+
+        assert!(state.try_start_selection_move(Pos::new(100.0, 0.0)));
+        assert!(state.try_end_selection_move(Pos::new(111.0, -18.0)));
+        assert_eq!(state.anchors[1].pos, Pos::new(120.0, -20.0));
+    }
+
+    #[test]
+    fn test_moved_anchor_aligns_with_others() {
+        let mut state = setup_edge_state();
+        state.snap.align = true;
+        state.try_start_move(Pos::new(80.0, 100.0));
+        assert!(state.snap_guides(None).contains(&Guide::Horizontal(100.0)));
+        state.try_end_move(Pos::new(97.0, 150.0));
+        assert_eq!(state.anchors[3].pos, Pos::new(100.0, 150.0));
+    }
+
+    #[test]
+    fn test_keyboard_focus_moves_in_direction() {
+        let mut state = setup_edge_state();
+        // Without a focus the anchor closest to the centre is focused
+        state.move_focus(Pos::new(1.0, 0.0), Pos::new(60.0, 90.0));
+        assert_eq!(state.focused, Some(2));
+
+        state.move_focus(Pos::new(1.0, 0.0), Pos::new(0.0, 0.0));
+        assert_eq!(state.focused, Some(3));
+        state.move_focus(Pos::new(0.0, -1.0), Pos::new(0.0, 0.0));
+        assert_eq!(state.focused, Some(1));
+        state.move_focus(Pos::new(-1.0, 0.0), Pos::new(0.0, 0.0));
+        assert_eq!(state.focused, Some(0));
+        // Nothing further left keeps the focus where it is
+        state.move_focus(Pos::new(-1.0, 0.0), Pos::new(0.0, 0.0));
+        assert_eq!(state.focused, Some(0));
+    }
+
+    #[test]
+    fn test_keyboard_confirm_draws_edges() {
+        let mut state = setup_edge_state();
+        state.edges.clear();
+        state.focused = Some(0);
+        assert_eq!(state.confirm_focus(&EdgeProps::default()), None);
+        assert_eq!(state.dragged_anchor, Some(0));
+        assert!(state.keyboard_link);
+
+        state.focused = Some(2);
+        assert_eq!(state.confirm_focus(&EdgeProps::default()), Some((0, 2)));
+        assert_eq!(state.dragged_anchor, None);
+        assert!(!state.keyboard_link);
+
+        // Confirming without a focus does nothing
+        state.focused = None;
+        assert_eq!(state.confirm_focus(&EdgeProps::default()), None);
+        assert_eq!(state.dragged_anchor, None);
+    }
+
+    #[test]
+    fn test_focus_follows_anchor_removal() {
+        let mut state = setup_edge_state();
+        state.focused = Some(3);
+        state.remove_anchor(1);
+        assert_eq!(state.focused, Some(2));
+        assert!(state.undo());
+        assert_eq!(state.focused, Some(3));
+        state.remove_anchor(3);
+        assert_eq!(state.focused, None);
+    }
+
+    #[test]
+    fn test_load_board_keeps_settings() {
+        let mut state = setup_edge_state();
+        state.min_spacing = 20.0;
+        state.selection.extend([0, 1]);
+        let board = state.to_board();
+
+        state.reset(vec![Anchor::new(Pos::new(5.0, 5.0))]);
+        assert_eq!(state.anchor_count(), 1);
+        assert_eq!(state.edge_count(), 0);
+        assert!(state.selection.is_empty());
+        assert!(!state.history.can_undo());
+        assert_eq!(state.min_spacing, 20.0);
+
+        state.load_board(board);
+        assert_eq!(state.anchor_count(), 4);
+        assert_eq!(state.edges, vec![(0, 1), (2, 3)]);
+    }
+
+    #[test]
+    fn test_picked_path_ignores_missing_anchors() {
+        let mut state = setup_edge_state();
+        assert_eq!(picked_path(&state, (Some(0), None), Metric::Hops), None);
+        // Anchors 0 and 2 sit in different components
+        assert_eq!(picked_path(&state, (Some(0), Some(2)), Metric::Hops), None);
+        let path = picked_path(&state, (Some(1), Some(0)), Metric::Hops).unwrap();
+        assert_eq!(path.anchors, vec![1, 0]);
+
+        state.remove_anchor(3);
+        assert_eq!(picked_path(&state, (Some(2), Some(3)), Metric::Hops), None);
+    }
+
+    #[test]
+    fn test_won_game_follows_removed_edges() {
+        let base = |x: f32, y: f32, owner: u8| Anchor {
+            pos: Pos::new(x, y),
+            props: AnchorProps { kind: AnchorKind::Base, owner: Some(owner), ..AnchorProps::default() },
+        };
+        let mut state = InteractionState::with_anchors(vec![
+            base(0.0, 0.0, 0),
+            base(200.0, 0.0, 0),
+            Anchor::new(Pos::new(100.0, 50.0)),
+            base(0.0, 300.0, 1),
+            base(200.0, 300.0, 1),
+        ]);
+        let mut game = Game::new(Rules::default());
+        for (from, to) in [(0, 2), (3, 2), (2, 1)] {
+            state.try_start_drag(state.anchors[from].pos);
+            state.try_end_drag(state.anchors[to].pos, &game.claim(&EdgeProps::default()));
+            game.edge_drawn(&state.anchors, &state.edges);
+        }
+        assert_eq!(game.winner.as_ref().map(|win| win.path.clone()), Some(vec![0, 2]));
+        // What `draw_game` looks up to show the replay
+        let draw = |state: &InteractionState, game: &Game| -> Vec<LineSegment> {
+            game.replayed().iter().map(|edge| state.edge_line(*edge)).collect()
+        };
+
+        // Removing the opponent's edge renumbers the winning path
+        state.remove_edge(1);
+        game.recheck(&state.anchors, &state.edges);
+        assert_eq!(game.winner.as_ref().map(|win| win.path.clone()), Some(vec![0, 1]));
+        game.tick(10.0);
+        assert_eq!(draw(&state, &game).len(), 2);
+
+        // Clearing the board takes the win back
+        state.clear_edges();
+        game.recheck(&state.anchors, &state.edges);
+        assert!(!game.is_over());
+        assert_eq!(game.turn, 0);
+        assert!(draw(&state, &game).is_empty());
+    }
+
+    #[test]
+    fn test_triangulate_keeps_edges_and_adds_no_crossings() {
+        let mut state = setup_edge_state();
+        let added = state.triangulate(&EdgeProps::default());
+        assert!(added > 0);
+        assert_eq!(state.edge_count(), 2 + added);
+        state.sync_crossings();
+        assert_eq!(state.crossing_count(), 0);
+        assert_eq!(state.edges[..2], [(0, 1), (2, 3)]);
+        assert_eq!(state.triangulate(&EdgeProps::default()), 0);
+
+        assert!(state.undo());
+        assert_eq!(state.edge_count(), 2);
+    }
+
+    #[test]
+    fn test_randomize_planar_edges() {
+        let anchors = (0..50)
+            .map(|i| Anchor::new(Pos::new((i % 7) as f32 * 40.0 + (i * i % 11) as f32, (i / 7) as f32 * 35.0)))
+            .collect();
+        let mut state = InteractionState::with_anchors(anchors);
+        state.randomize_planar_edges();
+        assert_eq!(state.edge_count(), state.anchor_count());
+        state.sync_crossings();
+        assert_eq!(state.crossing_count(), 0);
+    }
+
     #[test]
     fn test_randomize_edges_distribution() {
         let mut state = setup_test_state();
@@ -909,7 +3286,7 @@ mod interaction_tests {
         for _ in 0..100 {
             state.randomize_edges();
             assert!(state.edge_count() > 0); // Should always create some edges
-            for (from, to) in &state.edges {
+            for &Edge { from, to, .. } in &state.edges {
                 let edge_index = from * 2 + to;
                 edge_counts[edge_index] += 1;
             }
@@ -919,93 +3296,786 @@ mod interaction_tests {
     }
 }
 
-fn update(app: &App, m: &mut Model, update: Update) {
-    if m.egui.is_none() {
-        let window_id = unsafe { WINDOW_ID.unwrap() };
-        let window = app.window(window_id).unwrap();
-        m.egui = Some(Egui::from_window(&window));
-    }
+fn update(app: &App, m: &mut Model, update: Update) {
+    if m.egui.is_none() {
+        let window_id = unsafe { WINDOW_ID.unwrap() };
+        let window = app.window(window_id).unwrap();
+        m.egui = Some(Egui::from_window(&window));
+    }
+
+    // Move a playing voice to another device when its device disappears
+    if m.audio.as_ref().is_some_and(|handle| handle.is_lost()) {
+        m.audio = m.output.reselect(&m.controls);
+    }
+
+    let intensity = m.conductor.intensity(
+        m.interaction.edge_count(),
+        m.interaction.blocked_attempts,
+        m.interaction.completion(),
+        update.since_last.as_secs_f32(),
+    );
+    if let Ok(mut music) = m.controls.music.lock() {
+        music.intensity = intensity;
+    }
+
+    match m.audio.as_mut() {
+        Some(handle) => m.analyser.feed(&mut handle.tap),
+        None => m.analyser.silence(),
+    }
+
+    for command in m.input.tick(update.since_last.as_secs_f32()) {
+        execute(app, m, command);
+    }
+    if let Some(game) = m.game.as_mut() {
+        game.tick(update.since_last.as_secs_f32());
+    }
+    m.interaction.free_moves = m.puzzle.is_some();
+    // A file picked on the web arrives some frames after Import was clicked
+    if let Some(picked) = m.import.as_ref().and_then(files::Pick::take) {
+        m.import = None;
+        m.status = Some(if m.may_edit() {
+            import_board(&mut m.interaction, &mut m.puzzle, picked, m.exchange_format)
+        } else {
+            "Importing the board failed: a puzzle or game is running".to_string()
+        });
+    }
+    // Edits and board swaps since the last frame must not be judged by stale crossings
+    m.interaction.sync_crossings();
+    if m.layout_origins.is_some() {
+        // Laying out a puzzle would solve it
+        if !m.may_edit() || m.interaction.layout_step(&mut m.layout) < layout::TOLERANCE {
+            stop_layout(&mut m.interaction, &mut m.layout_origins);
+            m.layout_status = Some("Layout settled".to_string());
+        }
+    }
+    if let Some(puzzle) = m.puzzle.as_mut() {
+        puzzle.update(update.since_last.as_secs_f32(), m.interaction.moves, m.interaction.crossing_count());
+    }
+    // A hint only fits the board it was searched on
+    if m.solver.as_ref().is_some_and(|solver| solver.is_stale(&m.interaction.anchors, &m.interaction.edges)) {
+        m.solver = None;
+    }
+    if let Some(solver) = m.solver.as_mut() {
+        solver.search(solver::FRAME_BUDGET);
+    }
+    m.interaction.zoom = m.camera.zoom;
+    m.interaction.pointer_scale = match m.input.device {
+        Device::Mouse => 1.0,
+        Device::Touch => input::TOUCH_TOLERANCE_SCALE,
+    };
+
+    // Preview a move by placing the anchor under the mouse
+    if m.interaction.moving_anchor.is_some() {
+        m.interaction.update_move(m.pointer());
+    }
+    if m.interaction.moving_selection.is_some() {
+        m.interaction.update_selection_move(m.pointer());
+    }
+    m.interaction.extend_selection_path(m.pointer());
+
+    if m.recorder.is_recording() {
+        if let Some(handle) = m.audio.as_mut() {
+            m.recorder.drain(&mut handle.record);
+        }
+    }
+
+    // Change the frequency of the sine wave over time.
+    if m.audio.is_some() {
+        let mouse_pos = m.pointer();
+        let drag_length = m.interaction.dragged_anchor.map(|idx| {
+            m.interaction.anchors[idx].pos.distance(&mouse_pos)
+        });
+
+        if m.last_drag_length.is_some() && drag_length.is_some() {
+            let mut freq = drag_length.unwrap() / 3.0 + 100.0;
+
+            if m.interaction.is_dragging_intersecting(mouse_pos) {
+                freq /= random_range(0.25, 0.75);
+            }
+
+            // Move value closer to target freq, rather than just setting it
+            let old_freq = m.controls.freq.borrow_mut().lock().unwrap().value;
+            m.controls.freq.borrow_mut().lock().unwrap().value += (freq - old_freq) / 10.0;
+
+            // Place the voice at the middle of the edge being drawn
+            if let Some(dragged) = m.interaction.dragged_anchor {
+                let source = m.camera.to_screen((m.interaction.anchors[dragged].pos + mouse_pos) * 0.5);
+                let window = app.window_rect();
+                let (pan, gain) = audio::placement(source.x, source.y, window.w() / 2.0, window.h() / 2.0);
+                if let Ok(mut voice) = m.controls.freq.lock() {
+                    voice.pan = pan;
+                    voice.gain = gain;
+                }
+            }
+
+            m.last_drag_length = drag_length;
+        }
+    }
+
+    // Edges added from the UI are claimed like the ones drawn
+    let new_edge = m.new_edge_props();
+    let editable = m.may_edit();
+    let movable = m.may_move();
+    if let Some(egui) = m.egui.as_mut() {
+        egui.set_elapsed_time(update.since_start);
+        let ctx = egui.begin_frame();
+        egui::Window::new("Settings").show(&ctx, |ui| {
+            // Add volume slider
+
+            // Convert between f32 and u32 bits
+            let current_vol = f32::from_bits(VOLUME.load(Ordering::Relaxed));
+            let mut vol = current_vol;
+            
+            if ui.add(egui::Slider::new(&mut vol, 0.0..=1.0).text("Volume")).changed() {
+                VOLUME.store(vol.to_bits(), Ordering::Relaxed);
+                if let Ok(mut freq) = m.controls.freq.lock() {
+                    freq.volume = vol;
+                }
+            }
+
+            if let Ok(mut music) = m.controls.music.lock() {
+                ui.checkbox(&mut music.enabled, "Music");
+                ui.add_enabled(music.enabled, egui::Slider::new(&mut music.level, 0.0..=1.0).text("Music volume"));
+            }
+            ui.label("Board:");
+            ui.horizontal(|ui| {
+                if ui.add_enabled(editable, egui::Button::new("New")).clicked() {
+                    m.interaction.reset(random_anchors());
+                    m.puzzle = None;
+                }
+                if ui.button("Save").clicked() {
+                    m.status = Some(save_board(&m.interaction));
+                }
+                if ui.add_enabled(editable, egui::Button::new("Load")).clicked() {
+                    m.status = Some(load_board(&mut m.interaction, &mut m.puzzle));
+                }
+            });
+            egui::CollapsingHeader::new("Import / export").show(ui, |ui| {
+                let before = m.exchange_format;
+                egui::ComboBox::from_label("Format")
+                    .selected_text(m.exchange_format.label())
+                    .show_ui(ui, |ui| {
+                        for format in Format::ALL {
+                            ui.selectable_value(&mut m.exchange_format, format, format.label());
+                        }
+                    });
+                if m.exchange_format != before {
+                    let stem = m.exchange_file.rsplit_once('.').map_or(m.exchange_file.as_str(), |(stem, _)| stem);
+                    m.exchange_file = format!("{}.{}", stem, m.exchange_format.extension());
+                }
+                ui.horizontal(|ui| {
+                    ui.label("File:");
+                    if ui.text_edit_singleline(&mut m.exchange_file).changed() {
+                        m.exchange_format = Format::of_file(&m.exchange_file).unwrap_or(m.exchange_format);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    // On the web, Import opens a file chooser instead of reading the named file
+                    if ui.add_enabled(editable, egui::Button::new("Import")).clicked() {
+                        m.import = Some(files::pick(&m.exchange_file, &Format::file_types()));
+                    }
+                    if ui.button("Export").clicked() {
+                        m.status = Some(export_board(&m.interaction, &m.exchange_file, m.exchange_format));
+                    }
+                });
+            });
+            if let Some(status) = &m.status {
+                ui.label(status);
+            }
+
+            // Randomize connections button
+            ui.label("Randomize connections:");
+            ui.add_enabled_ui(editable, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Randomize").clicked() {
+                        m.interaction.randomize_edges();
+                    }
+                    if ui.button("Randomize (planar)").clicked() {
+                        m.interaction.randomize_planar_edges();
+                    }
+                    if ui.button("Triangulate").clicked() {
+                        m.status = Some(format!("Added {} edges", m.interaction.triangulate(&new_edge)));
+                    }
+                });
+            });
+
+            ui.label("Clear connections:");
+            if ui.add_enabled(editable, egui::Button::new("Clear")).clicked() {
+                m.interaction.clear_edges();
+            }
+
+            ui.label("Wiggle anchors:");
+            ui.checkbox(&mut m.wiggle_anchors, "Wiggle");
+
+            ui.label("Tool:");
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut m.tool, Tool::Place, "Place");
+                ui.radio_value(&mut m.tool, Tool::Connect, "Connect");
+                ui.radio_value(&mut m.tool, Tool::RectSelect, "Rectangle");
+                ui.radio_value(&mut m.tool, Tool::Lasso, "Lasso");
+            });
+
+            ui.collapsing("New edges", |ui| {
+                edge_props_ui(ui, "template", &mut m.edge_template);
+            });
+
+            ui.label("Snapping:");
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut m.interaction.snap.grid, Grid::Off, "No grid");
+                ui.radio_value(&mut m.interaction.snap.grid, Grid::Square, "Square");
+                ui.radio_value(&mut m.interaction.snap.grid, Grid::Hex, "Hex");
+            });
+            ui.add_enabled(
+                m.interaction.snap.grid != Grid::Off,
+                egui::Slider::new(&mut m.interaction.snap.spacing, 10.0..=100.0).text("Grid spacing"),
+            );
+            ui.checkbox(&mut m.interaction.snap.align, "Align with anchors");
+            ui.add(egui::Slider::new(&mut m.interaction.min_spacing, 0.0..=100.0).text("Minimum anchor spacing"));
+
+            ui.horizontal(|ui| {
+                ui.label(format!("Zoom: {:.0}% (wheel, middle-drag pans)", m.camera.zoom * 100.0));
+                if ui.button("Reset view").clicked() {
+                    m.camera = Camera::default();
+                }
+            });
+
+            let selected = m.interaction.selection.len();
+            ui.label(format!("Selection: {} anchors, {} edges", selected, m.interaction.selected_edges().len()));
+            ui.horizontal(|ui| {
+                if ui.add_enabled(editable && selected > 0, egui::Button::new("Delete")).clicked() {
+                    m.interaction.delete_selection();
+                }
+                if ui.add_enabled(selected > 0, egui::Button::new("Copy")).clicked() {
+                    m.interaction.copy_selection();
+                }
+                // Pasting from the button offsets the copy so it does not cover the original
+                let can_paste = !m.interaction.clipboard.anchors.is_empty();
+                if ui.add_enabled(editable && can_paste, egui::Button::new("Paste")).clicked() {
+                    m.interaction.paste(Pos::new(30.0, -30.0));
+                }
+                if ui.add_enabled(editable && selected > 1, egui::Button::new("Connect all")).clicked() {
+                    m.interaction.connect_selection(&new_edge);
+                }
+            });
+
+            ui.label("Edits (click inspects, Shift-drag or right-drag moves, right-click deletes):");
+            ui.horizontal(|ui| {
+                if ui.add_enabled(movable && m.interaction.history.can_undo(), egui::Button::new("Undo")).clicked() {
+                    m.interaction.undo();
+                }
+                if ui.add_enabled(movable && m.interaction.history.can_redo(), egui::Button::new("Redo")).clicked() {
+                    m.interaction.redo();
+                }
+            });
+
+            ui.label("Audio overlay:");
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut m.overlay, Overlay::None, "None");
+                ui.radio_value(&mut m.overlay, Overlay::Oscilloscope, "Oscilloscope");
+                ui.radio_value(&mut m.overlay, Overlay::Spectrum, "Spectrum");
+            });
+
+            ui.separator();
+            m.output.list();
+            ui.label(format!("Audio: {}", m.output.status));
+
+            let previous = m.output.config.clone();
+            let device_text = m.output.config.device.clone().unwrap_or_else(|| "Default".to_string());
+            egui::ComboBox::from_label("Output device")
+                .selected_text(device_text)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut m.output.config.device, None, "Default");
+                    for device in &m.output.devices {
+                        ui.selectable_value(&mut m.output.config.device, Some(device.clone()), device);
+                    }
+                });
+
+            let rate_text = m.output.config.sample_rate
+                .map_or_else(|| "Default".to_string(), |rate| format!("{} Hz", rate));
+            egui::ComboBox::from_label("Sample rate")
+                .selected_text(rate_text)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut m.output.config.sample_rate, None, "Default");
+                    for rate in &m.output.sample_rates {
+                        ui.selectable_value(&mut m.output.config.sample_rate, Some(*rate), format!("{} Hz", rate));
+                    }
+                });
+
+            if ui.button("Refresh devices").clicked() {
+                m.output.refresh();
+            }
+
+            ui.label("Recording:");
+            if m.recorder.is_recording() {
+                ui.label(format!("Recording {:.1} s", m.recorder.duration()));
+                if ui.button("Stop recording").clicked() {
+                    m.recording_status = Some(stop_recording(&mut m.recorder, m.audio.as_mut()));
+                }
+            } else if ui.button("Start recording").clicked() {
+                if m.audio.is_none() {
+                    m.audio = m.output.open(&m.controls);
+                }
+                match m.audio.as_mut() {
+                    Some(handle) => {
+                        m.recorder.start(&mut handle.record, handle.channels, handle.sample_rate);
+                        m.recording_status = None;
+                    }
+                    None => m.recording_status = Some(format!("Cannot record: audio {}", m.output.status)),
+                }
+            }
+            if let Some(status) = &m.recording_status {
+                ui.label(status);
+            }
 
-    // Change the frequency of the sine wave over time.
-    if m.audio.is_some() {
-        let mouse_pos = Pos::new(app.mouse.x, app.mouse.y);
-        let drag_length = m.interaction.dragged_anchor.map(|idx| {
-            m.interaction.anchors[idx].pos.distance(&mouse_pos)
+            if m.output.config != previous {
+                if m.output.config.device != previous.device {
+                    m.output.select_device(m.output.config.device.clone());
+                }
+                // Hot-swap the running stream onto the new selection
+                if m.recorder.is_recording() {
+                    m.recording_status = Some(stop_recording(&mut m.recorder, m.audio.as_mut()));
+                }
+                // Close the old stream first, some devices only allow one at a time
+                drop(m.audio.take());
+                m.audio = m.output.open(&m.controls);
+            }
         });
 
-        if m.last_drag_length.is_some() && drag_length.is_some() {
-            let mut freq = drag_length.unwrap() / 3.0 + 100.0;
+        // A single selected anchor comes first, then the anchor or edge pinned
+        // by clicking it, then whatever is under the pointer. Indices go stale
+        // when anchors or edges are removed.
+        m.inspected = m.inspected.filter(|hit| m.interaction.exists(*hit));
+        let selected = m.interaction.selection.iter().next().filter(|_| m.interaction.selection.len() == 1);
+        let pointer = m.camera.to_world(m.input.pointer);
+        let hovered = if ctx.is_pointer_over_area() { None } else { m.interaction.hit_test(pointer) };
+        let subject = selected.map(Hit::Anchor).or(m.inspected).or(hovered);
+        egui::Window::new("Inspector").show(&ctx, |ui| {
+            match subject {
+                Some(Hit::Anchor(index)) => {
+                    anchor_inspector_ui(ui, &mut m.interaction, index, editable, movable, &mut m.inspected, &mut m.status);
+                    ui.horizontal(|ui| {
+                        if ui.button("Path from here").clicked() {
+                            m.path_ends.0 = Some(index);
+                            m.highlight = Highlight::Path;
+                        }
+                        if ui.button("Path to here").clicked() {
+                            m.path_ends.1 = Some(index);
+                            m.highlight = Highlight::Path;
+                        }
+                    });
+                }
+                Some(Hit::Edge(index)) => {
+                    edge_inspector_ui(ui, &mut m.interaction, index, editable, &mut m.inspected, &mut m.edge_template)
+                }
+                None if !m.interaction.selection.is_empty() => {
+                    ui.label(format!(
+                        "{} anchors and {} edges selected",
+                        m.interaction.selection.len(),
+                        m.interaction.selected_edges().len()
+                    ));
+                }
+                None => {
+                    ui.label("Hover or click an anchor or edge to inspect it");
+                }
+            }
 
-            if m.interaction.is_dragging_intersecting(mouse_pos) {
-                freq /= random_range(0.25, 0.75);
+            ui.separator();
+            egui::Grid::new("board summary").show(ui, |ui| {
+                let interaction = &m.interaction;
+                ui.label("Anchors");
+                ui.label(interaction.anchor_count().to_string());
+                ui.end_row();
+                ui.label("Edges");
+                ui.label(interaction.edge_count().to_string());
+                ui.end_row();
+                ui.label("Crossings");
+                ui.label(interaction.crossing_count().to_string());
+                ui.end_row();
+                ui.label("Components");
+                ui.label(interaction.component_count().to_string());
+                ui.end_row();
+                ui.label("Faces");
+                ui.label(interaction.face_count().map_or_else(|| "none while edges cross".to_string(), |faces| faces.to_string()));
+                ui.end_row();
+            });
+            if let Some(edge) = m.interaction.most_tangled_edge() {
+                let (from, to) = m.interaction.edges[edge].ends();
+                let crossings = m.interaction.edge_crossings(edge);
+                if ui.button(format!("Most tangled: edge {} to {}, {} crossings", from, to, crossings)).clicked() {
+                    m.inspected = Some(Hit::Edge(edge));
+                }
             }
 
-            // Move value closer to target freq, rather than just setting it
-            let old_freq = m.freq.borrow_mut().lock().unwrap().value;
-            m.freq.borrow_mut().lock().unwrap().value += (freq - old_freq) / 10.0;
+            ui.collapsing("Analysis", |ui| {
+                let graph = m.interaction.graph();
+                let degrees = graph.degree_stats();
+                ui.label(format!(
+                    "Degrees from {} to {}, {:.2} on average, {} anchors without edges",
+                    degrees.min, degrees.max, degrees.mean, degrees.isolated
+                ));
+                let cuts = graph.cut_sets();
+                ui.label(format!(
+                    "{} bridges, {} articulation points",
+                    cuts.bridges.len(),
+                    cuts.articulation_points.len()
+                ));
+                ui.label(if graph.find_cycle().is_some() { "Has cycles" } else { "Has no cycles" });
 
-            m.last_drag_length = drag_length;
-        }
-    }
+                ui.label("Show:");
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut m.highlight, Highlight::None, "Nothing");
+                    ui.radio_value(&mut m.highlight, Highlight::Components, "Components");
+                    ui.radio_value(&mut m.highlight, Highlight::Cuts, "Bridges");
+                    ui.radio_value(&mut m.highlight, Highlight::Cycle, "A cycle");
+                    ui.radio_value(&mut m.highlight, Highlight::Path, "Path");
+                });
 
-    if let Some(egui) = m.egui.as_mut() {
-        egui.set_elapsed_time(update.since_start);
-        let ctx = egui.begin_frame();
-        egui::Window::new("Settings").show(&ctx, |ui| {
-            // Add volume slider
+                egui::ComboBox::from_label("Path metric")
+                    .selected_text(m.metric.label())
+                    .show_ui(ui, |ui| {
+                        for metric in Metric::ALL {
+                            ui.selectable_value(&mut m.metric, metric, metric.label());
+                        }
+                    });
+                let name = |end: Option<usize>| end.map_or_else(|| "?".to_string(), |anchor| anchor.to_string());
+                let path = picked_path(&m.interaction, m.path_ends, m.metric);
+                ui.label(match (&path, m.path_ends) {
+                    (Some(path), _) => format!("Path over {} edges, length {:.1}", path.edges.len(), path.length),
+                    (None, (Some(_), Some(_))) => "The picked anchors are not connected".to_string(),
+                    (None, (from, to)) => format!("Pick anchors in the inspector: from {} to {}", name(from), name(to)),
+                });
+                if ui.button("Clear path").clicked() {
+                    m.path_ends = (None, None);
+                }
+            });
+        });
 
-            // Convert between f32 and u32 bits
-            let current_vol = f32::from_bits(VOLUME.load(Ordering::Relaxed));
-            let mut vol = current_vol;
-            
-            if ui.add(egui::Slider::new(&mut vol, 0.0..=1.0).text("Volume")).changed() {
-                VOLUME.store(vol.to_bits(), Ordering::Relaxed);
-                if let Ok(mut freq) = m.freq.lock() {
-                    freq.volume = vol;
+        egui::Window::new("Game").default_open(false).show(&ctx, |ui| match m.game.as_mut() {
+            Some(game) => {
+                ui.label(game.rules.goal.label());
+                match &game.winner {
+                    Some(win) => {
+                        ui.colored_label(player_color32(win.player), format!("Player {} wins!", win.player + 1));
+                        ui.label(format!("The winning path has {} edges", win.path.len()));
+                        if ui.button("Replay").clicked() {
+                            game.restart_replay();
+                        }
+                    }
+                    None => {
+                        ui.colored_label(player_color32(game.turn), format!("Player {} to move", game.turn + 1));
+                    }
+                }
+                if ui.button("End game").clicked() {
+                    m.game = None;
                 }
             }
-            // Randomize connections button
-            ui.label("Randomize connections:");
-            if ui.button("Randomize").clicked() {
-                m.interaction.randomize_edges();
+            None => {
+                egui::ComboBox::from_label("Goal")
+                    .selected_text(m.rules.goal.label())
+                    .show_ui(ui, |ui| {
+                        for goal in Goal::ALL {
+                            ui.selectable_value(&mut m.rules.goal, goal, goal.label());
+                        }
+                    });
+                ui.add(egui::Slider::new(&mut m.rules.players, 2..=m.rules.goal.max_players()).text("Players"));
+                if m.rules.goal == Goal::Bases {
+                    ui.label("Each player connects the bases they own.");
+                }
+                if ui.button("Start game").clicked() {
+                    m.game = Some(Game::new(m.rules));
+                    m.status = Some("Player 1 to move".to_string());
+                }
             }
+        });
 
-            ui.label("Clear connections:");
-            if ui.button("Clear").clicked() {
-                m.interaction.clear_edges();
+        egui::Window::new("Puzzle").default_open(false).show(&ctx, |ui| {
+            match &m.puzzle {
+                Some(puzzle) => {
+                    ui.label(format!("{} untangle puzzle", puzzle.difficulty.label()));
+                    ui.label(format!("Crossings left: {}", m.interaction.crossing_count()));
+                    ui.label(format!("Moves: {}", puzzle.moves));
+                    ui.label(format!("Time: {}", puzzle::clock(puzzle.elapsed)));
+                }
+                None => {
+                    ui.label("Move the anchors until no edges cross.");
+                }
+            }
+            egui::ComboBox::from_label("Difficulty")
+                .selected_text(format!("{} ({} anchors)", m.difficulty.label(), m.difficulty.anchor_count()))
+                .show_ui(ui, |ui| {
+                    for difficulty in Difficulty::ALL {
+                        let text = format!("{} ({} anchors)", difficulty.label(), difficulty.anchor_count());
+                        ui.selectable_value(&mut m.difficulty, difficulty, text);
+                    }
+                });
+            ui.horizontal(|ui| {
+                if ui.button("New puzzle").clicked() {
+                    start_puzzle(&mut m.interaction, &mut m.puzzle, m.difficulty);
+                    m.game = None;
+                    m.challenge = None;
+                    m.camera = Camera::default();
+                }
+                if m.puzzle.is_some() && ui.button("Give up").clicked() {
+                    m.puzzle = None;
+                }
+            });
+            ui.separator();
+            egui::ComboBox::from_label("Hint for")
+                .selected_text(m.objective.label())
+                .show_ui(ui, |ui| {
+                    for objective in Objective::ALL {
+                        ui.selectable_value(&mut m.objective, objective, objective.label());
+                    }
+                });
+            if ui.button("Hint").clicked() {
+                // Connections are suggested to the player to move, or the first one outside a game
+                let (goal, player) = m.game.as_ref().map_or((m.rules.goal, 0), |game| (game.rules.goal, game.turn));
+                let objective = m.objective.for_player(goal, player);
+                m.solver = Some(Solver::new(objective, &m.interaction.anchors, &m.interaction.edges));
             }
+            if let Some(solver) = &m.solver {
+                let steps = solver.plan.len();
+                ui.label(match (solver.hint(), solver.status) {
+                    (None, solver::Status::Searching) => "Looking for a hint...".to_string(),
+                    (None, solver::Status::Solved) => "Nothing left to do".to_string(),
+                    (None, solver::Status::Stuck) => "No hint found".to_string(),
+                    (Some(Hint::Move { .. }), _) => "Move the marked anchor to the ring".to_string(),
+                    (Some(Hint::Edge { .. }), _) => "Draw the marked edge".to_string(),
+                });
+                if steps > 0 {
+                    ui.label(match solver.status {
+                        solver::Status::Searching => format!("{} steps found so far", steps),
+                        solver::Status::Solved => format!("Solved in {} steps", steps),
+                        solver::Status::Stuck => format!("{} steps get closer, but no solution was found", steps),
+                    });
+                }
+            }
+        });
 
-            ui.label("Wiggle anchors:");
-            ui.checkbox(&mut m.wiggle_anchors, "Wiggle");
+        // Victory screen
+        if let Some(puzzle) = m.puzzle.clone().filter(|puzzle| puzzle.solved) {
+            egui::Window::new("Untangled!")
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .collapsible(false)
+                .resizable(false)
+                .show(&ctx, |ui| {
+                    ui.heading(format!("{} puzzle solved", puzzle.difficulty.label()));
+                    ui.label(format!("{} moves in {}", puzzle.moves, puzzle::clock(puzzle.elapsed)));
+                    ui.horizontal(|ui| {
+                        if ui.button("Next puzzle").clicked() {
+                            start_puzzle(&mut m.interaction, &mut m.puzzle, puzzle.difficulty);
+                        }
+                        if ui.button("Close").clicked() {
+                            m.puzzle = None;
+                        }
+                    });
+                });
+        }
+
+        egui::Window::new("Spanning tree").default_open(false).show(&ctx, |ui| {
+            match m.challenge.as_mut() {
+                Some(challenge) => {
+                    let length = spanning::total_length(&m.interaction.anchors, &m.interaction.edges);
+                    ui.label(format!("Target: {:.0}", challenge.optimal_length));
+                    ui.label(format!("Your length: {:.0}", length));
+                    if challenge.is_connected(&m.interaction.anchors, &m.interaction.edges) {
+                        ui.label(format!("Connected at {:.0}% of the optimum", challenge.score(length) * 100.0));
+                    } else {
+                        ui.label(format!("Connect all {} terminals", challenge.terminals.len()));
+                    }
+                    ui.checkbox(&mut challenge.revealed, "Show the optimum");
+                    if ui.button("End challenge").clicked() {
+                        m.challenge = None;
+                    }
+                }
+                None => {
+                    ui.label("Connect the anchors with the shortest total length, without crossings.");
+                    egui::ComboBox::from_label("Terminals")
+                        .selected_text(m.terminals.label())
+                        .show_ui(ui, |ui| {
+                            for terminals in Terminals::ALL {
+                                ui.selectable_value(&mut m.terminals, terminals, terminals.label());
+                            }
+                        });
+                    if ui.button("Start challenge").clicked() {
+                        m.challenge = start_challenge(&mut m.interaction, m.terminals);
+                        if m.challenge.is_some() {
+                            m.game = None;
+                            m.puzzle = None;
+                        } else {
+                            m.status = Some("A challenge needs at least two terminals".to_string());
+                        }
+                    }
+                }
+            }
+        });
+
+        egui::Window::new("Layout").default_open(false).show(&ctx, |ui| {
+            // Laying out a puzzle would solve it
+            ui.add_enabled_ui(editable, |ui| {
+                egui::ComboBox::from_label("Algorithm")
+                    .selected_text(m.layout.options.algorithm.label())
+                    .show_ui(ui, |ui| {
+                        for algorithm in Algorithm::ALL {
+                            ui.selectable_value(&mut m.layout.options.algorithm, algorithm, algorithm.label());
+                        }
+                    });
+                ui.checkbox(&mut m.layout.options.pin_selection, "Pin selected anchors");
+                ui.checkbox(&mut m.layout.options.keep_inside, "Keep inside the board");
+                ui.checkbox(&mut m.layout.options.planar, "Never add crossings");
+                ui.horizontal(|ui| {
+                    if m.layout_origins.is_some() {
+                        if ui.button("Stop").clicked() {
+                            stop_layout(&mut m.interaction, &mut m.layout_origins);
+                            m.layout_status = Some("Layout stopped".to_string());
+                        }
+                    } else if ui.button("Animate").clicked() {
+                        m.layout.restart();
+                        m.layout_origins = Some(m.interaction.anchors.iter().map(|anchor| anchor.pos).collect());
+                        m.layout_status = None;
+                    }
+                    if ui.button("Run to convergence").clicked() {
+                        stop_layout(&mut m.interaction, &mut m.layout_origins);
+                        let steps = m.interaction.run_layout(&mut m.layout);
+                        m.layout_status = Some(if steps < layout::MAX_STEPS {
+                            format!("Settled after {} steps", steps)
+                        } else {
+                            format!("Stopped after {} steps", steps)
+                        });
+                    }
+                });
+            });
+            if let Some(status) = &m.layout_status {
+                ui.label(status);
+            }
         });
+
+        egui::Window::new("Key bindings").default_open(false).show(&ctx, |ui| {
+            egui::Grid::new("bindings").striped(true).show(ui, |ui| {
+                for action in Action::ALL {
+                    ui.label(action.label());
+                    let keys: Vec<String> = m.keymap.bindings_of(action).map(|binding| binding.to_string()).collect();
+                    ui.label(if keys.is_empty() { "unbound".to_string() } else { keys.join(", ") });
+                    let waiting = m.rebinding == Some(action);
+                    if ui.button(if waiting { "Press a key..." } else { "Rebind" }).clicked() {
+                        m.rebinding = if waiting { None } else { Some(action) };
+                    }
+                    ui.end_row();
+                }
+            });
+
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    m.status = Some(match m.keymap.to_yaml().and_then(|yaml| files::store(KEYMAP_FILE, &yaml)) {
+                        Ok(location) => format!("Saved bindings to {}", location),
+                        Err(err) => format!("Saving the bindings failed: {}", err),
+                    });
+                }
+                if ui.button("Load").clicked() {
+                    m.status = Some(match files::load(KEYMAP_FILE).and_then(|yaml| Keymap::from_yaml(&yaml)) {
+                        Ok(keymap) => {
+                            m.keymap = keymap;
+                            "Loaded bindings".to_string()
+                        }
+                        Err(err) => format!("Loading the bindings failed: {}", err),
+                    });
+                }
+                if ui.button("Defaults").clicked() {
+                    m.keymap = Keymap::default();
+                }
+            });
+            if let Some(status) = &m.status {
+                ui.label(status);
+            }
+        });
+
+        // An inspector edit ends when its widget is let go, so the next one undoes on its own
+        if !ctx.is_using_pointer() && !ctx.wants_keyboard_input() {
+            m.interaction.history.seal();
+        }
     }
 
-    if m.wiggle_anchors {
+    // Wiggling moves anchors too, which a game does not allow
+    if m.wiggle_anchors && m.may_move() {
         for anchor in &mut m.interaction.anchors {
             anchor.pos.x += random_range(-1.0, 1.0);
             anchor.pos.y += random_range(-1.0, 1.0);
         }
     }
+
+    // Edits since the win may have removed or renumbered the edges of the winning path
+    if let Some(game) = m.game.as_mut() {
+        game.recheck(&m.interaction.anchors, &m.interaction.edges);
+    }
+    // Anchor edits since the challenge started change the terminals and the optimum
+    if m.challenge.as_mut().is_some_and(|challenge| !challenge.recheck(&m.interaction.anchors)) {
+        m.challenge = None;
+        m.status = Some("The challenge ended with fewer than two terminals left".to_string());
+    }
+    // The crossings drawn and shown until the next update
+    m.interaction.sync_crossings();
 }
 
 fn view(app: &App, m: &Model, frame: Frame) {
     let main_color = Rgb::new(0x0du8, 0x11u8, 0x17u8);
     let sec_color = Rgb::new(0xf2u8, 0xeeu8, 0xe8u8);
     let tri_color = INDIGO;
-    let draw = app.draw();
-    draw.background().color(main_color);
+    let screen = app.draw();
+    screen.background().color(main_color);
+    // The board is drawn in world positions, the overlay in screen positions
+    let draw = m.camera.transform(&screen);
 
-    // Draw anchors
-    for anchor in &m.interaction.anchors {
+    // Draw the snapping grid
+    let window = app.window_rect();
+    let world_min = m.camera.to_world(Pos::new(window.left(), window.bottom()));
+    let world_max = m.camera.to_world(Pos::new(window.right(), window.top()));
+    for point in m.interaction.snap.points(world_min, world_max) {
         draw.ellipse()
-            .x_y(anchor.pos.x, anchor.pos.y)
-            .w_h(5.0, 5.0)
-            .color(WHEAT);
+            .x_y(point.x, point.y)
+            .w_h(2.0, 2.0)
+            .color(rgba(1.0, 1.0, 1.0, 0.15));
+    }
+
+    // Draw alignment guides while placing or moving, and where a new anchor would go
+    let mouse_pos = m.pointer();
+    let guide_color = rgba(0.3, 0.7, 1.0, 0.4);
+    let placing = (m.tool == Tool::Place && m.interaction.moving_anchor.is_none()).then_some(mouse_pos);
+    if m.interaction.snap.is_enabled() {
+        for guide in m.interaction.snap_guides(placing) {
+            let (start, end) = match guide {
+                Guide::Vertical(x) => (pt2(x, world_min.y), pt2(x, world_max.y)),
+                Guide::Horizontal(y) => (pt2(world_min.x, y), pt2(world_max.x, y)),
+            };
+            draw.line().start(start).end(end).weight(1.0).color(guide_color);
+        }
+    }
+    // The preview turns red where the minimum spacing forbids placing
+    if let Some(pos) = placing {
+        let (snapped, _) = m.interaction.snap_position(pos, None);
+        let color = if m.interaction.can_place(snapped) { guide_color } else { rgba(1.0, 0.2, 0.2, 0.6) };
+        draw.ellipse()
+            .x_y(snapped.x, snapped.y)
+            .w_h(8.0, 8.0)
+            .no_fill()
+            .stroke_weight(1.0)
+            .stroke(color);
     }
 
-    // Draw dragged anchor red
+    // Draw anchors, shaped by their kind and colored by their owner
+    for anchor in &m.interaction.anchors {
+        draw_anchor(&draw, anchor);
+    }
+
+    let amplitude = m.analyser.amplitude();
+
+    // Draw dragged anchor red, glowing with the sound
     if let Some(dragged_anchor) = m.interaction.dragged_anchor {
         let anchor = &m.interaction.anchors[dragged_anchor];
+        let glow = 10.0 + amplitude * GLOW_GAIN;
+        draw.ellipse()
+            .x_y(anchor.pos.x, anchor.pos.y)
+            .w_h(glow, glow)
+            .color(rgba(1.0, 0.0, 0.0, 0.3));
         draw.ellipse()
             .x_y(anchor.pos.x, anchor.pos.y)
             .w_h(10.0, 10.0)
@@ -1014,7 +4084,11 @@ fn view(app: &App, m: &Model, frame: Frame) {
 
     // Draw uncompleted Line
     if let Some(dragged_anchor) = m.interaction.dragged_anchor {
-        let mouse_pos = Pos::new(app.mouse.x, app.mouse.y);
+        // An edge started from the keyboard points at the focused anchor
+        let mouse_pos = match (m.interaction.keyboard_link, m.interaction.focused) {
+            (true, Some(focused)) => m.interaction.anchors[focused].pos,
+            _ => m.pointer(),
+        };
         let line = LineSegment::new(
             m.interaction.anchors[dragged_anchor].pos,
             mouse_pos,
@@ -1031,41 +4105,564 @@ fn view(app: &App, m: &Model, frame: Frame) {
         } else {
             MIDNIGHTBLUE
         };
-        line.draw_with_outline(&draw, color_inner, color_outer);
+        line.draw_with_jitter(&draw, color_inner, color_outer, 2.5 + amplitude * JITTER_GAIN);
+    }
+
+    // Draw the keyboard focus
+    if let Some(focused) = m.interaction.focused {
+        let anchor = &m.interaction.anchors[focused];
+        draw.ellipse()
+            .x_y(anchor.pos.x, anchor.pos.y)
+            .w_h(18.0, 18.0)
+            .no_fill()
+            .stroke_weight(2.0)
+            .stroke(YELLOW);
     }
 
-    // Draw Edges
-    for edge in &m.interaction.edges {
-        let anchor_start = &m.interaction.anchors[edge.0];
-        let anchor_end = &m.interaction.anchors[edge.1];
+    // Draw the anchor being moved and highlight its edges that would cross
+    if let Some(AnchorMove { index, .. }) = m.interaction.moving_anchor {
+        let anchor = &m.interaction.anchors[index];
+        draw.ellipse()
+            .x_y(anchor.pos.x, anchor.pos.y)
+            .w_h(10.0, 10.0)
+            .color(ORANGE);
 
-        let line = LineSegment::new(anchor_start.pos, anchor_end.pos);
+        for edge in m.interaction.move_crossings() {
+            let line = m.interaction.edge_line(edge);
+            draw.line()
+                .start(line.start.into())
+                .end(line.end.into())
+                .weight(8.0)
+                .color(rgba(1.0, 0.5, 0.0, 0.4));
+        }
+    }
 
-        let any_line_intersecting = m.interaction.edges.iter().any(|(a, b)| {
-            let edge = LineSegment::new(m.interaction.anchors[*a].pos, m.interaction.anchors[*b].pos);
-            edge.line_segments_intersect(&line)
-        });
+    // Draw Edges, styled by their kind and colored by their owner
+    for (index, edge) in m.interaction.edges.iter().enumerate() {
+        let line = m.interaction.edge_line(index);
+        let any_line_intersecting = m.interaction.edge_crossings(index) > 0;
 
         let color_inner = if any_line_intersecting {
             MIDNIGHTBLUE
         } else {
-            sec_color
+            edge.props.owner.map_or(sec_color, player_color)
         };
         let color_outer = if any_line_intersecting {
             RED
         } else {
             tri_color
         };
-        line.draw_with_outline(&draw, color_inner, color_outer);
+        match edge.props.kind {
+            EdgeKind::Road => line.draw_with_outline(&draw, color_inner, color_outer),
+            // Walls are solid and grow thicker with their strength
+            EdgeKind::Wall => {
+                let width = 3.0 + edge.props.weight.clamp(0.0, 5.0);
+                draw.line().start(line.start.into()).end(line.end.into()).weight(width + 3.0).color(color_outer);
+                draw.line().start(line.start.into()).end(line.end.into()).weight(width).color(color_inner);
+            }
+            EdgeKind::Bridge => line.draw_dashed(&draw, color_inner, color_outer, 8.0),
+        }
+
+        if let Some(label) = &edge.props.label {
+            let middle = (line.start + line.end) * 0.5;
+            draw.text(label)
+                .x_y(middle.x, middle.y + 10.0)
+                .font_size(12)
+                .color(color_inner);
+        }
+    }
+
+    // Mark where edges cross
+    for point in m.interaction.crossing_points() {
+        draw.ellipse()
+            .x_y(point.x, point.y)
+            .w_h(7.0, 7.0)
+            .color(RED)
+            .stroke_weight(1.5)
+            .stroke(WHEAT);
+    }
+
+    draw_highlight(&draw, m);
+    if let Some(game) = &m.game {
+        draw_game(&draw, &m.interaction, game, app.time);
+    }
+    if let Some(challenge) = &m.challenge {
+        draw_challenge(&draw, &m.interaction, challenge);
+    }
+    if let Some(hint) = m.solver.as_ref().and_then(Solver::hint) {
+        draw_hint(&draw, &m.interaction, hint);
+    }
+
+    // Draw the selection and the rectangle or lasso being drawn
+    let selection_color = rgba(0.3, 0.7, 1.0, 0.6);
+    let inspected_color = rgba(1.0, 0.9, 0.3, 0.35);
+    match m.inspected.filter(|hit| m.interaction.exists(*hit)) {
+        Some(Hit::Anchor(index)) => {
+            let anchor = &m.interaction.anchors[index];
+            draw.ellipse()
+                .x_y(anchor.pos.x, anchor.pos.y)
+                .w_h(20.0, 20.0)
+                .color(inspected_color);
+        }
+        Some(Hit::Edge(index)) => {
+            let line = m.interaction.edge_line(index);
+            draw.line()
+                .start(line.start.into())
+                .end(line.end.into())
+                .weight(10.0)
+                .color(inspected_color);
+        }
+        None => {}
+    }
+    for edge in m.interaction.selected_edges() {
+        let line = m.interaction.edge_line(edge);
+        draw.line()
+            .start(line.start.into())
+            .end(line.end.into())
+            .weight(6.0)
+            .color(selection_color);
+    }
+    for index in m.interaction.selection.iter() {
+        let anchor = &m.interaction.anchors[index];
+        draw.ellipse()
+            .x_y(anchor.pos.x, anchor.pos.y)
+            .w_h(12.0, 12.0)
+            .color(selection_color);
+    }
+    if let (Some(path), Some(shape)) = (&m.interaction.selecting, m.tool.shape()) {
+        let mouse_pos = m.pointer();
+        match shape {
+            Shape::Rect => {
+                let start = path[0];
+                draw.rect()
+                    .x_y((start.x + mouse_pos.x) / 2.0, (start.y + mouse_pos.y) / 2.0)
+                    .w_h((mouse_pos.x - start.x).abs(), (mouse_pos.y - start.y).abs())
+                    .color(rgba(0.3, 0.7, 1.0, 0.1))
+                    .stroke_weight(1.0)
+                    .stroke(selection_color);
+            }
+            Shape::Lasso => {
+                let points: Vec<Vec2> = path.iter().chain([&mouse_pos, &path[0]]).map(|pos| (*pos).into()).collect();
+                draw.polyline()
+                    .weight(1.0)
+                    .points(points)
+                    .color(selection_color);
+            }
+        }
+    }
+
+    // Highlight what a right click or the Delete key would remove
+    if m.interaction.dragged_anchor.is_none()
+        && m.interaction.moving_anchor.is_none()
+        && m.interaction.selection.is_empty()
+    {
+        let hover_color = rgba(1.0, 0.3, 0.2, 0.5);
+        let highlighted_edges: Vec<usize> = match m.interaction.hit_test(m.pointer()) {
+            Some(Hit::Anchor(index)) => {
+                let anchor = &m.interaction.anchors[index];
+                draw.ellipse()
+                    .x_y(anchor.pos.x, anchor.pos.y)
+                    .w_h(14.0, 14.0)
+                    .no_fill()
+                    .stroke_weight(2.0)
+                    .stroke(hover_color);
+                m.interaction.incident_edges(index).collect()
+            }
+            Some(Hit::Edge(index)) => vec![index],
+            None => Vec::new(),
+        };
+        for edge in highlighted_edges {
+            let line = m.interaction.edge_line(edge);
+            draw.line()
+                .start(line.start.into())
+                .end(line.end.into())
+                .weight(8.0)
+                .color(hover_color);
+        }
     }
 
+    draw_overlay(&screen, app.window_rect(), &m.analyser, m.overlay);
+
+    // Live crossing counter in the top right corner
+    let crossings = m.interaction.crossing_count();
+    screen.text(&format!("Crossings: {}", crossings))
+        .x_y(window.right() - 90.0, window.top() - 20.0)
+        .font_size(16)
+        .color(if crossings == 0 { WHEAT } else { RED });
+
     draw.to_frame(app, &frame).unwrap();
     if let Some(egui) = m.egui.as_ref() {
         egui.draw_to_frame(&frame).unwrap();
     }
 }
 
+/// Draws the analysis result chosen in the inspector.
+fn draw_highlight(draw: &nannou::draw::Draw, m: &Model) {
+    let interaction = &m.interaction;
+    let graph = interaction.graph();
+    let ring = |anchor: usize, size: f32, color: Rgba| {
+        let pos = interaction.anchors[anchor].pos;
+        draw.ellipse().x_y(pos.x, pos.y).w_h(size, size).no_fill().stroke_weight(2.0).stroke(color);
+    };
+    let mark = |edge: usize, color: Rgba| {
+        let line = interaction.edge_line(edge);
+        draw.line().start(line.start.into()).end(line.end.into()).weight(8.0).color(color);
+    };
+
+    match m.highlight {
+        Highlight::None => {}
+        // Spread the hues by the golden ratio so neighbouring numbers differ clearly
+        Highlight::Components => {
+            for (anchor, component) in graph.components().into_iter().enumerate() {
+                let pos = interaction.anchors[anchor].pos;
+                let hue = (component as f32 * 0.618_034).fract();
+                draw.ellipse().x_y(pos.x, pos.y).w_h(16.0, 16.0).color(hsla(hue, 0.8, 0.55, 0.45));
+            }
+        }
+        Highlight::Cuts => {
+            let cuts = graph.cut_sets();
+            for edge in cuts.bridges {
+                mark(edge, rgba(1.0, 0.6, 0.1, 0.5));
+            }
+            for anchor in cuts.articulation_points {
+                ring(anchor, 18.0, rgba(1.0, 0.6, 0.1, 0.9));
+            }
+        }
+        Highlight::Cycle => {
+            for edge in graph.find_cycle().unwrap_or_default() {
+                mark(edge, rgba(0.3, 1.0, 0.5, 0.5));
+            }
+        }
+        Highlight::Path => {
+            if let Some(path) = picked_path(interaction, m.path_ends, m.metric) {
+                for edge in path.edges {
+                    mark(edge, rgba(0.2, 0.9, 1.0, 0.5));
+                }
+            }
+            let (from, to) = m.path_ends;
+            for anchor in [from, to].into_iter().flatten().filter(|anchor| *anchor < interaction.anchors.len()) {
+                ring(anchor, 18.0, rgba(0.2, 0.9, 1.0, 0.9));
+            }
+        }
+    }
+}
+
+/// Marks what each player has to connect, and replays the winning path once the game is won.
+fn draw_game(draw: &nannou::draw::Draw, interaction: &InteractionState, game: &Game, time: f32) {
+    let color = |player: u8, alpha: f32| {
+        let color = player_color(player);
+        let [r, g, b] = [color.red, color.green, color.blue].map(|channel| channel as f32 / 255.0);
+        rgba(r, g, b, alpha)
+    };
+
+    for player in 0..game.rules.players {
+        for anchor in game::targets(game.rules.goal, player, &interaction.anchors).into_iter().flatten() {
+            let pos = interaction.anchors[anchor].pos;
+            draw.ellipse().x_y(pos.x, pos.y).w_h(22.0, 22.0).no_fill().stroke_weight(1.5).stroke(color(player, 0.7));
+        }
+    }
+
+    if let Some(win) = &game.winner {
+        let pulse = 0.6 + 0.4 * (time * 6.0).sin();
+        for &edge in game.replayed() {
+            let line = interaction.edge_line(edge);
+            draw.line().start(line.start.into()).end(line.end.into()).weight(10.0).color(color(win.player, 0.5 * pulse));
+        }
+    }
+}
+
+/// Marks the terminals of a spanning tree challenge, and its optimal tree if revealed.
+fn draw_challenge(draw: &nannou::draw::Draw, interaction: &InteractionState, challenge: &Challenge) {
+    let anchors = &interaction.anchors;
+    for pos in challenge.terminals.iter().filter_map(|terminal| anchors.get(*terminal)).map(|anchor| anchor.pos) {
+        draw.ellipse().x_y(pos.x, pos.y).w_h(18.0, 18.0).no_fill().stroke_weight(1.5).stroke(rgba(0.5, 0.9, 0.6, 0.7));
+    }
+    if challenge.revealed {
+        for &(from, to) in &challenge.optimal {
+            if let (Some(from), Some(to)) = (anchors.get(from), anchors.get(to)) {
+                LineSegment::new(from.pos, to.pos).draw_with_outline(draw, PALEGREEN, FORESTGREEN);
+            }
+        }
+    }
+}
+
+/// Marks the anchor to move and where to, or the edge to draw.
+fn draw_hint(draw: &nannou::draw::Draw, interaction: &InteractionState, hint: Hint) {
+    match hint {
+        Hint::Move { anchor, to, .. } => {
+            let Some(from) = interaction.anchors.get(anchor).map(|anchor| anchor.pos) else {
+                return;
+            };
+            LineSegment::new(from, to).shorten_by_fixed_amount(12.0).draw_dashed(draw, GOLD, DARKORANGE, 6.0);
+            draw.ellipse().x_y(from.x, from.y).w_h(24.0, 24.0).no_fill().stroke_weight(2.0).stroke(GOLD);
+            draw.ellipse().x_y(to.x, to.y).w_h(24.0, 24.0).no_fill().stroke_weight(2.0).stroke(DARKORANGE);
+        }
+        Hint::Edge { from, to } => {
+            if let (Some(from), Some(to)) = (interaction.anchors.get(from), interaction.anchors.get(to)) {
+                LineSegment::new(from.pos, to.pos).draw_dashed(draw, GOLD, DARKORANGE, 6.0);
+            }
+        }
+    }
+}
+
+/// Draws an anchor with the shape of its kind, in its owner's color.
+fn draw_anchor(draw: &nannou::draw::Draw, anchor: &Anchor) {
+    let (x, y) = (anchor.pos.x, anchor.pos.y);
+    let color = |fallback| anchor.props.owner.map_or(fallback, player_color);
+    match anchor.props.kind {
+        AnchorKind::Neutral => {
+            draw.ellipse().x_y(x, y).w_h(5.0, 5.0).color(color(WHEAT));
+        }
+        AnchorKind::Base => {
+            draw.rect().x_y(x, y).w_h(12.0, 12.0).color(color(WHEAT)).stroke_weight(2.0).stroke(WHITE);
+        }
+        AnchorKind::Resource => {
+            draw.quad()
+                .points(pt2(x, y + 7.0), pt2(x + 7.0, y), pt2(x, y - 7.0), pt2(x - 7.0, y))
+                .color(color(GOLD));
+        }
+        // Blockers belong to nobody, so they keep their color
+        AnchorKind::Blocker => {
+            for (dx, dy) in [(5.0, 5.0), (5.0, -5.0)] {
+                draw.line().start(pt2(x - dx, y - dy)).end(pt2(x + dx, y + dy)).weight(3.0).color(GRAY);
+            }
+        }
+    }
+}
+
+/// Returns the shortest path between the anchors picked in the inspector, if both are picked and connected.
+fn picked_path(interaction: &InteractionState, ends: (Option<usize>, Option<usize>), metric: Metric) -> Option<analysis::Path> {
+    let (Some(from), Some(to)) = ends else {
+        return None;
+    };
+    if from.max(to) >= interaction.anchors.len() {
+        return None;
+    }
+    interaction.graph().shortest_path(from, to, metric)
+}
+
+/// Shows the details of an anchor in the inspector and lets them be edited.
+///
+/// # Arguments
+/// * `ui` - The UI to add the details to
+/// * `interaction` - The board the anchor is on
+/// * `index` - The index of the anchor
+/// * `editable` - Whether its properties may be changed
+/// * `movable` - Whether its position may be changed
+/// * `inspected` - Set to an incident edge when its link is clicked
+/// * `status` - Set to the reason an edit was rejected
+fn anchor_inspector_ui(
+    ui: &mut egui::Ui,
+    interaction: &mut InteractionState,
+    index: usize,
+    editable: bool,
+    movable: bool,
+    inspected: &mut Option<Hit>,
+    status: &mut Option<String>,
+) {
+    let pos = interaction.anchors[index].pos;
+    ui.heading(format!("Anchor {}", index));
+
+    let mut edited = pos;
+    ui.horizontal(|ui| {
+        ui.label("Position");
+        ui.add_enabled(movable, egui::DragValue::new(&mut edited.x).prefix("x: "));
+        ui.add_enabled(movable, egui::DragValue::new(&mut edited.y).prefix("y: "));
+    });
+    if edited != pos && !interaction.set_anchor_position(index, edited) {
+        *status = Some("Moving the anchor there would make edges cross".to_string());
+    }
+
+    let degree = interaction.degree(index);
+    ui.label(match interaction.anchors[index].props.capacity {
+        Some(capacity) => format!("Degree: {} of {}", degree, capacity),
+        None => format!("Degree: {}", degree),
+    });
+    ui.label(format!("Crossings of its edges: {}", interaction.incident_crossings(index)));
+
+    let incident: Vec<usize> = interaction.incident_edges(index).collect();
+    for edge in incident {
+        let Edge { from, to, .. } = interaction.edges[edge];
+        let other = if from == index { to } else { from };
+        if ui.link(format!("Edge {} to anchor {}", edge, other)).clicked() {
+            *inspected = Some(Hit::Edge(edge));
+        }
+    }
+
+    let mut props = interaction.anchors[index].props.clone();
+    let changed = ui.add_enabled_ui(editable, |ui| anchor_props_ui(ui, &mut props)).inner;
+    if changed && !interaction.set_anchor_props(index, props) {
+        *status = Some("The anchor has more edges than that allows".to_string());
+    }
+    if let Some(status) = status {
+        ui.label(status.as_str());
+    }
+}
+
+/// Shows the details of an edge in the inspector and lets its properties be edited.
+///
+/// # Arguments
+/// * `ui` - The UI to add the details to
+/// * `interaction` - The board the edge is on
+/// * `index` - The index of the edge
+/// * `editable` - Whether its properties may be changed
+/// * `inspected` - Set to one of the edge's anchors when its link is clicked
+/// * `template` - Set to the edge's properties when asked to
+fn edge_inspector_ui(
+    ui: &mut egui::Ui,
+    interaction: &mut InteractionState,
+    index: usize,
+    editable: bool,
+    inspected: &mut Option<Hit>,
+    template: &mut EdgeProps,
+) {
+    let edge = interaction.edges[index].clone();
+    ui.heading(format!("Edge {}", index));
+    for anchor in [edge.from, edge.to] {
+        if ui.link(format!("Anchor {}", anchor)).clicked() {
+            *inspected = Some(Hit::Anchor(anchor));
+        }
+    }
+    let line = interaction.edge_line(index);
+    ui.label(format!("Length: {:.1}", line.start.distance(&line.end)));
+    ui.label(format!("Crosses {} edges", interaction.edge_crossings(index)));
+
+    let mut props = edge.props;
+    if ui.add_enabled_ui(editable, |ui| edge_props_ui(ui, "inspected", &mut props)).inner {
+        interaction.set_edge_props(index, props);
+    }
+    if ui.button("Use for new edges").clicked() {
+        *template = interaction.edges[index].props.clone();
+    }
+}
+
+/// Shows editors for the properties of an anchor.
+///
+/// # Arguments
+/// * `ui` - The UI to add the editors to
+/// * `props` - The properties to edit
+///
+/// # Returns
+/// `true` if any property was changed
+fn anchor_props_ui(ui: &mut egui::Ui, props: &mut AnchorProps) -> bool {
+    let before = props.clone();
+    egui::ComboBox::from_id_source("anchor kind")
+        .selected_text(props.kind.label())
+        .show_ui(ui, |ui| {
+            for kind in AnchorKind::ALL {
+                ui.selectable_value(&mut props.kind, kind, kind.label());
+            }
+        });
+    owner_ui(ui, "anchor owner", &mut props.owner);
+
+    let mut limited = props.capacity.is_some();
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut limited, "Capacity");
+        let mut capacity = props.capacity.unwrap_or(3);
+        ui.add_enabled(limited, egui::DragValue::new(&mut capacity).clamp_range(0..=32));
+        props.capacity = limited.then_some(capacity);
+    });
+    ui.add(egui::Slider::new(&mut props.hit_points, 0..=1000).text("Hit points"));
+
+    *props != before
+}
+
+/// Shows a choice between no owner and each of the players.
+fn owner_ui(ui: &mut egui::Ui, id: impl std::hash::Hash, owner: &mut Option<u8>) {
+    let text = |owner: Option<u8>| owner.map_or_else(|| "No owner".to_string(), |player| format!("Player {}", player + 1));
+    egui::ComboBox::from_id_source(id)
+        .selected_text(text(*owner))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(owner, None, text(None));
+            for player in 0..PLAYER_COLORS.len() as u8 {
+                ui.selectable_value(owner, Some(player), text(Some(player)));
+            }
+        });
+}
+
+/// Shows editors for the properties of an edge.
+///
+/// # Arguments
+/// * `ui` - The UI to add the editors to
+/// * `id` - Distinguishes the editors from others in the same window
+/// * `props` - The properties to edit
+///
+/// # Returns
+/// `true` if any property was changed
+fn edge_props_ui(ui: &mut egui::Ui, id: &str, props: &mut EdgeProps) -> bool {
+    let before = props.clone();
+    egui::ComboBox::from_id_source((id, "kind"))
+        .selected_text(props.kind.label())
+        .show_ui(ui, |ui| {
+            for kind in EdgeKind::ALL {
+                ui.selectable_value(&mut props.kind, kind, kind.label());
+            }
+        });
+
+    owner_ui(ui, (id, "owner"), &mut props.owner);
+
+    ui.add(egui::Slider::new(&mut props.weight, 0.0..=10.0).text("Weight"));
+
+    let mut label = props.label.clone().unwrap_or_default();
+    ui.horizontal(|ui| {
+        ui.label("Label:");
+        if ui.text_edit_singleline(&mut label).changed() {
+            props.label = (!label.is_empty()).then_some(label);
+        }
+    });
+
+    *props != before
+}
+
+/// Stops the running recording and exports it as a WAV file.
+///
+/// # Returns
+/// A message describing where the recording went, or why it failed
+fn stop_recording(recorder: &mut recording::Recorder, audio: Option<&mut audio::Handle>) -> String {
+    let wav = recorder.stop(audio.map(|handle| &mut handle.record));
+    let name = chrono::Local::now().format("hexbattle-%Y%m%d-%H%M%S.wav").to_string();
+    match wav.map_err(|err| err.to_string()).and_then(|bytes| files::save(&name, &bytes, "audio/wav")) {
+        Ok(location) => format!("Saved {}", location),
+        Err(err) => format!("Recording failed: {}", err),
+    }
+}
+
+/// Draws the selected audio visualisation along the bottom of the window.
+fn draw_overlay(draw: &nannou::draw::Draw, window: Rect, analyser: &spectrum::Analyser, overlay: Overlay) {
+    let height = 100.0;
+    let base = window.bottom() + 10.0;
+
+    match overlay {
+        Overlay::None => {}
+        Overlay::Oscilloscope => {
+            let samples = analyser.samples();
+            let step = window.w() / samples.len() as f32;
+            let points = samples.iter().enumerate().map(|(i, sample)| {
+                pt2(
+                    window.left() + i as f32 * step,
+                    base + height / 2.0 + sample.clamp(-1.0, 1.0) * height,
+                )
+            });
+            draw.polyline().weight(1.5).points(points).color(WHEAT);
+        }
+        Overlay::Spectrum => {
+            let bands = analyser.spectrum();
+            let width = window.w() / bands.len() as f32;
+            for (i, level) in bands.iter().enumerate() {
+                let bar = (level * 4.0).min(1.0) * height;
+                draw.rect()
+                    .x_y(window.left() + (i as f32 + 0.5) * width, base + bar / 2.0)
+                    .w_h(width * 0.8, bar.max(1.0))
+                    .color(INDIGO);
+            }
+        }
+    }
+}
+
 #[cfg(not(target_family = "wasm"))]
 fn main() {
-    println!("Must be run as a web app! Use trunk to build. (cargo install trunk && trunk serve)");
+    if let Err(err) = cli::run() {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,320 @@
+use crate::audio::Voice;
+use std::f32::consts::PI;
+use std::sync::{Arc, Mutex};
+
+/// Root note of the drone in Hz (A2).
+const ROOT: f32 = 110.0;
+/// Minor pentatonic scale in semitones above the root.
+const SCALE: [f32; 5] = [0.0, 3.0, 5.0, 7.0, 10.0];
+/// Number of samples between two reads of the shared `MusicParams`.
+const CONTROL_INTERVAL: u32 = 64;
+/// Seed of the arpeggio's note choices, so the music is the same every session.
+const DEFAULT_SEED: u32 = 0x5eed_1234;
+
+/// How much a single blocked edge adds to the tension, and how fast it fades (seconds).
+const TENSION_PER_BLOCK: f32 = 0.35;
+const TENSION_DECAY: f32 = 6.0;
+/// Number of edges at which the board counts as fully busy.
+const DENSE_BOARD_EDGES: f32 = 40.0;
+
+/// Settings and game state the music voices read from the audio thread.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MusicParams {
+    /// Whether the music plays at all
+    pub enabled: bool,
+    /// Mix level of the music (0.0 to 1.0)
+    pub level: f32,
+    /// How busy and bright the music is (0.0 calm to 1.0 tense)
+    pub intensity: f32,
+}
+
+impl Default for MusicParams {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            level: 0.4,
+            intensity: 0.0,
+        }
+    }
+}
+
+/// Derives the music intensity from the game state, once per frame.
+#[derive(Debug, Default)]
+pub struct Conductor {
+    /// Short-lived excitement caused by blocked edges
+    tension: f32,
+    /// Number of blocked attempts already accounted for
+    blocked_seen: usize,
+}
+
+impl Conductor {
+    /// Creates a conductor for a calm board.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Updates the tension and returns the current intensity.
+    ///
+    /// # Arguments
+    /// * `edges` - Number of edges on the board
+    /// * `blocked_attempts` - Total number of edges rejected for crossing
+    /// * `completion` - How close the board is to being full (0.0 to 1.0)
+    /// * `dt` - Seconds since the last update
+    ///
+    /// # Returns
+    /// The intensity between 0.0 (calm) and 1.0 (tense)
+    pub fn intensity(&mut self, edges: usize, blocked_attempts: usize, completion: f32, dt: f32) -> f32 {
+        let new_blocks = blocked_attempts.saturating_sub(self.blocked_seen);
+        self.blocked_seen = blocked_attempts;
+        self.tension = (self.tension + new_blocks as f32 * TENSION_PER_BLOCK).min(1.0) * (-dt / TENSION_DECAY).exp();
+
+        let density = (edges as f32 / DENSE_BOARD_EDGES).min(1.0);
+        (0.4 * completion.clamp(0.0, 1.0) + 0.3 * density + 0.3 * self.tension).clamp(0.0, 1.0)
+    }
+}
+
+/// Creates the voices of the background music for a stream.
+///
+/// # Arguments
+/// * `params` - The shared music settings
+/// * `sample_rate` - Sample rate of the stream in Hz
+pub fn voices(params: Arc<Mutex<MusicParams>>, sample_rate: f32) -> Vec<Box<dyn Voice>> {
+    vec![
+        Box::new(Drone::new(params.clone(), sample_rate)),
+        Box::new(Arpeggio::new(params, sample_rate, DEFAULT_SEED)),
+    ]
+}
+
+/// Reads the shared parameters at control rate and smooths the level per sample.
+#[derive(Debug)]
+struct Control {
+    params: Arc<Mutex<MusicParams>>,
+    countdown: u32,
+    target_level: f32,
+    level: f32,
+    intensity: f32,
+}
+
+impl Control {
+    fn new(params: Arc<Mutex<MusicParams>>) -> Self {
+        Self {
+            params,
+            countdown: 0,
+            target_level: 0.0,
+            level: 0.0,
+            intensity: 0.0,
+        }
+    }
+
+    /// Advances by one sample, returning the smoothed level.
+    fn tick(&mut self) -> f32 {
+        if self.countdown == 0 {
+            self.countdown = CONTROL_INTERVAL;
+            if let Ok(params) = self.params.try_lock() {
+                self.target_level = if params.enabled { params.level } else { 0.0 };
+                self.intensity = params.intensity;
+            }
+        }
+        self.countdown -= 1;
+        self.level += (self.target_level - self.level) * 0.0005;
+        self.level
+    }
+}
+
+/// A slowly swelling chord on the root, the fifth and the octave.
+/// Higher intensity brightens it with the octave partial.
+#[derive(Debug)]
+pub struct Drone {
+    control: Control,
+    sample_rate: f32,
+    phases: [f32; 3],
+    time: f32,
+}
+
+impl Drone {
+    /// Creates a drone following the given parameters.
+    pub fn new(params: Arc<Mutex<MusicParams>>, sample_rate: f32) -> Self {
+        Self {
+            control: Control::new(params),
+            sample_rate,
+            phases: [0.0; 3],
+            time: 0.0,
+        }
+    }
+}
+
+impl Voice for Drone {
+    fn next_sample(&mut self) -> (f32, f32, f32) {
+        let level = self.control.tick();
+        let intensity = self.control.intensity;
+
+        let freqs = [ROOT, ROOT * 1.5, ROOT * 2.0 * 1.003];
+        for (phase, freq) in self.phases.iter_mut().zip(freqs) {
+            *phase = (*phase + 2.0 * PI * freq / self.sample_rate) % (2.0 * PI);
+        }
+        self.time += 1.0 / self.sample_rate;
+
+        let swell = 0.5 + 0.5 * (2.0 * PI * 0.05 * self.time).sin();
+        let chord = 0.5 * self.phases[0].sin() + 0.3 * self.phases[1].sin() + 0.2 * intensity * self.phases[2].sin();
+        (chord * (0.6 + 0.4 * swell) * 0.25 * level, 0.0, 1.0)
+    }
+}
+
+/// A plucked pentatonic arpeggio. Intensity raises its tempo, note density
+/// and range; each note is placed somewhere in the stereo field.
+#[derive(Debug)]
+pub struct Arpeggio {
+    control: Control,
+    sample_rate: f32,
+    rng: u32,
+    samples_to_step: f32,
+    freq: f32,
+    phase: f32,
+    envelope: f32,
+    attack: f32,
+    pan: f32,
+    target_pan: f32,
+    notes_played: usize,
+}
+
+impl Arpeggio {
+    /// Creates an arpeggio following the given parameters.
+    ///
+    /// # Arguments
+    /// * `params` - The shared music settings
+    /// * `sample_rate` - Sample rate of the stream in Hz
+    /// * `seed` - Seed of the note choices; equal seeds play equal music
+    pub fn new(params: Arc<Mutex<MusicParams>>, sample_rate: f32, seed: u32) -> Self {
+        Self {
+            control: Control::new(params),
+            sample_rate,
+            rng: seed.max(1),
+            samples_to_step: 0.0,
+            freq: ROOT * 2.0,
+            phase: 0.0,
+            envelope: 0.0,
+            attack: 1.0,
+            pan: 0.0,
+            target_pan: 0.0,
+            notes_played: 0,
+        }
+    }
+
+    /// Returns the number of notes triggered so far.
+    pub fn notes_played(&self) -> usize {
+        self.notes_played
+    }
+
+    /// Xorshift random number in `0.0..1.0`; cheap and allocation free for the audio thread.
+    fn random(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        (self.rng >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    fn step(&mut self, intensity: f32) {
+        let bpm = 70.0 + 70.0 * intensity;
+        self.samples_to_step += 60.0 / bpm / 4.0 * self.sample_rate;
+
+        if self.random() > 0.25 + 0.7 * intensity {
+            return;
+        }
+
+        let octaves = 1 + (intensity * 2.0).round() as usize;
+        let degree = (self.random() * (SCALE.len() * octaves) as f32) as usize;
+        let semitones = SCALE[degree % SCALE.len()] + 12.0 * (degree / SCALE.len()) as f32;
+        self.freq = ROOT * 2.0 * 2f32.powf(semitones / 12.0);
+        self.target_pan = (self.random() * 2.0 - 1.0) * 0.7;
+        self.envelope = 1.0;
+        self.attack = 0.0;
+        self.notes_played += 1;
+    }
+}
+
+impl Voice for Arpeggio {
+    fn next_sample(&mut self) -> (f32, f32, f32) {
+        let level = self.control.tick();
+        let intensity = self.control.intensity;
+
+        self.samples_to_step -= 1.0;
+        if self.samples_to_step <= 0.0 {
+            self.step(intensity);
+        }
+
+        // 5ms attack against clicks, then an exponential pluck decay of about a quarter second
+        self.attack = (self.attack + 1.0 / (0.005 * self.sample_rate)).min(1.0);
+        self.envelope *= (-1.0 / (0.25 * self.sample_rate)).exp();
+        self.pan += (self.target_pan - self.pan) * 0.001;
+
+        self.phase = (self.phase + 2.0 * PI * self.freq / self.sample_rate) % (2.0 * PI);
+        let tone = self.phase.sin() + 0.3 * (2.0 * self.phase).sin();
+        (tone * self.envelope * self.attack * 0.2 * level, self.pan, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::Mixer;
+
+    const SAMPLE_RATE: f32 = 8000.0;
+
+    fn params(enabled: bool, intensity: f32) -> Arc<Mutex<MusicParams>> {
+        Arc::new(Mutex::new(MusicParams {
+            enabled,
+            level: 1.0,
+            intensity,
+        }))
+    }
+
+    #[test]
+    fn test_disabled_music_is_silent() {
+        let mut mixer = Mixer::new(voices(params(false, 1.0), SAMPLE_RATE));
+        let buffer = mixer.render(2, SAMPLE_RATE as usize * 2);
+        assert!(buffer.iter().all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn test_enabled_music_renders_bounded_signal() {
+        let mut mixer = Mixer::new(voices(params(true, 0.5), SAMPLE_RATE));
+        let buffer = mixer.render(2, SAMPLE_RATE as usize * 4);
+        let peak = buffer.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        assert!(peak > 0.01);
+        assert!(peak <= 1.0);
+    }
+
+    #[test]
+    fn test_rendering_is_deterministic() {
+        let render = || Mixer::new(voices(params(true, 0.7), SAMPLE_RATE)).render(1, SAMPLE_RATE as usize);
+        assert_eq!(render(), render());
+    }
+
+    #[test]
+    fn test_intensity_plays_more_notes() {
+        let notes = |intensity| {
+            let mut arpeggio = Arpeggio::new(params(true, intensity), SAMPLE_RATE, DEFAULT_SEED);
+            for _ in 0..SAMPLE_RATE as usize * 8 {
+                arpeggio.next_sample();
+            }
+            arpeggio.notes_played()
+        };
+        assert!(notes(1.0) > notes(0.0) * 2);
+    }
+
+    #[test]
+    fn test_conductor_follows_game_state() {
+        let mut conductor = Conductor::new();
+        let calm = conductor.intensity(0, 0, 0.0, 0.016);
+        let busy = conductor.intensity(30, 0, 0.8, 0.016);
+        assert_eq!(calm, 0.0);
+        assert!(busy > calm);
+
+        let blocked = conductor.intensity(30, 3, 0.8, 0.016);
+        assert!(blocked > busy);
+
+        // Tension from blocked edges fades over time
+        let later = conductor.intensity(30, 3, 0.8, 30.0);
+        assert!(later < blocked);
+    }
+}
@@ -0,0 +1,296 @@
+use crate::edge::Edge;
+use crate::{Anchor, LineSegment, Pos};
+use std::collections::{HashMap, HashSet};
+
+/// Side of the cells segments and anchors are sorted into.
+const CELL: f32 = 32.0;
+/// How close a new edge may pass by an anchor it does not end at.
+const ANCHOR_CLEARANCE: f32 = 1.0;
+
+/// The segments and anchors of a board, sorted into grid cells so a new
+/// segment is only tested against what lies near it.
+struct Occupancy {
+    /// Segments by the cells their bounding box covers, padded by one cell
+    segments: HashMap<(i32, i32), Vec<LineSegment>>,
+    /// Anchor indices by the cell they are in
+    anchors: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl Occupancy {
+    fn new(anchors: &[Anchor]) -> Self {
+        let mut occupancy = Self { segments: HashMap::new(), anchors: HashMap::new() };
+        for (index, anchor) in anchors.iter().enumerate() {
+            occupancy.anchors.entry(cell_of(anchor.pos)).or_default().push(index);
+        }
+        occupancy
+    }
+
+    fn insert(&mut self, line: LineSegment) {
+        let (min, max) = (cell_of(line.start), cell_of(line.end));
+        for x in min.0.min(max.0) - 1..=min.0.max(max.0) + 1 {
+            for y in min.1.min(max.1) - 1..=min.1.max(max.1) + 1 {
+                self.segments.entry((x, y)).or_default().push(line);
+            }
+        }
+    }
+
+    /// Returns the cells along a segment, sampled at half a cell so no cell
+    /// it passes is more than one cell away from a sample.
+    fn cells_along(line: &LineSegment) -> impl Iterator<Item = (i32, i32)> {
+        let LineSegment { start, end } = *line;
+        let steps = (start.distance(&end) / (CELL / 2.0)).ceil().max(1.0) as usize;
+        let mut previous = None;
+        (0..=steps)
+            .map(move |step| cell_of(start + (end - start) * (step as f32 / steps as f32)))
+            .filter(move |cell| previous.replace(*cell) != Some(*cell))
+    }
+
+    /// Returns `true` if the segment crosses a stored segment.
+    fn crosses(&self, line: &LineSegment) -> bool {
+        let (min, max) = bounds(line);
+        Self::cells_along(line).any(|cell| {
+            self.segments.get(&cell).is_some_and(|segments| {
+                segments.iter().any(|other| {
+                    // Most segments are nowhere near, and comparing their bounds is much cheaper
+                    let (other_min, other_max) = bounds(other);
+                    other_min.x <= max.x
+                        && min.x <= other_max.x
+                        && other_min.y <= max.y
+                        && min.y <= other_max.y
+                        && other.line_segments_intersect(line)
+                })
+            })
+        })
+    }
+
+    /// Returns `true` if the segment from anchor `from` to `to` passes over another anchor.
+    fn passes_anchor(&self, anchors: &[Anchor], from: usize, to: usize) -> bool {
+        let line = LineSegment::new(anchors[from].pos, anchors[to].pos);
+        let mut near: HashSet<(i32, i32)> = HashSet::new();
+        for (x, y) in Self::cells_along(&line) {
+            near.extend((-1..=1).flat_map(|dx| (-1..=1).map(move |dy| (x + dx, y + dy))));
+        }
+        near.into_iter().filter_map(|cell| self.anchors.get(&cell)).flatten().any(|&anchor| {
+            anchor != from && anchor != to && line.distance_to(&anchors[anchor].pos) < ANCHOR_CLEARANCE
+        })
+    }
+}
+
+/// Returns the corners of the bounding box of a segment.
+fn bounds(line: &LineSegment) -> (Pos, Pos) {
+    (
+        Pos::new(line.start.x.min(line.end.x), line.start.y.min(line.end.y)),
+        Pos::new(line.start.x.max(line.end.x), line.start.y.max(line.end.y)),
+    )
+}
+
+fn cell_of(pos: Pos) -> (i32, i32) {
+    ((pos.x / CELL).floor() as i32, (pos.y / CELL).floor() as i32)
+}
+
+/// Picks the pairs of anchors that can be connected without crossing the
+/// existing edges or each other, trying them in the order given.
+///
+/// A pair is skipped if the anchors are already connected, an anchor has no
+/// capacity left, or the edge would pass over another anchor.
+///
+/// # Arguments
+/// * `anchors` - All anchors of the board
+/// * `edges` - The existing edges, which are kept even if they cross
+/// * `candidates` - The pairs to try
+/// * `limit` - The most pairs to pick
+///
+/// # Returns
+/// The picked pairs, in the order they were picked
+pub(crate) fn connect(
+    anchors: &[Anchor],
+    edges: &[Edge],
+    candidates: impl IntoIterator<Item = (usize, usize)>,
+    limit: usize,
+) -> Vec<(usize, usize)> {
+    let (mut occupancy, mut degrees, mut connected) = occupied(anchors, edges);
+    let mut picked = Vec::new();
+    for (from, to) in candidates {
+        if picked.len() >= limit {
+            break;
+        }
+        if !fits(anchors, &occupancy, &degrees, &connected, from, to) {
+            continue;
+        }
+
+        occupancy.insert(LineSegment::new(anchors[from].pos, anchors[to].pos));
+        degrees[from] += 1;
+        degrees[to] += 1;
+        connected.insert((from.min(to), from.max(to)));
+        picked.push((from, to));
+    }
+    picked
+}
+
+/// Returns every pair of anchors that could be connected on its own, by the
+/// same rules as `connect`. Unlike the pairs `connect` picks, these may cross
+/// each other.
+pub(crate) fn addable(anchors: &[Anchor], edges: &[Edge]) -> Vec<(usize, usize)> {
+    let (occupancy, degrees, connected) = occupied(anchors, edges);
+    (0..anchors.len())
+        .flat_map(|from| (from + 1..anchors.len()).map(move |to| (from, to)))
+        .filter(|&(from, to)| fits(anchors, &occupancy, &degrees, &connected, from, to))
+        .collect()
+}
+
+/// Sorts the existing edges into cells, and counts the edges at each anchor
+/// and the pairs already connected.
+fn occupied(anchors: &[Anchor], edges: &[Edge]) -> (Occupancy, Vec<usize>, HashSet<(usize, usize)>) {
+    let mut occupancy = Occupancy::new(anchors);
+    let mut degrees = vec![0; anchors.len()];
+    let mut connected = HashSet::new();
+    for edge in edges {
+        occupancy.insert(LineSegment::new(anchors[edge.from].pos, anchors[edge.to].pos));
+        degrees[edge.from] += 1;
+        degrees[edge.to] += 1;
+        connected.insert((edge.from.min(edge.to), edge.from.max(edge.to)));
+    }
+    (occupancy, degrees, connected)
+}
+
+/// Returns `true` if anchors `from` and `to` can be connected without
+/// crossing the occupied cells or exceeding a capacity.
+fn fits(
+    anchors: &[Anchor],
+    occupancy: &Occupancy,
+    degrees: &[usize],
+    connected: &HashSet<(usize, usize)>,
+    from: usize,
+    to: usize,
+) -> bool {
+    if from == to
+        || connected.contains(&(from.min(to), from.max(to)))
+        || !anchors[from].props.accepts_edge(degrees[from])
+        || !anchors[to].props.accepts_edge(degrees[to])
+    {
+        return false;
+    }
+    let line = LineSegment::new(anchors[from].pos, anchors[to].pos);
+    !occupancy.crosses(&line) && !occupancy.passes_anchor(anchors, from, to)
+}
+
+/// Sorts pairs of anchors shortest first.
+pub(crate) fn shortest_first(anchors: &[Anchor], pairs: &mut Vec<(usize, usize)>) {
+    let mut measured: Vec<(f32, (usize, usize))> = pairs
+        .iter()
+        .map(|&(from, to)| (anchors[from].pos.distance(&anchors[to].pos), (from, to)))
+        .collect();
+    measured.sort_unstable_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+    *pairs = measured.into_iter().map(|(_, pair)| pair).collect();
+}
+
+/// Completes the board to a maximal planar graph: afterwards no two anchors
+/// with capacity left can be connected without crossing an edge.
+///
+/// Existing edges are kept. Connecting the shortest pairs first gives the
+/// greedy triangulation, which like a Delaunay triangulation avoids long,
+/// thin triangles.
+///
+/// # Returns
+/// The pairs to connect
+pub(crate) fn triangulate(anchors: &[Anchor], edges: &[Edge]) -> Vec<(usize, usize)> {
+    let mut pairs: Vec<(usize, usize)> = (0..anchors.len())
+        .flat_map(|from| (from + 1..anchors.len()).map(move |to| (from, to)))
+        .collect();
+    shortest_first(anchors, &mut pairs);
+    connect(anchors, edges, pairs, usize::MAX)
+}
+
+/// Returns each anchor paired with its `count` nearest anchors, each pair once.
+pub(crate) fn nearest_pairs(anchors: &[Anchor], count: usize) -> Vec<(usize, usize)> {
+    let mut pairs = HashSet::new();
+    for from in 0..anchors.len() {
+        let mut others: Vec<usize> = (0..anchors.len()).filter(|to| *to != from).collect();
+        let distance = |to: &usize| anchors[from].pos.distance(&anchors[*to].pos);
+        others.sort_by(|a, b| distance(a).total_cmp(&distance(b)));
+        pairs.extend(others.into_iter().take(count).map(|to| (from.min(to), from.max(to))));
+    }
+    let mut pairs: Vec<(usize, usize)> = pairs.into_iter().collect();
+    pairs.sort_unstable();
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anchor::{AnchorKind, AnchorProps};
+
+    fn anchors(positions: &[(f32, f32)]) -> Vec<Anchor> {
+        positions.iter().map(|&(x, y)| Anchor::new(Pos::new(x, y))).collect()
+    }
+
+    /// A pseudo-random scatter, so the tests do not depend on a random number generator.
+    fn scatter(count: usize) -> Vec<Anchor> {
+        let mut seed = 12345u32;
+        let mut next = move || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (seed >> 8) as f32 / (1u32 << 24) as f32 * 400.0 - 200.0
+        };
+        (0..count).map(|_| Anchor::new(Pos::new(next(), next()))).collect()
+    }
+
+    fn crossings(anchors: &[Anchor], edges: &[(usize, usize)]) -> usize {
+        let lines: Vec<LineSegment> =
+            edges.iter().map(|&(from, to)| LineSegment::new(anchors[from].pos, anchors[to].pos)).collect();
+        (0..lines.len())
+            .map(|a| (a + 1..lines.len()).filter(|b| lines[a].line_segments_intersect(&lines[*b])).count())
+            .sum()
+    }
+
+    #[test]
+    fn test_triangulation_is_planar_and_maximal() {
+        let anchors = scatter(40);
+        let edges = triangulate(&anchors, &[]);
+        assert_eq!(crossings(&anchors, &edges), 0);
+
+        // Every pair left out would cross an edge
+        let all: Vec<Edge> = edges.iter().map(|&(from, to)| Edge::new(from, to)).collect();
+        let pairs = (0..anchors.len()).flat_map(|from| (from + 1..anchors.len()).map(move |to| (from, to)));
+        assert!(connect(&anchors, &all, pairs, usize::MAX).is_empty());
+    }
+
+    #[test]
+    fn test_triangulation_of_square_with_centre() {
+        let anchors = anchors(&[(0.0, 0.0), (100.0, 0.0), (100.0, 100.0), (0.0, 100.0), (60.0, 30.0)]);
+        // Three times the anchors, less three, less the four on the outline
+        assert_eq!(triangulate(&anchors, &[]).len(), 8);
+
+        // A kept diagonal cuts the inner anchor off from the far corner
+        let diagonal = [Edge::new(0, 2)];
+        let added = triangulate(&anchors, &diagonal);
+        assert!(!added.iter().any(|&(from, to)| Edge::new(from, to).touches(3) && Edge::new(from, to).touches(4)));
+        assert_eq!(added.len(), 7);
+    }
+
+    #[test]
+    fn test_connect_respects_capacity_and_anchors_in_the_way() {
+        let mut anchors = anchors(&[(0.0, 0.0), (50.0, 0.0), (100.0, 0.0), (50.0, 50.0)]);
+        // The edge from 0 to 2 would run over anchor 1
+        assert_eq!(connect(&anchors, &[], [(0, 2)], usize::MAX), vec![]);
+
+        anchors[3].props = AnchorProps { capacity: Some(1), ..AnchorProps::default() };
+        assert_eq!(connect(&anchors, &[], [(0, 3), (1, 3), (2, 3)], usize::MAX), vec![(0, 3)]);
+
+        anchors[1].props.kind = AnchorKind::Blocker;
+        assert_eq!(connect(&anchors, &[], [(0, 1), (1, 2), (0, 3)], 5), vec![(0, 3)]);
+        assert_eq!(connect(&anchors, &[], [(0, 3), (2, 3), (0, 2)], 1).len(), 1);
+    }
+
+    #[test]
+    fn test_random_planar_edges_do_not_cross() {
+        let anchors = scatter(60);
+        let mut pairs = nearest_pairs(&anchors, 6);
+        assert!(pairs.len() >= 3 * anchors.len());
+        // Longest first gives many opportunities to cross
+        shortest_first(&anchors, &mut pairs);
+        pairs.reverse();
+        let edges = connect(&anchors, &[], pairs, anchors.len());
+        assert_eq!(edges.len(), anchors.len());
+        assert_eq!(crossings(&anchors, &edges), 0);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::board::Board;
+use crate::edge::Edge;
+use crate::{levels, Anchor, LineSegment, Pos};
+use nannou::rand::{seq::SliceRandom, Rng};
+
+/// Radius of the circle the anchors are scrambled onto.
+const SCRAMBLE_RADIUS: f32 = 320.0;
+
+/// How hard a generated puzzle is, by its number of anchors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+    Expert,
+}
+
+impl Difficulty {
+    /// All tiers, in the order they are offered in the UI.
+    pub(crate) const ALL: [Difficulty; 4] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard, Difficulty::Expert];
+
+    /// Returns the name shown in the UI.
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Medium => "Medium",
+            Difficulty::Hard => "Hard",
+            Difficulty::Expert => "Expert",
+        }
+    }
+
+    /// Returns the number of anchors of a puzzle of this tier.
+    pub(crate) fn anchor_count(self) -> usize {
+        match self {
+            Difficulty::Easy => 8,
+            Difficulty::Medium => 12,
+            Difficulty::Hard => 18,
+            Difficulty::Expert => 26,
+        }
+    }
+}
+
+/// An untangle puzzle: the anchors of a planar graph are scrambled so its
+/// edges cross, and the player moves them until no edges cross any more.
+#[derive(Clone, Debug)]
+pub(crate) struct Puzzle {
+    pub(crate) difficulty: Difficulty,
+    /// Anchor moves made so far
+    pub(crate) moves: usize,
+    /// Seconds spent solving so far
+    pub(crate) elapsed: f32,
+    /// Whether the crossing count has reached zero, which stops the clock
+    pub(crate) solved: bool,
+    /// The board's move count when the puzzle started
+    start_moves: usize,
+}
+
+impl Puzzle {
+    /// Starts tracking a puzzle on a board that has made `moves` moves so far.
+    pub(crate) fn new(difficulty: Difficulty, moves: usize) -> Self {
+        Self { difficulty, moves: 0, elapsed: 0.0, solved: false, start_moves: moves }
+    }
+
+    /// Advances the clock and checks for a solution.
+    ///
+    /// # Arguments
+    /// * `dt` - Seconds since the last update
+    /// * `moves` - The board's move count
+    /// * `crossings` - The number of crossing edges on the board
+    pub(crate) fn update(&mut self, dt: f32, moves: usize, crossings: usize) {
+        if self.solved {
+            return;
+        }
+        self.elapsed += dt;
+        self.moves = moves.saturating_sub(self.start_moves);
+        self.solved = crossings == 0;
+    }
+}
+
+/// Formats seconds as minutes and seconds.
+pub(crate) fn clock(seconds: f32) -> String {
+    let seconds = seconds as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Generates a puzzle board: a thinned-out triangulation of scattered
+/// anchors, with the anchors moved onto a circle in random order so that
+/// edges cross.
+pub(crate) fn generate(difficulty: Difficulty, rng: &mut impl Rng) -> Board {
+    let count = difficulty.anchor_count();
+    let Board { anchors, edges } = levels::solved(count, 2.0, rng);
+
+    let mut order: Vec<usize> = (0..count).collect();
+    let mut scrambled = anchors.clone();
+    // Every shuffle almost surely crosses, but a few more tries cost nothing
+    for _ in 0..100 {
+        order.shuffle(rng);
+        for (slot, &anchor) in order.iter().enumerate() {
+            let angle = slot as f32 / count as f32 * std::f32::consts::TAU;
+            scrambled[anchor].pos = Pos::new(angle.cos(), angle.sin()) * SCRAMBLE_RADIUS;
+        }
+        if crossings(&scrambled, &edges) > 0 {
+            break;
+        }
+    }
+    Board { anchors: scrambled, edges }
+}
+
+/// Counts the pairs of crossing edges.
+pub(crate) fn crossings(anchors: &[Anchor], edges: &[Edge]) -> usize {
+    let lines: Vec<LineSegment> =
+        edges.iter().map(|edge| LineSegment::new(anchors[edge.from].pos, anchors[edge.to].pos)).collect();
+    (0..lines.len())
+        .map(|a| (a + 1..lines.len()).filter(|b| lines[a].line_segments_intersect(&lines[*b])).count())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nannou::rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_generated_puzzles_are_scrambled_and_sized_by_tier() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for difficulty in Difficulty::ALL {
+            let board = generate(difficulty, &mut rng);
+            assert_eq!(board.anchors.len(), difficulty.anchor_count());
+            assert!(board.edges.len() >= board.anchors.len());
+            assert!(crossings(&board.anchors, &board.edges) > 0);
+
+            let mut degrees = vec![0; board.anchors.len()];
+            for edge in &board.edges {
+                degrees[edge.from] += 1;
+                degrees[edge.to] += 1;
+            }
+            assert!(degrees.iter().all(|degree| *degree >= 2));
+        }
+    }
+
+    #[test]
+    fn test_clock_stops_when_solved() {
+        let mut puzzle = Puzzle::new(Difficulty::Easy, 5);
+        puzzle.update(1.5, 7, 3);
+        assert_eq!((puzzle.moves, puzzle.solved), (2, false));
+
+        puzzle.update(1.0, 8, 0);
+        assert!(puzzle.solved);
+        puzzle.update(10.0, 9, 0);
+        assert_eq!(puzzle.moves, 3);
+        assert_eq!(clock(puzzle.elapsed), "0:02");
+        assert_eq!(clock(125.0), "2:05");
+    }
+}
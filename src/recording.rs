@@ -0,0 +1,149 @@
+use ringbuf::Consumer;
+use std::io::Cursor;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Collects the output of the audio stream on the main thread while a
+/// recording session runs, and encodes it as a WAV file when it stops.
+#[derive(Debug)]
+pub struct Recorder {
+    /// Shared with the mixer, which only copies its output while this is set
+    active: Arc<AtomicBool>,
+    /// Interleaved samples recorded so far
+    samples: Vec<f32>,
+    /// Channel count of the recorded stream
+    channels: u16,
+    /// Sample rate of the recorded stream in Hz
+    sample_rate: u32,
+}
+
+impl Recorder {
+    /// Creates an idle recorder controlling the given mixer flag.
+    pub fn new(active: Arc<AtomicBool>) -> Self {
+        Self {
+            active,
+            samples: Vec::new(),
+            channels: 2,
+            sample_rate: 44100,
+        }
+    }
+
+    /// Returns `true` while a recording session runs.
+    pub fn is_recording(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Returns the length of the current recording in seconds.
+    pub fn duration(&self) -> f32 {
+        self.samples.len() as f32 / (self.channels.max(1) as u32 * self.sample_rate) as f32
+    }
+
+    /// Starts a new recording of a stream with the given format.
+    ///
+    /// # Arguments
+    /// * `source` - The recording buffer of the stream; stale samples are discarded
+    /// * `channels` - Number of interleaved channels of the stream
+    /// * `sample_rate` - Sample rate of the stream in Hz
+    pub fn start(&mut self, source: &mut Consumer<f32>, channels: u16, sample_rate: u32) {
+        source.discard(source.len());
+        self.samples.clear();
+        self.channels = channels;
+        self.sample_rate = sample_rate;
+        self.active.store(true, Ordering::Relaxed);
+    }
+
+    /// Moves everything the audio callback recorded since the last call into the recording.
+    pub fn drain(&mut self, source: &mut Consumer<f32>) {
+        self.samples.reserve(source.len());
+        source.pop_each(
+            |sample| {
+                self.samples.push(sample);
+                true
+            },
+            None,
+        );
+    }
+
+    /// Stops the recording and encodes it as a 16 bit WAV file.
+    ///
+    /// # Arguments
+    /// * `source` - The recording buffer of the stream, drained one last time
+    pub fn stop(&mut self, source: Option<&mut Consumer<f32>>) -> Result<Vec<u8>, hound::Error> {
+        self.active.store(false, Ordering::Relaxed);
+        if let Some(source) = source {
+            self.drain(source);
+        }
+
+        // Drop a trailing partial frame so channels stay aligned
+        let whole_frames = self.samples.len() - self.samples.len() % self.channels.max(1) as usize;
+        self.samples.truncate(whole_frames);
+
+        let wav = encode_wav(&self.samples, self.channels, self.sample_rate);
+        self.samples.clear();
+        wav
+    }
+}
+
+/// Encodes interleaved samples as a 16 bit PCM WAV file.
+///
+/// # Arguments
+/// * `samples` - Interleaved samples between -1.0 and 1.0
+/// * `channels` - Number of interleaved channels
+/// * `sample_rate` - Sample rate in Hz
+pub fn encode_wav(samples: &[f32], channels: u16, sample_rate: u32) -> Result<Vec<u8>, hound::Error> {
+    let spec = hound::WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut bytes = Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut bytes, spec)?;
+    for sample in samples {
+        writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+    }
+    writer.finalize()?;
+    Ok(bytes.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ringbuf::RingBuffer;
+
+    #[test]
+    fn test_encode_wav_round_trip() {
+        let samples = [0.0, 0.5, -0.5, 1.0];
+        let bytes = encode_wav(&samples, 2, 48000).unwrap();
+
+        let mut reader = hound::WavReader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.spec().channels, 2);
+        assert_eq!(reader.spec().sample_rate, 48000);
+        let decoded: Vec<i16> = reader.samples::<i16>().map(|s| s.unwrap()).collect();
+        assert_eq!(decoded, vec![0, i16::MAX / 2, -(i16::MAX / 2), i16::MAX]);
+    }
+
+    #[test]
+    fn test_recorder_session() {
+        let (mut producer, mut consumer) = RingBuffer::new(64).split();
+        let active = Arc::new(AtomicBool::new(false));
+        let mut recorder = Recorder::new(active.clone());
+
+        // Samples from before the session are not part of it
+        producer.push_slice(&[0.9, 0.9]);
+        recorder.start(&mut consumer, 2, 10);
+        assert!(active.load(Ordering::Relaxed));
+
+        producer.push_slice(&[0.1; 10]);
+        recorder.drain(&mut consumer);
+        assert_eq!(recorder.duration(), 0.5);
+
+        producer.push_slice(&[0.1; 11]);
+        let bytes = recorder.stop(Some(&mut consumer)).unwrap();
+        assert!(!recorder.is_recording());
+
+        let reader = hound::WavReader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.len(), 20);
+    }
+}
@@ -0,0 +1,187 @@
+use crate::edge::Edge;
+use crate::{Anchor, Pos};
+use std::collections::BTreeSet;
+
+/// The shape drawn to select anchors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Shape {
+    /// A rectangle spanned by the first and the last point of the path
+    Rect,
+    /// A freehand polygon through all points of the path
+    Lasso,
+}
+
+impl Shape {
+    /// Returns `true` if the point lies inside the shape drawn along `path`.
+    pub(crate) fn contains(self, path: &[Pos], point: Pos) -> bool {
+        match self {
+            Shape::Rect => match (path.first(), path.last()) {
+                (Some(a), Some(b)) => {
+                    point.x >= a.x.min(b.x)
+                        && point.x <= a.x.max(b.x)
+                        && point.y >= a.y.min(b.y)
+                        && point.y <= a.y.max(b.y)
+                }
+                _ => false,
+            },
+            Shape::Lasso => polygon_contains(path, point),
+        }
+    }
+}
+
+/// Even-odd test whether a point lies inside a closed polygon.
+///
+/// # Arguments
+/// * `polygon` - The corners of the polygon; the last one connects back to the first
+/// * `point` - The point to test
+pub(crate) fn polygon_contains(polygon: &[Pos], point: Pos) -> bool {
+    if polygon.len() < 3 {
+        return false;
+    }
+
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+    for i in 0..polygon.len() {
+        let (a, b) = (polygon[i], polygon[j]);
+        if (a.y > point.y) != (b.y > point.y) && point.x < (b.x - a.x) * (point.y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/// A set of selected anchors. An edge counts as selected when both of its
+/// anchors are.
+///
+/// The selection refers to anchors by index, so the board must tell it
+/// whenever anchors are inserted or removed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Selection {
+    anchors: BTreeSet<usize>,
+}
+
+impl Selection {
+    /// Returns `true` if nothing is selected.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.anchors.is_empty()
+    }
+
+    /// Returns the number of selected anchors.
+    pub(crate) fn len(&self) -> usize {
+        self.anchors.len()
+    }
+
+    /// Returns `true` if the anchor is selected.
+    pub(crate) fn contains(&self, anchor: usize) -> bool {
+        self.anchors.contains(&anchor)
+    }
+
+    /// Returns `true` if both anchors of the edge are selected.
+    pub(crate) fn contains_edge(&self, (from, to): (usize, usize)) -> bool {
+        self.contains(from) && self.contains(to)
+    }
+
+    /// Iterates over the selected anchors in ascending order.
+    pub(crate) fn iter(&self) -> impl DoubleEndedIterator<Item = usize> + '_ {
+        self.anchors.iter().copied()
+    }
+
+    /// Adds anchors to the selection.
+    pub(crate) fn extend(&mut self, anchors: impl IntoIterator<Item = usize>) {
+        self.anchors.extend(anchors);
+    }
+
+    /// Deselects everything.
+    pub(crate) fn clear(&mut self) {
+        self.anchors.clear();
+    }
+
+    /// Follows the removal of an anchor: deselects it and shifts later indices down.
+    pub(crate) fn anchor_removed(&mut self, index: usize) {
+        self.anchors = self.anchors
+            .iter()
+            .filter(|anchor| **anchor != index)
+            .map(|anchor| if *anchor > index { anchor - 1 } else { *anchor })
+            .collect();
+    }
+
+    /// Follows the insertion of an anchor: shifts the indices from `index` on up.
+    pub(crate) fn anchor_inserted(&mut self, index: usize) {
+        self.anchors = self.anchors
+            .iter()
+            .map(|anchor| if *anchor >= index { anchor + 1 } else { *anchor })
+            .collect();
+    }
+
+    /// Follows the board shrinking to `count` anchors.
+    pub(crate) fn truncate(&mut self, count: usize) {
+        self.anchors.retain(|anchor| *anchor < count);
+    }
+}
+
+/// Copied anchors and the edges between them, ready to be pasted.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Clipboard {
+    /// The copied anchors
+    pub(crate) anchors: Vec<Anchor>,
+    /// Edges between the copied anchors, with indices into `anchors`
+    pub(crate) edges: Vec<Edge>,
+}
+
+impl Clipboard {
+    /// Returns the average position of the copied anchors.
+    pub(crate) fn centroid(&self) -> Pos {
+        let sum = self.anchors.iter().fold(Pos::new(0.0, 0.0), |sum, anchor| sum + anchor.pos);
+        sum * (1.0 / self.anchors.len().max(1) as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rect_contains_in_any_drag_direction() {
+        let path = [Pos::new(10.0, 10.0), Pos::new(-10.0, -10.0)];
+        assert!(Shape::Rect.contains(&path, Pos::new(0.0, 5.0)));
+        assert!(!Shape::Rect.contains(&path, Pos::new(0.0, 15.0)));
+        assert!(!Shape::Rect.contains(&[], Pos::new(0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_lasso_contains_concave_polygon() {
+        // A U shape opening upwards
+        let path = [
+            Pos::new(0.0, 0.0),
+            Pos::new(30.0, 0.0),
+            Pos::new(30.0, 30.0),
+            Pos::new(20.0, 30.0),
+            Pos::new(20.0, 10.0),
+            Pos::new(10.0, 10.0),
+            Pos::new(10.0, 30.0),
+            Pos::new(0.0, 30.0),
+        ];
+        assert!(Shape::Lasso.contains(&path, Pos::new(5.0, 20.0)));
+        assert!(Shape::Lasso.contains(&path, Pos::new(15.0, 5.0)));
+        assert!(!Shape::Lasso.contains(&path, Pos::new(15.0, 20.0)));
+        assert!(!Shape::Lasso.contains(&path[..2], Pos::new(15.0, 0.0)));
+    }
+
+    #[test]
+    fn test_selection_follows_anchor_removal_and_insertion() {
+        let mut selection = Selection::default();
+        selection.extend([1, 3, 5]);
+
+        selection.anchor_removed(3);
+        assert_eq!(selection.iter().collect::<Vec<_>>(), vec![1, 4]);
+
+        selection.anchor_inserted(2);
+        assert_eq!(selection.iter().collect::<Vec<_>>(), vec![1, 5]);
+
+        selection.truncate(5);
+        assert_eq!(selection.iter().collect::<Vec<_>>(), vec![1]);
+        assert!(selection.contains_edge((1, 1)));
+        assert!(!selection.contains_edge((1, 5)));
+    }
+}
@@ -0,0 +1,190 @@
+use crate::Pos;
+
+/// Distance within which a position aligns with another anchor's x or y.
+const ALIGN_TOLERANCE: f32 = 8.0;
+
+/// The grid positions snap to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Grid {
+    /// Positions are not snapped to a grid
+    Off,
+    /// A square grid with `spacing` between neighbouring points
+    Square,
+    /// A triangular lattice of hex cell centres, `spacing` apart, with every
+    /// other row shifted by half the spacing
+    Hex,
+}
+
+/// A line drawn to show what a position was aligned with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Guide {
+    /// A vertical line at the given x
+    Vertical(f32),
+    /// A horizontal line at the given y
+    Horizontal(f32),
+}
+
+/// Settings of the snapping applied to placed, moved and pasted anchors.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Snap {
+    /// The grid to snap to
+    pub(crate) grid: Grid,
+    /// Distance between neighbouring grid points
+    pub(crate) spacing: f32,
+    /// Whether to align with the x and y of other anchors
+    pub(crate) align: bool,
+}
+
+impl Default for Snap {
+    fn default() -> Self {
+        Self {
+            grid: Grid::Off,
+            spacing: 40.0,
+            align: false,
+        }
+    }
+}
+
+impl Snap {
+    /// Returns `true` if any kind of snapping is enabled.
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.grid != Grid::Off || self.align
+    }
+
+    /// Snaps a position to the grid, then aligns it with nearby anchors.
+    ///
+    /// Alignment wins over the grid, so anchors placed before the grid was
+    /// enabled can still be lined up with.
+    ///
+    /// # Arguments
+    /// * `pos` - The position to snap
+    /// * `others` - Positions of the anchors to align with
+    ///
+    /// # Returns
+    /// The snapped position and the guides of the alignments made
+    pub(crate) fn apply(&self, pos: Pos, others: impl Iterator<Item = Pos> + Clone) -> (Pos, Vec<Guide>) {
+        let mut snapped = self.to_grid(pos);
+        let mut guides = Vec::new();
+        if !self.align {
+            return (snapped, guides);
+        }
+
+        let closest = |axis: fn(&Pos) -> f32| {
+            others
+                .clone()
+                .map(|other| axis(&other))
+                .filter(|value| (value - axis(&pos)).abs() < ALIGN_TOLERANCE)
+                .min_by(|a, b| (a - axis(&pos)).abs().total_cmp(&(b - axis(&pos)).abs()))
+        };
+        if let Some(x) = closest(|p| p.x) {
+            snapped.x = x;
+            guides.push(Guide::Vertical(x));
+        }
+        if let Some(y) = closest(|p| p.y) {
+            snapped.y = y;
+            guides.push(Guide::Horizontal(y));
+        }
+        (snapped, guides)
+    }
+
+    /// Returns the grid point closest to a position.
+    pub(crate) fn to_grid(&self, pos: Pos) -> Pos {
+        let spacing = self.spacing.max(1.0);
+        match self.grid {
+            Grid::Off => pos,
+            Grid::Square => Pos::new((pos.x / spacing).round() * spacing, (pos.y / spacing).round() * spacing),
+            Grid::Hex => {
+                let row_height = spacing * 3f32.sqrt() / 2.0;
+                let row = (pos.y / row_height).round() as i64;
+                // The closest point is in the nearest row or in one of its neighbours
+                (row - 1..=row + 1)
+                    .map(|row| {
+                        let shift = if row.rem_euclid(2) == 1 { spacing / 2.0 } else { 0.0 };
+                        let x = ((pos.x - shift) / spacing).round() * spacing + shift;
+                        Pos::new(x, row as f32 * row_height)
+                    })
+                    .min_by(|a, b| a.distance(&pos).total_cmp(&b.distance(&pos)))
+                    .unwrap_or(pos)
+            }
+        }
+    }
+
+    /// Returns the grid points inside the given bounds, for drawing the grid.
+    ///
+    /// # Arguments
+    /// * `min` - The bottom left corner of the bounds
+    /// * `max` - The top right corner of the bounds
+    pub(crate) fn points(&self, min: Pos, max: Pos) -> Vec<Pos> {
+        let spacing = self.spacing.max(1.0);
+        let (row_height, stagger) = match self.grid {
+            Grid::Off => return Vec::new(),
+            Grid::Square => (spacing, 0.0),
+            Grid::Hex => (spacing * 3f32.sqrt() / 2.0, spacing / 2.0),
+        };
+
+        let rows = (min.y / row_height).floor() as i64..=(max.y / row_height).ceil() as i64;
+        rows.flat_map(|row| {
+            let shift = if row.rem_euclid(2) == 1 { stagger } else { 0.0 };
+            let columns = ((min.x - shift) / spacing).floor() as i64..=((max.x - shift) / spacing).ceil() as i64;
+            columns.map(move |column| Pos::new(column as f32 * spacing + shift, row as f32 * row_height))
+        })
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snap(grid: Grid, align: bool) -> Snap {
+        Snap {
+            grid,
+            spacing: 20.0,
+            align,
+        }
+    }
+
+    #[test]
+    fn test_off_keeps_position() {
+        let pos = Pos::new(13.3, -7.1);
+        assert_eq!(snap(Grid::Off, false).apply(pos, std::iter::empty()), (pos, Vec::new()));
+        assert!(!snap(Grid::Off, false).is_enabled());
+    }
+
+    #[test]
+    fn test_square_grid_rounds_to_nearest_point() {
+        let snap = snap(Grid::Square, false);
+        assert_eq!(snap.to_grid(Pos::new(29.0, -11.0)), Pos::new(20.0, -20.0));
+        assert_eq!(snap.to_grid(Pos::new(31.0, 9.0)), Pos::new(40.0, 0.0));
+    }
+
+    #[test]
+    fn test_hex_grid_staggers_odd_rows() {
+        let snap = snap(Grid::Hex, false);
+        let row_height = 20.0 * 3f32.sqrt() / 2.0;
+        assert_eq!(snap.to_grid(Pos::new(3.0, 1.0)), Pos::new(0.0, 0.0));
+        assert_eq!(snap.to_grid(Pos::new(12.0, row_height - 2.0)), Pos::new(10.0, row_height));
+        assert_eq!(snap.to_grid(Pos::new(-9.0, -row_height)), Pos::new(-10.0, -row_height));
+
+        // Every grid point is at least `spacing` away from its neighbours
+        let points = snap.points(Pos::new(-50.0, -50.0), Pos::new(50.0, 50.0));
+        for (i, a) in points.iter().enumerate() {
+            for b in &points[i + 1..] {
+                assert!(a.distance(b) > 19.9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_alignment_overrides_grid_and_reports_guides() {
+        let others = [Pos::new(33.0, 100.0), Pos::new(-80.0, 47.0)];
+        let (pos, guides) = snap(Grid::Square, true).apply(Pos::new(37.0, 43.0), others.iter().copied());
+        assert_eq!(pos, Pos::new(33.0, 47.0));
+        assert_eq!(guides, vec![Guide::Vertical(33.0), Guide::Horizontal(47.0)]);
+
+        // Too far away to align, so only the grid applies
+        let (pos, guides) = snap(Grid::Square, true).apply(Pos::new(60.0, 70.0), others.iter().copied());
+        assert_eq!(pos, Pos::new(60.0, 80.0));
+        assert!(guides.is_empty());
+    }
+}
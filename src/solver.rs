@@ -0,0 +1,483 @@
+use crate::analysis::UnionFind;
+use crate::edge::{Edge, EdgeProps};
+use crate::game::{self, Goal};
+use crate::{planar, puzzle, Anchor, LineSegment, Pos};
+use std::time::Duration;
+use wasm_timer::Instant;
+
+/// How long the search may run in each frame.
+pub(crate) const FRAME_BUDGET: Duration = Duration::from_millis(4);
+/// Closest a suggested spot may be to another anchor, or half that to an edge.
+const CLEARANCE: f32 = 25.0;
+/// Directions tried around the middle of an anchor's neighbours.
+const DIRECTIONS: usize = 12;
+/// Distances tried from the middle of the neighbours, as shares of their mean distance.
+const REACH: [f32; 4] = [0.25, 0.5, 0.8, 1.1];
+
+/// What the solver works towards.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Objective {
+    /// No two edges cross, by moving anchors
+    Untangle,
+    /// The player's goal is connected by their own edges, by drawing edges
+    /// for them
+    Connect { goal: Goal, player: u8 },
+    /// No more edges fit without crossings, by drawing edges
+    MaxEdges,
+}
+
+impl Objective {
+    /// All objectives, in the order they are offered in the UI.
+    pub(crate) const ALL: [Objective; 3] =
+        [Objective::Untangle, Objective::Connect { goal: Goal::Bases, player: 0 }, Objective::MaxEdges];
+
+    /// Returns the name shown in the UI.
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Objective::Untangle => "Untangle",
+            Objective::Connect { .. } => "Connect the goal",
+            Objective::MaxEdges => "Most edges",
+        }
+    }
+
+    /// Returns the objective aimed at the given player's goal, which only
+    /// changes a connection.
+    pub(crate) fn for_player(self, goal: Goal, player: u8) -> Self {
+        match self {
+            Objective::Connect { .. } => Objective::Connect { goal, player },
+            other => other,
+        }
+    }
+}
+
+/// A step suggested by the solver.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Hint {
+    /// Move an anchor to another spot
+    Move { anchor: usize, from: Pos, to: Pos },
+    /// Draw an edge between two anchors
+    Edge { from: usize, to: usize },
+}
+
+/// How far the search has got.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Status {
+    /// More steps may follow
+    Searching,
+    /// The plan reaches the objective
+    Solved,
+    /// No step improves the board any more, so the plan only gets closer
+    Stuck,
+}
+
+/// Searches for steps towards an objective on a copy of the board, a little
+/// at a time so it can run alongside the frame loop.
+///
+/// The search is greedy: each step is the best one found on the board the
+/// earlier steps leave behind, so its first step is the hint.
+#[derive(Clone, Debug)]
+pub(crate) struct Solver {
+    pub(crate) objective: Objective,
+    pub(crate) status: Status,
+    /// The steps found so far, in order
+    pub(crate) plan: Vec<Hint>,
+    /// The board as it is after the plan
+    anchors: Vec<Anchor>,
+    edges: Vec<Edge>,
+    /// Where the anchors were and which pairs were connected when the search started
+    start: (Vec<Pos>, Vec<(usize, usize)>),
+    /// Crossings on the board after the plan, kept while untangling
+    crossings: usize,
+    /// The next anchor whose moves are tried
+    cursor: usize,
+    /// The best move of the current round, with how many crossings it removes
+    best: Option<(usize, Hint)>,
+}
+
+impl Solver {
+    /// Starts a search on a copy of the given board.
+    pub(crate) fn new(objective: Objective, anchors: &[Anchor], edges: &[Edge]) -> Self {
+        let start = (
+            anchors.iter().map(|anchor| anchor.pos).collect(),
+            edges.iter().map(|edge| (edge.from, edge.to)).collect(),
+        );
+        Self {
+            objective,
+            status: Status::Searching,
+            plan: Vec::new(),
+            anchors: anchors.to_vec(),
+            edges: edges.to_vec(),
+            start,
+            crossings: puzzle::crossings(anchors, edges),
+            cursor: 0,
+            best: None,
+        }
+    }
+
+    /// Returns the first step of the plan, if there is one.
+    pub(crate) fn hint(&self) -> Option<Hint> {
+        self.plan.first().copied()
+    }
+
+    /// Returns `true` if the board no longer is the one the search started on.
+    pub(crate) fn is_stale(&self, anchors: &[Anchor], edges: &[Edge]) -> bool {
+        let (positions, pairs) = &self.start;
+        !anchors.iter().map(|anchor| anchor.pos).eq(positions.iter().copied())
+            || !edges.iter().map(|edge| (edge.from, edge.to)).eq(pairs.iter().copied())
+    }
+
+    /// Searches until the budget is spent or the search ends. At least one
+    /// piece of work is done, so a zero budget still makes progress.
+    pub(crate) fn search(&mut self, budget: Duration) -> Status {
+        let deadline = Instant::now() + budget;
+        while self.status == Status::Searching {
+            self.advance();
+            if Instant::now() >= deadline {
+                break;
+            }
+        }
+        self.status
+    }
+
+    /// Does one piece of work: tries the moves of one anchor when
+    /// untangling, or finds one step otherwise.
+    fn advance(&mut self) {
+        match self.objective {
+            Objective::Untangle => self.advance_untangle(),
+            Objective::Connect { goal, player } if self.goal_connected(goal, player) => self.status = Status::Solved,
+            Objective::Connect { goal, player } => match self.next_connection(goal, player) {
+                Some((from, to)) => self.add_edge(Edge::with_props(
+                    from,
+                    to,
+                    EdgeProps { owner: Some(player), ..EdgeProps::default() },
+                )),
+                None => self.status = Status::Stuck,
+            },
+            Objective::MaxEdges => {
+                for (from, to) in planar::triangulate(&self.anchors, &self.edges) {
+                    self.add_edge(Edge::new(from, to));
+                }
+                self.status = Status::Solved;
+            }
+        }
+    }
+
+    fn add_edge(&mut self, edge: Edge) {
+        self.plan.push(Hint::Edge { from: edge.from, to: edge.to });
+        self.edges.push(edge);
+    }
+
+    fn advance_untangle(&mut self) {
+        if self.crossings == 0 {
+            self.status = Status::Solved;
+            return;
+        }
+        if self.cursor < self.anchors.len() {
+            let anchor = self.cursor;
+            self.cursor += 1;
+            let before = self.incident_crossings(anchor);
+            if before == 0 {
+                return;
+            }
+            let from = self.anchors[anchor].pos;
+            for to in self.spots(anchor) {
+                self.anchors[anchor].pos = to;
+                let gain = before.saturating_sub(self.incident_crossings(anchor));
+                if gain > self.best.map_or(0, |(best, _)| best) {
+                    self.best = Some((gain, Hint::Move { anchor, from, to }));
+                }
+            }
+            self.anchors[anchor].pos = from;
+            return;
+        }
+
+        // Every anchor has been tried, so make the best move and start over
+        self.cursor = 0;
+        match self.best.take() {
+            Some((gain, hint @ Hint::Move { anchor, to, .. })) => {
+                self.anchors[anchor].pos = to;
+                self.crossings -= gain;
+                self.plan.push(hint);
+            }
+            _ => self.status = Status::Stuck,
+        }
+    }
+
+    /// Counts the crossings of the edges at an anchor.
+    fn incident_crossings(&self, anchor: usize) -> usize {
+        let line = |edge: &Edge| LineSegment::new(self.anchors[edge.from].pos, self.anchors[edge.to].pos);
+        self.edges
+            .iter()
+            .filter(|edge| edge.touches(anchor))
+            .map(|edge| {
+                let incident = line(edge);
+                self.edges.iter().filter(|other| line(other).line_segments_intersect(&incident)).count()
+            })
+            .sum()
+    }
+
+    /// Returns the spots worth moving an anchor to: the middle of its
+    /// neighbours and a ring of spots around it, leaving out the ones too
+    /// close to other anchors or edges.
+    fn spots(&self, anchor: usize) -> Vec<Pos> {
+        let neighbours: Vec<Pos> = self
+            .edges
+            .iter()
+            .filter(|edge| edge.touches(anchor))
+            .map(|edge| self.anchors[if edge.from == anchor { edge.to } else { edge.from }].pos)
+            .collect();
+        if neighbours.is_empty() {
+            return Vec::new();
+        }
+        let count = neighbours.len() as f32;
+        let middle = neighbours.iter().fold(Pos::new(0.0, 0.0), |sum, pos| sum + *pos) * (1.0 / count);
+        let spread = neighbours.iter().map(|pos| pos.distance(&middle)).sum::<f32>() / count;
+
+        let ring = REACH.iter().flat_map(|reach| {
+            (0..DIRECTIONS).map(move |direction| {
+                let angle = direction as f32 / DIRECTIONS as f32 * std::f32::consts::TAU;
+                middle + Pos::new(angle.cos(), angle.sin()) * (spread * reach)
+            })
+        });
+        std::iter::once(middle)
+            .chain(ring)
+            .filter(|spot| {
+                let crowded = self.anchors.iter().enumerate().any(|(other, at)| {
+                    other != anchor && at.pos.distance(spot) < CLEARANCE
+                });
+                let on_edge = self.edges.iter().filter(|edge| !edge.touches(anchor)).any(|edge| {
+                    LineSegment::new(self.anchors[edge.from].pos, self.anchors[edge.to].pos).distance_to(spot)
+                        < CLEARANCE / 2.0
+                });
+                !crowded && !on_edge
+            })
+            .collect()
+    }
+
+    /// Returns the components joined by the player's own edges.
+    fn components(&self, player: u8) -> UnionFind {
+        let mut sets = UnionFind::new(self.anchors.len());
+        for edge in self.edges.iter().filter(|edge| edge.props.owner == Some(player)) {
+            sets.union(edge.from, edge.to);
+        }
+        sets
+    }
+
+    /// Returns `true` if the player's goal is connected, which holds
+    /// trivially when there are fewer than two groups to connect.
+    fn goal_connected(&self, goal: Goal, player: u8) -> bool {
+        game::targets(goal, player, &self.anchors).len() < 2
+            || game::winning_path(goal, player, &self.anchors, &self.edges).is_some()
+    }
+
+    /// Finds the shortest way to join the first group of the player's goal
+    /// to a group it is not connected to yet, over the player's edges and
+    /// edges that can be drawn, and returns the first new edge along it.
+    ///
+    /// New edges are measured by their length and the player's edges are
+    /// free, while other players' edges only stand in the way. An anchor
+    /// reached over a new edge can only be left over another new one if it
+    /// has room for both.
+    fn next_connection(&self, goal: Goal, player: u8) -> Option<(usize, usize)> {
+        let count = self.anchors.len();
+        let groups = game::targets(goal, player, &self.anchors);
+        let (first, rest) = groups.split_first()?;
+        let mut sets = self.components(player);
+        let sources: Vec<usize> = first.iter().map(|anchor| sets.find(*anchor)).collect();
+        let mut targets = Vec::new();
+        for group in rest {
+            let roots: Vec<usize> = group.iter().map(|anchor| sets.find(*anchor)).collect();
+            if !roots.iter().any(|root| sources.contains(root)) {
+                targets.extend(roots);
+            }
+        }
+
+        let mut degrees = vec![0; count];
+        let mut links: Vec<Vec<(usize, bool)>> = vec![Vec::new(); count];
+        for edge in &self.edges {
+            degrees[edge.from] += 1;
+            degrees[edge.to] += 1;
+            if edge.props.owner == Some(player) {
+                links[edge.from].push((edge.to, false));
+                links[edge.to].push((edge.from, false));
+            }
+        }
+        for (from, to) in planar::addable(&self.anchors, &self.edges) {
+            links[from].push((to, true));
+            links[to].push((from, true));
+        }
+
+        // Dijkstra without a heap, as boards are small and the graph is dense
+        let mut distance: Vec<f32> =
+            (0..count).map(|anchor| if sources.contains(&sets.find(anchor)) { 0.0 } else { f32::INFINITY }).collect();
+        // The anchor each one is reached from, and whether over a new edge
+        let mut previous: Vec<Option<(usize, bool)>> = vec![None; count];
+        let mut done = vec![false; count];
+        loop {
+            let current = (0..count)
+                .filter(|anchor| !done[*anchor] && distance[*anchor].is_finite())
+                .min_by(|a, b| distance[*a].total_cmp(&distance[*b]))?;
+            if targets.contains(&sets.find(current)) {
+                let mut first = None;
+                let mut anchor = current;
+                while let Some((from, new)) = previous[anchor] {
+                    if new {
+                        first = Some((from, anchor));
+                    }
+                    anchor = from;
+                }
+                return first;
+            }
+            done[current] = true;
+
+            let arrived_new = previous[current].is_some_and(|(_, new)| new);
+            for &(next, new) in &links[current] {
+                if new && arrived_new && !self.anchors[current].props.accepts_edge(degrees[current] + 1) {
+                    continue;
+                }
+                let length = if new { self.anchors[current].pos.distance(&self.anchors[next].pos) } else { 0.0 };
+                if distance[current] + length < distance[next] {
+                    distance[next] = distance[current] + length;
+                    previous[next] = Some((current, new));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anchor::{AnchorKind, AnchorProps};
+    use crate::levels::{self, Params};
+
+    fn anchors(positions: &[(f32, f32)]) -> Vec<Anchor> {
+        positions.iter().map(|&(x, y)| Anchor::new(Pos::new(x, y))).collect()
+    }
+
+    fn edges(pairs: &[(usize, usize)]) -> Vec<Edge> {
+        pairs.iter().map(|&(from, to)| Edge::new(from, to)).collect()
+    }
+
+    const CONNECT: Objective = Objective::Connect { goal: Goal::Bases, player: 0 };
+
+    fn run(solver: &mut Solver) -> Status {
+        while solver.search(Duration::ZERO) == Status::Searching {}
+        solver.status
+    }
+
+    #[test]
+    fn test_untangles_a_bowtie_in_one_move() {
+        // A square drawn as a bowtie, whose diagonals cross once
+        let anchors = anchors(&[(0.0, 0.0), (200.0, 0.0), (0.0, 200.0), (200.0, 200.0)]);
+        let edges = edges(&[(0, 1), (1, 2), (2, 3), (3, 0)]);
+        let mut solver = Solver::new(Objective::Untangle, &anchors, &edges);
+        assert_eq!(solver.search(Duration::ZERO), Status::Searching);
+        assert_eq!(solver.hint(), None);
+
+        assert_eq!(run(&mut solver), Status::Solved);
+        assert_eq!(solver.plan.len(), 1);
+        let Some(Hint::Move { anchor, from, to }) = solver.hint() else {
+            panic!("not a move");
+        };
+        assert_eq!(from, anchors[anchor].pos);
+        let mut moved = anchors.clone();
+        moved[anchor].pos = to;
+        assert_eq!(puzzle::crossings(&moved, &edges), 0);
+
+        assert!(!solver.is_stale(&anchors, &edges));
+        assert!(solver.is_stale(&moved, &edges));
+        assert!(solver.is_stale(&anchors, &edges[1..]));
+    }
+
+    #[test]
+    fn test_moves_only_remove_crossings() {
+        let level = levels::generate(Params { anchors: 12, density: 2.0, crossings: 12 }, 4);
+        let mut solver = Solver::new(Objective::Untangle, &level.board.anchors, &level.board.edges);
+        run(&mut solver);
+        assert!(!solver.plan.is_empty());
+
+        let mut board = level.board.clone();
+        let mut crossings = level.crossings;
+        for hint in &solver.plan {
+            let Hint::Move { anchor, from, to } = *hint else {
+                panic!("not a move");
+            };
+            assert_eq!(board.anchors[anchor].pos, from);
+            board.anchors[anchor].pos = to;
+            let now = puzzle::crossings(&board.anchors, &board.edges);
+            assert!(now < crossings);
+            crossings = now;
+        }
+        assert_eq!(crossings == 0, solver.status == Status::Solved);
+    }
+
+    #[test]
+    fn test_connects_bases_around_a_blocker() {
+        // Two bases with a blocker between them and a plain anchor off to the side
+        let mut anchors = anchors(&[(0.0, 0.0), (300.0, 0.0), (150.0, 0.0), (150.0, 100.0), (150.0, -400.0)]);
+        for base in &mut anchors[..2] {
+            base.props = AnchorProps { kind: AnchorKind::Base, owner: Some(0), ..AnchorProps::default() };
+        }
+        anchors[2].props.kind = AnchorKind::Blocker;
+        let mut solver = Solver::new(CONNECT, &anchors, &[]);
+        assert_eq!(run(&mut solver), Status::Solved);
+        assert_eq!(solver.plan, vec![Hint::Edge { from: 0, to: 3 }, Hint::Edge { from: 3, to: 1 }]);
+
+        // With no room left at the detour, the far anchor has to do
+        anchors[3].props.capacity = Some(1);
+        let mut solver = Solver::new(CONNECT, &anchors, &[]);
+        assert_eq!(run(&mut solver), Status::Solved);
+        assert_eq!(solver.plan, vec![Hint::Edge { from: 0, to: 4 }, Hint::Edge { from: 4, to: 1 }]);
+
+        // Without any detour the bases stay apart
+        anchors[3].props.kind = AnchorKind::Blocker;
+        anchors[4].props.kind = AnchorKind::Blocker;
+        let mut solver = Solver::new(CONNECT, &anchors, &[]);
+        assert_eq!(run(&mut solver), Status::Stuck);
+        assert!(solver.plan.is_empty());
+    }
+
+    #[test]
+    fn test_connects_only_with_own_edges() {
+        let mut anchors = anchors(&[(0.0, 0.0), (300.0, 0.0), (140.0, -200.0), (150.0, 200.0), (80.0, 100.0)]);
+        for (base, owner) in [(0, 0), (1, 0), (2, 1), (3, 1)] {
+            anchors[base].props = AnchorProps { kind: AnchorKind::Base, owner: Some(owner), ..AnchorProps::default() };
+        }
+        // The second player's edges join the first player's bases, and cut
+        // across the straight way between their own
+        let owned = EdgeProps { owner: Some(1), ..EdgeProps::default() };
+        let edges = vec![Edge::with_props(0, 4, owned.clone()), Edge::with_props(4, 1, owned)];
+
+        let mut solver = Solver::new(CONNECT, &anchors, &edges);
+        assert_eq!(run(&mut solver), Status::Solved);
+        assert_eq!(solver.plan, vec![Hint::Edge { from: 0, to: 1 }]);
+        assert_eq!(solver.edges[2].props.owner, Some(0));
+
+        // The second player goes around, over their own edges for free
+        let mut solver = Solver::new(CONNECT.for_player(Goal::Bases, 1), &anchors, &edges);
+        assert_eq!(run(&mut solver), Status::Solved);
+        assert_eq!(solver.plan, vec![Hint::Edge { from: 2, to: 0 }, Hint::Edge { from: 4, to: 3 }]);
+
+        // Across the board, the second player plays from bottom to top
+        let mut solver = Solver::new(CONNECT.for_player(Goal::Sides, 1), &anchors, &edges);
+        assert_eq!(run(&mut solver), Status::Solved);
+        assert_eq!(solver.plan, vec![Hint::Edge { from: 2, to: 0 }, Hint::Edge { from: 4, to: 3 }]);
+        assert!(game::winning_path(Goal::Sides, 1, &anchors, &solver.edges).is_some());
+
+        // Nothing else changes for the other objectives
+        assert_eq!(Objective::Untangle.for_player(Goal::Sides, 1), Objective::Untangle);
+    }
+
+    #[test]
+    fn test_most_edges_of_square_with_centre() {
+        let anchors = anchors(&[(0.0, 0.0), (100.0, 0.0), (100.0, 100.0), (0.0, 100.0), (50.0, 50.0)]);
+        let existing = edges(&[(0, 1)]);
+        let mut solver = Solver::new(Objective::MaxEdges, &anchors, &existing);
+        assert_eq!(run(&mut solver), Status::Solved);
+        // Four sides and four spokes, one side of which is there already
+        assert_eq!(solver.plan.len(), 7);
+        assert!(solver.plan.iter().all(|hint| matches!(hint, Hint::Edge { .. })));
+        assert!(planar::addable(&anchors, &solver.edges).is_empty());
+    }
+}
@@ -0,0 +1,232 @@
+use crate::analysis::UnionFind;
+use crate::anchor::AnchorKind;
+use crate::edge::Edge;
+use crate::{Anchor, Pos};
+
+/// Which anchors a spanning challenge asks to connect.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Terminals {
+    /// Every anchor that takes edges
+    All,
+    /// Only the bases, with the other anchors free to route through
+    Bases,
+}
+
+impl Terminals {
+    /// All choices, in the order they are offered in the UI.
+    pub(crate) const ALL: [Terminals; 2] = [Terminals::All, Terminals::Bases];
+
+    /// Returns the name shown in the UI.
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Terminals::All => "All anchors",
+            Terminals::Bases => "Bases only",
+        }
+    }
+
+    /// Returns the indices of the anchors to connect.
+    pub(crate) fn pick(self, anchors: &[Anchor]) -> Vec<usize> {
+        (0..anchors.len())
+            .filter(|index| match self {
+                Terminals::All => anchors[*index].props.kind != AnchorKind::Blocker,
+                Terminals::Bases => anchors[*index].props.kind == AnchorKind::Base,
+            })
+            .collect()
+    }
+}
+
+/// A challenge to connect the terminals with edges as short as possible in
+/// total.
+///
+/// The target is the Euclidean minimum spanning tree of the terminals, which
+/// never crosses itself. When only some anchors are terminals, routing
+/// through the others as Steiner points can beat it.
+#[derive(Clone, Debug)]
+pub(crate) struct Challenge {
+    pub(crate) terminals: Vec<usize>,
+    /// The pairs of anchors joined by the minimum spanning tree
+    pub(crate) optimal: Vec<(usize, usize)>,
+    /// The total length of the minimum spanning tree
+    pub(crate) optimal_length: f32,
+    /// Whether the minimum spanning tree is drawn on the board
+    pub(crate) revealed: bool,
+    /// Which anchors were picked as terminals
+    choice: Terminals,
+    /// The position and kind of every anchor the optimum was computed on
+    layout: Vec<(Pos, AnchorKind)>,
+}
+
+impl Challenge {
+    /// Sets up a challenge on the given anchors.
+    ///
+    /// # Returns
+    /// The challenge, or `None` if there are fewer than two terminals
+    pub(crate) fn new(anchors: &[Anchor], choice: Terminals) -> Option<Self> {
+        let terminals = choice.pick(anchors);
+        if terminals.len() < 2 {
+            return None;
+        }
+        let positions: Vec<Pos> = terminals.iter().map(|index| anchors[*index].pos).collect();
+        let optimal: Vec<(usize, usize)> = minimum_spanning_tree(&positions)
+            .into_iter()
+            .map(|(from, to)| (terminals[from], terminals[to]))
+            .collect();
+        let optimal_length = optimal.iter().map(|&(from, to)| anchors[from].pos.distance(&anchors[to].pos)).sum();
+        Some(Self {
+            terminals,
+            optimal,
+            optimal_length,
+            revealed: false,
+            choice,
+            layout: layout(anchors),
+        })
+    }
+
+    /// Picks the terminals and computes the optimum again if anchors were
+    /// moved, added, removed or changed kind since, keeping whether the
+    /// optimum is revealed.
+    ///
+    /// # Returns
+    /// `false` if fewer than two terminals are left
+    pub(crate) fn recheck(&mut self, anchors: &[Anchor]) -> bool {
+        if self.layout == layout(anchors) {
+            return true;
+        }
+        match Self::new(anchors, self.choice) {
+            Some(challenge) => {
+                *self = Self { revealed: self.revealed, ..challenge };
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns `true` if the edges connect all terminals.
+    pub(crate) fn is_connected(&self, anchors: &[Anchor], edges: &[Edge]) -> bool {
+        if self.terminals.iter().any(|terminal| *terminal >= anchors.len()) {
+            return false;
+        }
+        let mut sets = UnionFind::new(anchors.len());
+        for edge in edges {
+            sets.union(edge.from, edge.to);
+        }
+        let root = sets.find(self.terminals[0]);
+        self.terminals.iter().all(|terminal| sets.find(*terminal) == root)
+    }
+
+    /// Returns how the given total length compares to the optimum, where 1.0
+    /// is optimal and more is worse.
+    pub(crate) fn score(&self, length: f32) -> f32 {
+        if self.optimal_length > 0.0 {
+            length / self.optimal_length
+        } else {
+            1.0
+        }
+    }
+}
+
+fn layout(anchors: &[Anchor]) -> Vec<(Pos, AnchorKind)> {
+    anchors.iter().map(|anchor| (anchor.pos, anchor.props.kind)).collect()
+}
+
+/// Returns the summed length of all edges.
+pub(crate) fn total_length(anchors: &[Anchor], edges: &[Edge]) -> f32 {
+    edges.iter().map(|edge| anchors[edge.from].pos.distance(&anchors[edge.to].pos)).sum()
+}
+
+/// Finds the Euclidean minimum spanning tree with Prim's algorithm on the
+/// complete graph, which takes quadratic time but no extra structures.
+///
+/// # Returns
+/// The pairs of indices into `positions` joined by the tree
+pub(crate) fn minimum_spanning_tree(positions: &[Pos]) -> Vec<(usize, usize)> {
+    let count = positions.len();
+    if count == 0 {
+        return Vec::new();
+    }
+    let mut in_tree = vec![false; count];
+    // The closest tree point of every point outside the tree, and how far it is
+    let mut closest: Vec<(f32, usize)> = positions.iter().map(|pos| (pos.distance(&positions[0]), 0)).collect();
+    in_tree[0] = true;
+
+    let mut pairs = Vec::with_capacity(count - 1);
+    for _ in 1..count {
+        let next = (0..count)
+            .filter(|index| !in_tree[*index])
+            .min_by(|a, b| closest[*a].0.total_cmp(&closest[*b].0))
+            .unwrap();
+        in_tree[next] = true;
+        pairs.push((closest[next].1, next));
+        for other in (0..count).filter(|index| !in_tree[*index]) {
+            let distance = positions[next].distance(&positions[other]);
+            if distance < closest[other].0 {
+                closest[other] = (distance, next);
+            }
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anchor::AnchorProps;
+
+    fn anchors(positions: &[(f32, f32)]) -> Vec<Anchor> {
+        positions.iter().map(|&(x, y)| Anchor::new(Pos::new(x, y))).collect()
+    }
+
+    #[test]
+    fn test_minimum_spanning_tree_of_square_with_centre() {
+        let anchors = anchors(&[(0.0, 0.0), (100.0, 0.0), (100.0, 100.0), (0.0, 100.0), (50.0, 50.0)]);
+        let challenge = Challenge::new(&anchors, Terminals::All).unwrap();
+        // Every corner joins the centre
+        assert_eq!(challenge.optimal.len(), 4);
+        assert!(challenge.optimal.iter().all(|&(from, to)| from == 4 || to == 4));
+        assert!((challenge.optimal_length - 4.0 * 50.0 * 2f32.sqrt()).abs() < 1e-3);
+
+        let mut edges: Vec<Edge> = (0..3).map(|corner| Edge::new(corner, corner + 1)).collect();
+        assert!(!challenge.is_connected(&anchors, &edges));
+        edges.push(Edge::new(3, 4));
+        assert!(challenge.is_connected(&anchors, &edges));
+        let score = challenge.score(total_length(&anchors, &edges));
+        assert!(score > 1.0 && score < 1.4);
+    }
+
+    #[test]
+    fn test_steiner_points_beat_the_terminal_tree() {
+        // Three bases on a triangle and a plain anchor in its middle
+        let mut anchors = anchors(&[(0.0, 0.0), (100.0, 0.0), (50.0, 86.6), (50.0, 28.9)]);
+        for base in &mut anchors[..3] {
+            base.props = AnchorProps { kind: AnchorKind::Base, ..AnchorProps::default() };
+        }
+        let challenge = Challenge::new(&anchors, Terminals::Bases).unwrap();
+        assert_eq!(challenge.terminals, vec![0, 1, 2]);
+        assert_eq!(challenge.optimal.len(), 2);
+
+        let star: Vec<Edge> = (0..3).map(|base| Edge::new(base, 3)).collect();
+        assert!(challenge.is_connected(&anchors, &star));
+        assert!(challenge.score(total_length(&anchors, &star)) < 1.0);
+
+        // Moving a base moves the optimum with it
+        let mut moved = challenge.clone();
+        moved.revealed = true;
+        anchors[2].pos = Pos::new(50.0, 200.0);
+        assert!(moved.recheck(&anchors));
+        assert!(moved.revealed);
+        assert!(moved.optimal_length > challenge.optimal_length);
+
+        // Removing an anchor renumbers the terminals
+        let mut removed = challenge.clone();
+        anchors.remove(0);
+        assert!(removed.recheck(&anchors));
+        assert_eq!(removed.terminals, vec![0, 1]);
+        assert_eq!(removed.optimal, vec![(0, 1)]);
+
+        // A single base leaves nothing to connect
+        assert!(!removed.clone().recheck(&anchors[1..]));
+        anchors[1].props.kind = AnchorKind::Neutral;
+        anchors[2].props.kind = AnchorKind::Neutral;
+        assert!(Challenge::new(&anchors, Terminals::Bases).is_none());
+    }
+}
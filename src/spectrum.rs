@@ -0,0 +1,239 @@
+use ringbuf::Consumer;
+use std::f32::consts::PI;
+
+/// Number of recent samples kept for the oscilloscope and the FFT.
+pub const WINDOW_SIZE: usize = 512;
+/// Number of logarithmic frequency bands the spectrum is reduced to.
+pub const BANDS: usize = 32;
+/// Capacity of the ring buffer between the audio callback and the analyser.
+pub const TAP_CAPACITY: usize = 8192;
+
+/// How fast the amplitude follows a rising and a falling level per frame.
+const ATTACK: f32 = 0.6;
+const RELEASE: f32 = 0.12;
+
+/// Turns the samples tapped from the audio callback into per-frame
+/// amplitude and spectrum values for `view`.
+#[derive(Debug)]
+pub struct Analyser {
+    /// The most recent samples, oldest first
+    samples: Vec<f32>,
+    /// Smoothed RMS level of the most recent samples
+    amplitude: f32,
+    /// Smoothed magnitude of each frequency band
+    spectrum: Vec<f32>,
+}
+
+impl Analyser {
+    /// Creates an analyser that reports silence.
+    pub fn new() -> Self {
+        Self {
+            samples: vec![0.0; WINDOW_SIZE],
+            amplitude: 0.0,
+            spectrum: vec![0.0; BANDS],
+        }
+    }
+
+    /// Drains all samples the audio callback produced since the last frame.
+    ///
+    /// # Arguments
+    /// * `tap` - Consumer end of the ring buffer filled by the audio callback
+    pub fn feed(&mut self, tap: &mut Consumer<f32>) {
+        let mut fresh = Vec::with_capacity(tap.len());
+        tap.pop_each(
+            |sample| {
+                fresh.push(sample);
+                true
+            },
+            None,
+        );
+        self.push_samples(&fresh);
+    }
+
+    /// Lets the levels fall back to zero while no audio is playing.
+    pub fn silence(&mut self) {
+        self.push_samples(&[0.0; WINDOW_SIZE / 8]);
+    }
+
+    /// Appends samples to the window and recomputes amplitude and spectrum.
+    ///
+    /// # Arguments
+    /// * `fresh` - The samples to append, oldest first
+    pub fn push_samples(&mut self, fresh: &[f32]) {
+        if fresh.is_empty() {
+            return;
+        }
+
+        let keep = fresh.len().min(WINDOW_SIZE);
+        self.samples.rotate_left(keep);
+        self.samples[WINDOW_SIZE - keep..].copy_from_slice(&fresh[fresh.len() - keep..]);
+
+        let rms = (fresh.iter().map(|s| s * s).sum::<f32>() / fresh.len() as f32).sqrt();
+        self.amplitude = smooth(self.amplitude, rms);
+
+        for (band, level) in self.spectrum.iter_mut().zip(bands(&magnitudes(&self.samples))) {
+            *band = smooth(*band, level);
+        }
+    }
+
+    /// Returns the smoothed RMS level of the output.
+    pub fn amplitude(&self) -> f32 {
+        self.amplitude
+    }
+
+    /// Returns the smoothed level of each of the `BANDS` frequency bands, lowest first.
+    pub fn spectrum(&self) -> &[f32] {
+        &self.spectrum
+    }
+
+    /// Returns the most recent `WINDOW_SIZE` samples, oldest first.
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+}
+
+impl Default for Analyser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn smooth(current: f32, target: f32) -> f32 {
+    let rate = if target > current { ATTACK } else { RELEASE };
+    current + (target - current) * rate
+}
+
+/// In-place iterative radix-2 FFT.
+///
+/// # Arguments
+/// * `re` - Real parts, replaced by the real parts of the transform
+/// * `im` - Imaginary parts, replaced by the imaginary parts of the transform
+///
+/// Both slices must have the same power-of-two length.
+pub fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    assert!(n.is_power_of_two() && im.len() == n, "fft needs two slices of the same power-of-two length");
+
+    // Bit-reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (w_im, w_re) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * w_re - im[b] * w_im;
+                let t_im = re[b] * w_im + im[b] * w_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Returns the magnitude of the first half of the spectrum of a Hann-windowed signal.
+///
+/// # Arguments
+/// * `samples` - A power-of-two number of samples
+pub fn magnitudes(samples: &[f32]) -> Vec<f32> {
+    let n = samples.len();
+    let mut re: Vec<f32> = samples
+        .iter()
+        .enumerate()
+        .map(|(i, s)| s * 0.5 * (1.0 - (2.0 * PI * i as f32 / n as f32).cos()))
+        .collect();
+    let mut im = vec![0.0; n];
+    fft(&mut re, &mut im);
+
+    re.iter()
+        .zip(&im)
+        .take(n / 2)
+        .map(|(re, im)| (re * re + im * im).sqrt() * 2.0 / n as f32)
+        .collect()
+}
+
+/// Groups FFT magnitudes into `BANDS` logarithmically spaced bands.
+fn bands(magnitudes: &[f32]) -> Vec<f32> {
+    let bins = magnitudes.len().max(1) as f32;
+    (0..BANDS)
+        .map(|band| {
+            let lo = bins.powf(band as f32 / BANDS as f32) as usize;
+            let hi = (bins.powf((band + 1) as f32 / BANDS as f32) as usize).max(lo + 1);
+            magnitudes[lo.min(magnitudes.len())..hi.min(magnitudes.len())]
+                .iter()
+                .copied()
+                .fold(0.0, f32::max)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(bin: usize) -> Vec<f32> {
+        (0..WINDOW_SIZE)
+            .map(|i| (2.0 * PI * bin as f32 * i as f32 / WINDOW_SIZE as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_fft_finds_sine_frequency() {
+        let spectrum = magnitudes(&sine(40));
+        let peak = spectrum
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(bin, _)| bin);
+        assert_eq!(peak, Some(40));
+    }
+
+    #[test]
+    fn test_fft_of_silence_is_zero() {
+        assert!(magnitudes(&[0.0; WINDOW_SIZE]).iter().all(|m| *m == 0.0));
+    }
+
+    #[test]
+    fn test_analyser_amplitude_rises_and_decays() {
+        let mut analyser = Analyser::new();
+        assert_eq!(analyser.amplitude(), 0.0);
+
+        analyser.push_samples(&sine(10));
+        let loud = analyser.amplitude();
+        assert!(loud > 0.3);
+
+        for _ in 0..50 {
+            analyser.silence();
+        }
+        assert!(analyser.amplitude() < loud * 0.01);
+    }
+
+    #[test]
+    fn test_analyser_feeds_from_ring_buffer() {
+        let (mut producer, mut consumer) = ringbuf::RingBuffer::<f32>::new(TAP_CAPACITY).split();
+        producer.push_slice(&sine(10));
+
+        let mut analyser = Analyser::new();
+        analyser.feed(&mut consumer);
+        assert!(consumer.is_empty());
+        assert_eq!(analyser.samples().len(), WINDOW_SIZE);
+        assert!(analyser.spectrum().iter().any(|band| *band > 0.0));
+    }
+}